@@ -1,6 +1,6 @@
 use std::{env, path::{PathBuf}};
 
-use pewter::pe::{PEFile, definition::PEImageDef, sections::{SectionFlags}, optional_header::data_directories::ImageDataDirectory};
+use pewter::pe::{PEFile, definition::PEImageDef, sections::{SectionFlags}, optional_header::data_directories::{ImageDataDirectory, SectionName}};
 
 fn main() {
     let mut args = env::args();
@@ -26,6 +26,8 @@ fn main() {
             virtual_address: dotnet_vaddr as u32,
             size: 6,
         };  
+        // The original file may not have had room for the CLR header directory.
+        def.optional_header.ensure_directory_count(SectionName::ClrRuntimeHeader as u32 + 1);
         
         let pacthed_data = def.write_file().unwrap();
 
@@ -11,6 +11,7 @@ fn main() {
 
     let opt = Options {
         parse_special_sections: ParseSectionFlags::NONE,
+        ..Default::default()
     };
 
     let pe = PEFile::parse_with_options(&data, opt).unwrap();
@@ -18,6 +18,12 @@ impl<T> Table<T> {
     pub fn with_capacity(capacity: usize) -> Self {
         Self(Vec::with_capacity(capacity))
     }
+
+    /// The number of entries in the table.
+    #[inline(always)]
+    pub fn count(&self) -> usize {
+        self.0.len()
+    }
 }
 
 impl<T: ReadData> Table<T> {
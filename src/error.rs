@@ -1,13 +1,35 @@
 #[cfg(feature = "std")]
-use crate::alloc_containers::error::Error;
+use crate::alloc_containers::{error::Error, io};
 
 pub type Result<T> = core::result::Result<T, PewterError>;
 
 #[derive(Debug)]
 pub enum PewterError {
-    NotEnoughDataLeft { attempted_read: usize },
-    NotEnoughSpaceLeft { attempted_write: usize },
-    InvalidImageFormat { message: &'static str },
+    NotEnoughDataLeft {
+        attempted_read: usize,
+        /// The file offset being read, if known.
+        offset: Option<usize>,
+    },
+    NotEnoughSpaceLeft {
+        attempted_write: usize,
+    },
+    /// A table has more entries than allowed by [`Options`](crate::Options).
+    LimitExceeded {
+        /// What was being counted.
+        limit_name: &'static str,
+        limit: usize,
+    },
+    /// [`Writer::pad_to`](crate::io::Writer::pad_to) was used on a writer that doesn't know its position.
+    UnknownPosition,
+    InvalidImageFormat {
+        message: &'static str,
+        /// The file offset of the invalid data, if known.
+        offset: Option<usize>,
+    },
+    #[cfg(feature = "std")]
+    Io {
+        source: io::Error,
+    },
 }
 
 impl PewterError {
@@ -15,6 +37,7 @@ impl PewterError {
     pub const fn not_enough_data(size: usize) -> Self {
         Self::NotEnoughDataLeft {
             attempted_read: size,
+            offset: None,
         }
     }
     #[cold]
@@ -24,33 +47,86 @@ impl PewterError {
         }
     }
     #[cold]
+    pub const fn limit_exceeded(limit_name: &'static str, limit: usize) -> Self {
+        Self::LimitExceeded { limit_name, limit }
+    }
+    #[cold]
     pub const fn invalid_image_format(message: &'static str) -> Self {
-        Self::InvalidImageFormat { message }
+        Self::InvalidImageFormat {
+            message,
+            offset: None,
+        }
+    }
+    #[cold]
+    pub const fn invalid_image_format_at(message: &'static str, offset: usize) -> Self {
+        Self::InvalidImageFormat {
+            message,
+            offset: Some(offset),
+        }
+    }
+
+    /// Sets the file offset of the error, if it doesn't already have one.
+    ///
+    /// Offsets are set from the innermost parser outward, so the most precise one is kept.
+    #[cold]
+    pub fn with_offset(mut self, file_offset: usize) -> Self {
+        match &mut self {
+            Self::NotEnoughDataLeft { offset, .. } | Self::InvalidImageFormat { offset, .. } => {
+                offset.get_or_insert(file_offset);
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// The file offset of the error, if known.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::NotEnoughDataLeft { offset, .. } | Self::InvalidImageFormat { offset, .. } => {
+                *offset
+            }
+            _ => None,
+        }
     }
 }
 
 impl core::fmt::Display for PewterError {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
-            Self::InvalidImageFormat { message } => write!(f, "Invalid image format: {}", message),
-            Self::NotEnoughDataLeft { attempted_read } => write!(
+            Self::InvalidImageFormat { message, .. } => {
+                write!(f, "Invalid image format: {}", message)?
+            }
+            Self::NotEnoughDataLeft { attempted_read, .. } => write!(
                 f,
                 "Attempted to read {} bytes but there was not enough data.",
                 attempted_read
-            ),
+            )?,
             Self::NotEnoughSpaceLeft { attempted_write } => write!(
                 f,
                 "Attempted to write {} bytes but there was not enough space.",
                 attempted_write
-            ),
+            )?,
+            Self::LimitExceeded { limit_name, limit } => {
+                write!(f, "More than the limit of {} {}.", limit, limit_name)?
+            }
+            Self::UnknownPosition => write!(f, "The writer does not know its position.")?,
+            #[cfg(feature = "std")]
+            Self::Io { source } => write!(f, "IO error: {}", source)?,
         }
+        if let Some(offset) = self.offset() {
+            write!(f, " (at 0x{:X})", offset)?;
+        }
+        Ok(())
     }
 }
 
 #[cfg(feature = "std")]
 impl Error for PewterError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        None
+        match self {
+            Self::Io { source } => Some(source),
+            _ => None,
+        }
     }
 
     fn description(&self) -> &str {
@@ -61,3 +137,10 @@ impl Error for PewterError {
         self.source()
     }
 }
+
+#[cfg(feature = "std")]
+impl From<io::Error> for PewterError {
+    fn from(source: io::Error) -> Self {
+        Self::Io { source }
+    }
+}
@@ -0,0 +1,57 @@
+//! [`Writer`] adapter for [`std::io::Write`].
+use crate::{alloc_containers::io::Write, error::Result};
+
+use super::Writer;
+
+/// Writes straight to any [`std::io::Write`], like a file,
+/// without collecting the output in a `Vec` first.
+///
+/// IO errors are returned as [`PewterError::Io`](crate::error::PewterError::Io).
+#[derive(Debug)]
+pub struct IoWriter<W: Write>(pub W);
+
+impl<W: Write> IoWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self(inner)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+impl<W: Write> Writer for IoWriter<W> {
+    #[inline(always)]
+    fn write_slice(&mut self, data: &[u8]) -> Result<()> {
+        self.0.write_all(data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        io::{ReadData, WriteData},
+        pe::coff::{CoffFileHeader, ImageFileMachine},
+        vec::Vec,
+    };
+
+    #[test]
+    fn write_header_through_adapter() {
+        let header = CoffFileHeader {
+            machine: ImageFileMachine::Amd64,
+            number_of_sections: 3,
+            ..Default::default()
+        };
+        let mut writer = IoWriter::new(Vec::new());
+        header.write_to(&mut writer).unwrap();
+
+        let written = writer.into_inner();
+        assert_eq!(written.len(), CoffFileHeader::SIZE);
+        assert_eq!(
+            CoffFileHeader::read(&mut written.as_slice()).unwrap(),
+            header
+        );
+    }
+}
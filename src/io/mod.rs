@@ -1,5 +1,6 @@
+#[cfg(feature = "std")]
+pub mod io_writer;
 pub mod stream;
-use core::mem::MaybeUninit;
 
 use crate::error::{PewterError, Result};
 
@@ -9,6 +10,20 @@ pub trait Reader: Sized {
     fn read<T: ReadData>(&mut self) -> Result<T> {
         T::read(self)
     }
+    /// Read a value without advancing the reader.
+    fn peek<T: ReadData>(&mut self) -> Result<T>;
+
+    /// Read a `u16` length, then that many bytes.
+    fn read_prefixed_u16(&mut self) -> Result<&[u8]> {
+        let size: u16 = self.read()?;
+        self.read_slice(size as usize)
+    }
+
+    /// Read a `u32` length, then that many bytes.
+    fn read_prefixed_u32(&mut self) -> Result<&[u8]> {
+        let size: u32 = self.read()?;
+        self.read_slice(size as usize)
+    }
 }
 
 pub trait Writer: Sized {
@@ -17,6 +32,36 @@ pub trait Writer: Sized {
     fn write<T: WriteData>(&mut self, value: T) -> Result<()> {
         T::write_to(value, self)
     }
+
+    /// The number of bytes from the start of the output, if the writer keeps track of it.
+    fn position(&self) -> Option<usize> {
+        None
+    }
+
+    /// Write `count` zero bytes.
+    fn write_zeros(&mut self, count: usize) -> Result<()> {
+        const ZEROS: [u8; 512] = [0; 512];
+        let mut remaining = count;
+        while remaining > 0 {
+            let chunk = remaining.min(ZEROS.len());
+            self.write_slice(&ZEROS[..chunk])?;
+            remaining -= chunk;
+        }
+        Ok(())
+    }
+
+    /// Write zeros until [`Writer::position`] is a multiple of `boundary`.
+    ///
+    /// Fails with [`PewterError::UnknownPosition`] if the writer doesn't know its position.
+    fn pad_to(&mut self, boundary: usize) -> Result<()> {
+        let position = self.position().ok_or(PewterError::UnknownPosition)?;
+        let aligned = if boundary == 0 {
+            position
+        } else {
+            position.next_multiple_of(boundary)
+        };
+        self.write_zeros(aligned - position)
+    }
 }
 
 impl<'a> Reader for &'a [u8] {
@@ -29,9 +74,15 @@ impl<'a> Reader for &'a [u8] {
         *self = remaining;
         Ok(data)
     }
+
+    #[inline(always)]
+    fn peek<T: ReadData>(&mut self) -> Result<T> {
+        let mut peek_reader = *self;
+        T::read(&mut peek_reader)
+    }
 }
 
-impl<'a> Writer for &'a mut [u8] {
+impl Writer for &mut [u8] {
     #[inline(always)]
     fn write_slice(&mut self, data: &[u8]) -> Result<()> {
         if self.len() < data.len() {
@@ -43,6 +94,17 @@ impl<'a> Writer for &'a mut [u8] {
         write_buffer.copy_from_slice(data);
         Ok(())
     }
+
+    fn write_zeros(&mut self, count: usize) -> Result<()> {
+        if self.len() < count {
+            return Err(PewterError::not_enough_space(count));
+        }
+        let this = core::mem::take(self);
+        let (write_buffer, remaining) = this.split_at_mut(count);
+        *self = remaining;
+        write_buffer.fill(0);
+        Ok(())
+    }
 }
 
 impl Writer for crate::vec::Vec<u8> {
@@ -51,8 +113,26 @@ impl Writer for crate::vec::Vec<u8> {
         self.extend_from_slice(data);
         Ok(())
     }
+
+    #[inline(always)]
+    fn position(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn write_zeros(&mut self, count: usize) -> Result<()> {
+        self.resize(self.len() + count, 0);
+        Ok(())
+    }
 }
 
+/// `fast-rw` copies values straight out of the buffer instead of decoding them byte by byte.
+/// The buffer is only byte aligned, so values are always copied with `read_unaligned`.
+/// The tests in this module should pass under Miri with the feature enabled.
+/// That is only correct when the target is little-endian like the PE format, so big-endian
+/// targets always use the portable path, even with the feature enabled.
+/// Byte arrays have no byte order and always use the fast path when the feature is enabled.
+pub(crate) const FAST_RW: bool = cfg!(all(target_endian = "little", feature = "fast-rw"));
+
 pub trait ReadData: Sized {
     fn read(reader: &mut impl Reader) -> Result<Self>;
 }
@@ -66,7 +146,8 @@ impl<const N: usize> ReadData for [u8; N] {
     fn read(reader: &mut impl Reader) -> Result<Self> {
         if cfg!(feature = "fast-rw") {
             let slice = reader.read_slice(N)?;
-            unsafe { Ok(*slice.as_ptr().cast()) }
+            // SAFETY: `read_slice` returned `N` bytes, and `read_unaligned` has no alignment requirement.
+            unsafe { Ok(slice.as_ptr().cast::<[u8; N]>().read_unaligned()) }
         } else {
             reader
                 .read_slice(N)?
@@ -85,27 +166,31 @@ impl<const N: usize> WriteData for [u8; N] {
 impl<const N: usize> ReadData for [u16; N] {
     #[cfg_attr(all(target_endian = "little", feature = "fast-rw"), inline(always))]
     fn read(reader: &mut impl Reader) -> Result<Self> {
-        let mut read_buffer = reader.read_slice(core::mem::size_of::<u16>() * N)?;
+        let read_buffer = reader.read_slice(core::mem::size_of::<u16>() * N)?;
 
-        if cfg!(all(target_endian = "little", feature = "fast-rw")) {
-            Ok(unsafe { *read_buffer.as_ptr().cast() })
+        if FAST_RW {
+            // SAFETY: `read_buffer` is `2 * N` bytes, and `read_unaligned` has no alignment requirement.
+            // Slices of the file are only byte aligned, so the `[u16; N]` can't be read through a reference.
+            Ok(unsafe { read_buffer.as_ptr().cast::<[u16; N]>().read_unaligned() })
         } else {
-            let mut write_buffer: [MaybeUninit<u16>; N] =
-                unsafe { MaybeUninit::uninit().assume_init() };
-            let read_ptr = &mut read_buffer;
-
-            for elem in write_buffer.iter_mut() {
-                elem.write(u16::read(read_ptr)?);
-            }
-
-            Ok(unsafe { *write_buffer.as_ptr().cast() })
+            read_u16_array_portable(read_buffer)
         }
     }
 }
+
+/// Decodes little-endian `u16`s one at a time, regardless of the target's byte order.
+fn read_u16_array_portable<const N: usize>(mut read_buffer: &[u8]) -> Result<[u16; N]> {
+    let mut values = [0u16; N];
+    for value in values.iter_mut() {
+        *value = u16::from_le_bytes(read_buffer.read()?);
+    }
+    Ok(values)
+}
+
 impl<const N: usize> WriteData for [u16; N] {
     #[cfg_attr(all(target_endian = "little", feature = "fast-rw"), inline(always))]
     fn write_to(self, writer: &mut impl Writer) -> Result<()> {
-        if cfg!(all(target_endian = "little", feature = "fast-rw")) {
+        if FAST_RW {
             let data_ptr: *const u8 = self.as_ptr().cast();
             let data_slice = unsafe { core::slice::from_raw_parts(data_ptr, N * 2) };
             writer.write_slice(data_slice)?;
@@ -158,15 +243,33 @@ impl WriteData for u8 {
     }
 }
 
+/// A single byte, where any nonzero value is `true`.
+///
+/// PE rarely stores a bool on its own (flags are usually packed into bit fields), so this is
+/// mostly for convenience structs.
+impl ReadData for bool {
+    fn read(reader: &mut impl Reader) -> Result<Self> {
+        reader.read::<u8>().map(|b| b != 0)
+    }
+}
+
+impl WriteData for bool {
+    fn write_to(self, writer: &mut impl Writer) -> Result<()> {
+        writer.write(self as u8)
+    }
+}
+
 macro_rules! impl_read_write_data {
     ($($t:ty),+) => {
         $(
             impl ReadData for $t {
                 #[inline(always)]
                 fn read(reader: &mut impl Reader) -> Result<$t> {
-                    if cfg!(all(target_endian = "little", feature = "fast-rw")) {
+                    if FAST_RW {
                         let read_buffer = reader.read_slice(core::mem::size_of::<$t>())?;
-                        Ok(unsafe { *read_buffer.as_ptr().cast() })
+                        // SAFETY: `read_buffer` is exactly the size of `$t`, and `read_unaligned`
+                        // has no alignment requirement.
+                        Ok(unsafe { read_buffer.as_ptr().cast::<$t>().read_unaligned() })
                     }else {
                         let data = reader.read();
                         data.map(<$t>::from_le_bytes)
@@ -188,5 +291,112 @@ macro_rules! impl_read_write_data {
 impl_read_write_data! {
     u16,
     u32,
-    u64
+    u64,
+    i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn portable_u16_array_read_is_little_endian() {
+        let data = [0x01, 0x02, 0x03, 0x04];
+        assert_eq!(
+            read_u16_array_portable::<2>(&data).unwrap(),
+            [0x0201, 0x0403]
+        );
+        assert_eq!(
+            <[u16; 2]>::read(&mut data.as_slice()).unwrap(),
+            read_u16_array_portable::<2>(&data).unwrap()
+        );
+        assert!(read_u16_array_portable::<3>(&data).is_err());
+    }
+
+    #[test]
+    fn fast_rw_reads_are_unaligned() {
+        // Offset by one so the values can't be aligned for `u16` and larger.
+        let data = [0u8, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09];
+        let misaligned = &data[1..];
+        assert_eq!(
+            <[u16; 2]>::read(&mut &misaligned[..4]).unwrap(),
+            [0x0201, 0x0403]
+        );
+        assert_eq!(<[u8; 3]>::read(&mut &misaligned[..3]).unwrap(), [1, 2, 3]);
+        assert_eq!(u32::read(&mut &misaligned[..4]).unwrap(), 0x04030201);
+        assert_eq!(
+            u64::read(&mut &misaligned[1..]).unwrap(),
+            0x0908070605040302
+        );
+    }
+
+    #[test]
+    fn read_prefixed_slices() {
+        let data = [0x03, 0x00, 0xAA, 0xBB, 0xCC, 0xDD];
+        let mut reader = data.as_slice();
+        assert_eq!(reader.read_prefixed_u16().unwrap(), [0xAA, 0xBB, 0xCC]);
+        assert_eq!(reader, [0xDD]);
+
+        let data = [0x02, 0x00, 0x00, 0x00, 0xAA, 0xBB];
+        assert_eq!(data.as_slice().read_prefixed_u32().unwrap(), [0xAA, 0xBB]);
+
+        let data = [0x10, 0x00, 0xAA];
+        assert!(matches!(
+            data.as_slice().read_prefixed_u16(),
+            Err(PewterError::NotEnoughDataLeft {
+                attempted_read: 0x10,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn scalars_are_little_endian() {
+        let data = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08];
+        assert_eq!(u16::read(&mut data.as_slice()).unwrap(), 0x0201);
+        assert_eq!(u32::read(&mut data.as_slice()).unwrap(), 0x04030201);
+        assert_eq!(u64::read(&mut data.as_slice()).unwrap(), 0x0807060504030201);
+
+        let mut written = crate::vec::Vec::new();
+        written.write(0x0807060504030201u64).unwrap();
+        assert_eq!(written, data);
+    }
+
+    #[test]
+    fn bool_round_trip() {
+        let mut written = crate::vec::Vec::new();
+        written.write(true).unwrap();
+        written.write(false).unwrap();
+        assert_eq!(written, [1, 0]);
+
+        let mut reader = written.as_slice();
+        assert!(bool::read(&mut reader).unwrap());
+        assert!(!bool::read(&mut reader).unwrap());
+        assert!(bool::read(&mut [2u8].as_slice()).unwrap());
+    }
+
+    #[test]
+    fn pad_vec_to_512() {
+        let mut written = crate::vec::Vec::from([0xFFu8; 3]);
+        written.pad_to(512).unwrap();
+        assert_eq!(written.len(), 512);
+        assert!(written[3..].iter().all(|b| *b == 0));
+
+        written.pad_to(512).unwrap();
+        assert_eq!(written.len(), 512);
+        written.write_zeros(1).unwrap();
+        written.pad_to(512).unwrap();
+        assert_eq!(written.len(), 1024);
+    }
+
+    #[test]
+    fn write_zeros_advances_slice() {
+        let mut buffer = [0xFFu8; 8];
+        let mut writer = buffer.as_mut_slice();
+        writer.write_zeros(3).unwrap();
+        writer.write(0x11u8).unwrap();
+        assert!(writer.write_zeros(5).is_err());
+        assert!(writer.pad_to(4).is_err());
+        assert_eq!(buffer, [0, 0, 0, 0x11, 0xFF, 0xFF, 0xFF, 0xFF]);
+    }
 }
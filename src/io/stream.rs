@@ -1,5 +1,3 @@
-use core::mem::MaybeUninit;
-
 use crate::{
     error::{PewterError, Result},
     io::{Reader, Writer},
@@ -28,6 +26,23 @@ impl<T> PEStream<T> {
     }
 }
 
+impl<T: AsRef<[u8]>> PEStream<T> {
+    /// The length of the whole buffer, regardless of the position.
+    pub fn len(&self) -> usize {
+        self.buffer.as_ref().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of bytes left to read from the current position.
+    /// This is 0 if the position was set past the end of the buffer.
+    pub fn remaining(&self) -> usize {
+        self.len().saturating_sub(self.position)
+    }
+}
+
 impl<T: AsRef<[u8]>> Reader for PEStream<T> {
     fn read_slice(&mut self, size: usize) -> Result<&[u8]> {
         let data = self.buffer.as_ref();
@@ -38,6 +53,13 @@ impl<T: AsRef<[u8]>> Reader for PEStream<T> {
         self.position += size;
         Ok(&data[data_pos..data_pos + size])
     }
+
+    fn peek<D: crate::io::ReadData>(&mut self) -> Result<D> {
+        let position = self.position;
+        let value = D::read(self);
+        self.position = position;
+        value
+    }
 }
 
 impl Writer for PEStream<&mut [u8]> {
@@ -49,28 +71,28 @@ impl Writer for PEStream<&mut [u8]> {
         self.position += data.len();
         Ok(())
     }
+
+    fn position(&self) -> Option<usize> {
+        Some(self.position)
+    }
 }
 
+/// Owned streams can be both read from and written to.
+/// [`Reader`] comes from the `AsRef<[u8]>` impl, writing past the end grows the buffer.
 impl Writer for PEStream<Vec<u8>> {
     fn write_slice(&mut self, data: &[u8]) -> Result<()> {
-
-
         let end_pos = self.position + data.len();
         if self.buffer.len() < end_pos {
-            let mut buffer : Vec<MaybeUninit<u8>> = {
-                let buffer = core::mem::take(&mut self.buffer);
-                unsafe { core::mem::transmute(buffer) }
-            };
-            buffer.reserve(data.len() - self.buffer.capacity());
-            unsafe { 
-                self.buffer.set_len(end_pos);
-                let buffer : Vec<u8> = core::mem::transmute(buffer);
-                let _ = core::mem::replace(&mut self.buffer, buffer);
-            };
+            self.buffer.resize(end_pos, 0);
         }
-        self.buffer[self.position..self.position + data.len()].copy_from_slice(data);
+        self.buffer[self.position..end_pos].copy_from_slice(data);
+        self.position = end_pos;
         Ok(())
     }
+
+    fn position(&self) -> Option<usize> {
+        Some(self.position)
+    }
 }
 
 #[cfg(test)]
@@ -132,4 +154,52 @@ mod test {
         let mut reader = PEStream::new(&out_buffer);
         assert_eq!(reader.read::<[u8; 13]>().unwrap(), test_data);
     }
+
+    #[test]
+    fn test_read_then_write_owned() {
+        let mut stream = PEStream::new(Vec::from(0xAABBCCDDu32.to_le_bytes()));
+        assert_eq!(stream.read::<u32>().unwrap(), 0xAABBCCDD);
+
+        stream.write(0x11223344u32).unwrap();
+        stream.set_position(0);
+        stream.write(0x55u8).unwrap();
+
+        let buffer = stream.into_inner();
+        assert_eq!(buffer.len(), 8);
+        assert_eq!(buffer[..4], [0x55, 0xCC, 0xBB, 0xAA]);
+        assert_eq!(buffer[4..], 0x11223344u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_remaining_decreases_after_read() {
+        let data = [0u8; 10];
+        let mut stream = PEStream::new(data.as_slice());
+        assert_eq!(stream.len(), 10);
+        assert_eq!(stream.remaining(), 10);
+
+        stream.read_slice(4).unwrap();
+        assert_eq!(stream.remaining(), 6);
+        stream.read_slice(6).unwrap();
+        assert_eq!(stream.remaining(), 0);
+        assert!(stream.read_slice(1).is_err());
+
+        stream.set_position(20);
+        assert_eq!(stream.remaining(), 0);
+        assert_eq!(stream.len(), 10);
+    }
+
+    #[test]
+    fn test_peek_does_not_advance() {
+        let data = [0x11223344u32.to_le_bytes(), 0x55667788u32.to_le_bytes()].concat();
+
+        let mut stream = PEStream::new(data.as_slice());
+        assert_eq!(stream.peek::<u32>().unwrap(), 0x11223344);
+        assert_eq!(stream.read::<u32>().unwrap(), 0x11223344);
+        assert_eq!(stream.peek::<u32>().unwrap(), 0x55667788);
+        assert!(stream.read::<u64>().is_err());
+
+        let mut reader = data.as_slice();
+        assert_eq!(reader.peek::<u32>().unwrap(), reader.read::<u32>().unwrap());
+        assert_eq!(reader.len(), 4);
+    }
 }
@@ -4,12 +4,12 @@
 #[cfg(feature = "std")]
 mod alloc_containers {
     pub extern crate std;
-    pub use std::{error, string, vec, borrow};
+    pub use std::{collections, error, io, string, vec};
 }
 #[cfg(not(feature = "std"))]
 mod alloc_containers {
     extern crate alloc;
-    pub use alloc::{string, vec, borrow};
+    pub use alloc::{collections, string, vec};
 }
 
 pub(crate) use alloc_containers::*;
@@ -19,4 +19,4 @@ pub mod error;
 pub mod io;
 pub mod pe;
 
-pub use pe::{options::Options, PEFile};
+pub use pe::{options::Options, read_rva, PEFile};
@@ -0,0 +1,57 @@
+//! Typed addresses.
+//!
+//! RVAs and file offsets are both stored as plain integers in the headers, which makes them easy
+//! to mix up. The certificate table, for example, stores a file offset where every other data
+//! directory stores an RVA. Lookups that take these types can't be passed the wrong kind.
+//!
+//! ```compile_fail
+//! # use pewter::pe::{address::FileOffset, sections::SectionTable};
+//! # fn lookup(section_table: &SectionTable) {
+//! section_table.find_rva(FileOffset(0x400));
+//! # }
+//! ```
+
+/// An address relative to the image base, once the image is loaded.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rva(pub u32);
+
+/// An offset into the file on disk.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileOffset(pub u32);
+
+impl From<u32> for Rva {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Rva> for u32 {
+    fn from(value: Rva) -> Self {
+        value.0
+    }
+}
+
+impl From<u32> for FileOffset {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<FileOffset> for u32 {
+    fn from(value: FileOffset) -> Self {
+        value.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions_round_trip() {
+        assert_eq!(Rva::from(0x1000), Rva(0x1000));
+        assert_eq!(u32::from(Rva(0x1000)), 0x1000);
+        assert_eq!(FileOffset::from(0x400), FileOffset(0x400));
+        assert_eq!(u32::from(FileOffset(0x400)), 0x400);
+    }
+}
@@ -1,8 +1,11 @@
 use bitflags::bitflags;
 
-use crate::io::{ReadData, WriteData};
+use crate::{
+    io::{ReadData, WriteData},
+    vec::Vec,
+};
 
-#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u32)]
 pub enum ImageFileMachine {
     #[default]
@@ -249,6 +252,115 @@ impl WriteData for &CoffFileHeader {
     }
 }
 
+/// COFF Relocations (Object Only)
+///
+/// Object files contain COFF relocations, which specify how the section data should be modified
+/// when placed in the image file and subsequently loaded into memory.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct CoffRelocation {
+    /// The address of the item to which relocation is applied. This is the offset from the
+    /// beginning of the section, plus the value of the section's RVA/Offset field.
+    pub virtual_address: u32,
+    /// A zero-based index into the symbol table. This symbol gives the address that is to be
+    /// used for the relocation.
+    pub symbol_table_index: u32,
+    /// A value that indicates the kind of relocation that should be performed.
+    /// Valid relocation types depend on machine type.
+    pub type_: u16,
+}
+
+impl CoffRelocation {
+    pub const SIZE: usize = 10;
+}
+
+impl ReadData for CoffRelocation {
+    fn read(reader: &mut impl crate::io::Reader) -> crate::error::Result<Self> {
+        Ok(Self {
+            virtual_address: reader.read()?,
+            symbol_table_index: reader.read()?,
+            type_: reader.read()?,
+        })
+    }
+}
+
+impl WriteData for &CoffRelocation {
+    fn write_to(self, writer: &mut impl crate::io::Writer) -> crate::error::Result<()> {
+        writer.write(self.virtual_address)?;
+        writer.write(self.symbol_table_index)?;
+        writer.write(self.type_)?;
+        Ok(())
+    }
+}
+
+/// COFF Symbol Table
+///
+/// A record of the symbol table of an object file. It is followed by `number_of_aux_symbols`
+/// auxiliary records, which are also [`CoffSymbol::SIZE`] bytes.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct CoffSymbol {
+    /// The name of the symbol, padded with nulls. If the first 4 bytes are zero, the last 4
+    /// bytes are an offset into the string table.
+    pub name: [u8; 8],
+    /// The value of the symbol. Its meaning depends on `section_number` and `storage_class`,
+    /// usually it is the offset of the symbol in its section.
+    pub value: u32,
+    /// The one-based index of the section that contains the symbol. Zero means the symbol is
+    /// external, -1 that it is an absolute value and -2 that it is a debugging symbol.
+    pub section_number: i16,
+    /// The type of the symbol. Microsoft tools only set it to 0x20 (function) or 0 (not a function).
+    pub type_: u16,
+    /// The storage class of the symbol, such as external (2) or static (3).
+    pub storage_class: u8,
+    /// The number of auxiliary records that follow this one.
+    pub number_of_aux_symbols: u8,
+    /// The auxiliary records, whose format depends on the symbol.
+    pub aux_symbols: Vec<[u8; CoffSymbol::SIZE]>,
+}
+
+impl CoffSymbol {
+    pub const SIZE: usize = 18;
+
+    /// The offset into the string table of the name, if it is too long for [`Self::name`].
+    pub fn string_table_offset(&self) -> Option<u32> {
+        (self.name[..4] == [0; 4])
+            .then(|| u32::from_le_bytes([self.name[4], self.name[5], self.name[6], self.name[7]]))
+    }
+}
+
+impl ReadData for CoffSymbol {
+    /// Reads the symbol and its auxiliary records.
+    fn read(reader: &mut impl crate::io::Reader) -> crate::error::Result<Self> {
+        let mut symbol = Self {
+            name: reader.read()?,
+            value: reader.read()?,
+            section_number: reader.read()?,
+            type_: reader.read()?,
+            storage_class: reader.read()?,
+            number_of_aux_symbols: reader.read()?,
+            aux_symbols: Vec::new(),
+        };
+        for _ in 0..symbol.number_of_aux_symbols {
+            symbol.aux_symbols.push(reader.read()?);
+        }
+        Ok(symbol)
+    }
+}
+
+impl WriteData for &CoffSymbol {
+    fn write_to(self, writer: &mut impl crate::io::Writer) -> crate::error::Result<()> {
+        writer.write(self.name)?;
+        writer.write(self.value)?;
+        writer.write(self.section_number)?;
+        writer.write(self.type_)?;
+        writer.write(self.storage_class)?;
+        writer.write(self.number_of_aux_symbols)?;
+        for aux_symbol in &self.aux_symbols {
+            writer.write(*aux_symbol)?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
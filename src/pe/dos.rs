@@ -5,7 +5,10 @@
 //! At location 0x3c, the stub has the file offset to the PE signature.
 //! This information enables Windows to properly execute the image file, even though it has an MS-DOS stub.
 //! This file offset is placed at location 0x3c during linking.
-use crate::io::{ReadData, WriteData};
+use crate::{
+    error::{PewterError, Result},
+    io::{ReadData, WriteData},
+};
 
 /// This is the structure at the beginning of every PE file.
 /// It mainaly contains legacy MS-DOS header that are not relivant
@@ -58,6 +61,25 @@ impl ImageDosHeader {
     pub const MAGIC_CONSTANT: u16 = 0x5A4D;
 
     pub const SIZE: usize = 64;
+
+    /// Checks that `e_magic` is [`ImageDosHeader::MAGIC_CONSTANT`] and that `e_lfanew`
+    /// is a 4-byte aligned offset inside a file of `file_len` bytes.
+    pub fn validate(&self, file_len: usize) -> Result<()> {
+        if self.e_magic != Self::MAGIC_CONSTANT {
+            return Err(PewterError::invalid_image_format("Bad DOS header magic."));
+        }
+        if self.e_lfanew as usize > file_len {
+            return Err(PewterError::invalid_image_format(
+                "e_lfanew points past the end of the file.",
+            ));
+        }
+        if !self.e_lfanew.is_multiple_of(4) {
+            return Err(PewterError::invalid_image_format(
+                "e_lfanew is not 4-byte aligned.",
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl ReadData for ImageDosHeader {
@@ -5,8 +5,17 @@ use crate::{
     pe::{
         coff::{CoffFileHeader, ImageFileCharacteristics, ImageFileMachine},
         dos::ImageDosHeader,
-        optional_header::{self, data_directories::SectionName},
-        sections::{SectionFlags, SectionTableRow, Sections},
+        optional_header::{
+            self,
+            data_directories::{ImageDataDirectory, SectionName},
+            OptionalHeaderMagic,
+        },
+        sections::{
+            base_relocation::{BaseRelocationDataDitectory, BaseRelocationType},
+            edata::ExportTableDataDirectory,
+            idata::{ImportDefinition, ImportName},
+            SectionFlags, SectionRow, SectionTable, SectionTableRow, Sections,
+        },
     },
     string::String,
     vec::Vec,
@@ -15,9 +24,13 @@ use crate::{
 
 pub struct PEImageDef {
     pub dos_header: ImageDosHeader,
+    /// The target machine written to the COFF header.
+    pub machine: ImageFileMachine,
     pub file_characteristics: ImageFileCharacteristics,
     pub optional_header: optional_header::OptionalHeader,
     pub sections: SectionDefinitions,
+    /// Imports added with [`PEImageDef::add_import`].
+    pub imports: Vec<ImportDefinition>,
 }
 
 impl PEImageDef {
@@ -57,9 +70,11 @@ impl PEImageDef {
 
         Self {
             dos_header,
+            machine: coff_header.machine,
             optional_header: optional_header.unwrap_or_default(),
             sections,
             file_characteristics: coff_header.characteristics,
+            imports: Vec::new(),
         }
     }
 
@@ -79,63 +94,297 @@ impl PEImageDef {
         self.sections.other.last_mut().unwrap()
     }
 
-    pub fn fix_headers(&mut self) {
-        let file_alignment = self.optional_header.windows_specific_fields.file_alignment() as usize;
-        let allign_section = |addr: usize| {
-            (addr + file_alignment) & (!(file_alignment - 1))
+    /// Import `functions` from `dll`.
+    ///
+    /// The import tables for every dll added with this function are (re)built into a
+    /// `.idata` section, and the `import_table` and `ita` data directories are pointed at it.
+    /// Imports already present in a parsed image are not carried over.
+    pub fn add_import(&mut self, dll: &str, functions: &[ImportName]) -> Result<()> {
+        // Drop the section built by the previous call, it is rebuilt with the new dll.
+        if !self.imports.is_empty() {
+            let import_table_rva = self
+                .optional_header
+                .data_directories
+                .import_table
+                .virtual_address;
+            self.sections
+                .other
+                .retain(|s| !(s.name == ".idata" && s.virtual_address == import_table_rva));
+        }
+
+        self.imports.push(ImportDefinition {
+            dll_name: dll.into(),
+            functions: functions.to_vec(),
+        });
+
+        let virtual_address = self.sections.next_virtual_address() as u32;
+        let layout = ImportDefinition::layout(
+            &self.imports,
+            virtual_address,
+            self.optional_header.standard_fields.magic,
+        )?;
+
+        self.sections.add_section(SectionHeap {
+            name: ".idata".into(),
+            virtual_address,
+            virtual_size: 0,
+            characteristics: SectionFlags::CNT_INITIALIZED_DATA
+                | SectionFlags::MEM_READ
+                | SectionFlags::MEM_WRITE,
+            data: layout.data,
+        });
+        self.optional_header.data_directories.import_table = layout.import_table;
+        self.optional_header.data_directories.ita = layout.import_address_table;
+        Ok(())
+    }
+
+    /// Export `exports`, given as `(name, rva)` pairs, under `dll_name`.
+    ///
+    /// The export tables are built into a `.edata` section and the `export_table` data directory is
+    /// pointed at it. A `.edata` section previously referenced by the `export_table` is replaced.
+    pub fn set_exports(&mut self, dll_name: &str, exports: &[(String, u32)]) -> Result<()> {
+        let export_table_rva = self
+            .optional_header
+            .data_directories
+            .export_table
+            .virtual_address;
+        if export_table_rva != 0 {
+            self.sections
+                .other
+                .retain(|s| !(s.name == ".edata" && s.virtual_address == export_table_rva));
+        }
+
+        let virtual_address = self.sections.next_virtual_address() as u32;
+        let layout = ExportTableDataDirectory::layout(dll_name, exports, virtual_address)?;
+
+        self.sections.add_section(SectionHeap {
+            name: ".edata".into(),
+            virtual_address,
+            virtual_size: 0,
+            characteristics: SectionFlags::CNT_INITIALIZED_DATA | SectionFlags::MEM_READ,
+            data: layout.data,
+        });
+        self.optional_header.data_directories.export_table = layout.export_table;
+        Ok(())
+    }
+
+    /// Build a `.reloc` section with base relocations for `sites`, given as `(rva, type)`, and
+    /// point the `base_relocation_table` data directory at it.
+    ///
+    /// See [`BaseRelocationDataDitectory::from_rvas`] for how the blocks are laid out. A `.reloc`
+    /// section previously referenced by the `base_relocation_table` is replaced, and
+    /// [`ImageFileCharacteristics::RELOCS_STRIPPED`] is cleared.
+    pub fn generate_relocations(&mut self, sites: &[(u32, BaseRelocationType)]) -> Result<()> {
+        let relocations = BaseRelocationDataDitectory::from_rvas(sites)?;
+
+        let base_relocation_rva = self
+            .optional_header
+            .data_directories
+            .base_relocation_table
+            .virtual_address;
+        if base_relocation_rva != 0 {
+            // A parsed image keeps its `.reloc` in `reloc`, sections built here go in `other`.
+            if self
+                .sections
+                .reloc
+                .as_ref()
+                .is_some_and(|s| s.virtual_address == base_relocation_rva)
+            {
+                self.sections.reloc = None;
+            }
+            self.sections
+                .other
+                .retain(|s| !(s.name == ".reloc" && s.virtual_address == base_relocation_rva));
+        }
+
+        let mut data = Vec::new();
+        data.write(&relocations)?;
+        let virtual_address = self.sections.next_virtual_address() as u32;
+        self.optional_header.data_directories.base_relocation_table = ImageDataDirectory {
+            virtual_address,
+            size: data.len() as u32,
         };
+        self.sections.add_section(SectionHeap {
+            name: ".reloc".into(),
+            virtual_address,
+            virtual_size: 0,
+            characteristics: SectionFlags::CNT_INITIALIZED_DATA
+                | SectionFlags::MEM_READ
+                | SectionFlags::MEM_DISCARDABLE,
+            data,
+        });
+        self.file_characteristics
+            .remove(ImageFileCharacteristics::RELOCS_STRIPPED);
+        Ok(())
+    }
+
+    /// Remove the Authenticode signature.
+    ///
+    /// The certificate table lives after the last section and is not mapped, so the section data
+    /// and `size_of_image` are unaffected. The certificate bytes themselves are never kept by
+    /// [`PEImageDef::from_pe_file`], so only the `certificate_table` data directory is cleared.
+    pub fn strip_certificate(&mut self) {
+        self.optional_header.data_directories.certificate_table = Default::default();
+    }
+
+    /// Remove the section named `name` and move the sections after it down to fill the gap.
+    ///
+    /// The entry point and data directories inside the moved sections are adjusted, and data
+    /// directories that pointed into the removed section are cleared. The section data is not
+    /// relocated, so references to the moved sections from code or data must be fixed separately.
+    pub fn remove_section(&mut self, name: &str) -> Option<SectionHeap> {
+        let removed = self.sections.remove(name)?;
+        let removed_start = removed.virtual_address;
+        let removed_end = removed_start as usize + removed.memory_size();
 
-        self.optional_header.windows_specific_fields.set_size_of_headers(
-            allign_section(self.dos_header.e_lfanew as usize
+        let moved: Vec<(u32, usize)> = self
+            .sections
+            .iter_sections()
+            .filter(|heap| heap.virtual_address > removed_start)
+            .map(|heap| (heap.virtual_address, heap.memory_size()))
+            .collect();
+        let shift = moved
+            .iter()
+            .map(|(virtual_address, _)| *virtual_address)
+            .min()
+            .map_or(0, |next_start| next_start - removed_start);
+        let moved_rva = |rva: u32| {
+            moved
+                .iter()
+                .any(|(start, size)| rva >= *start && (rva as usize) < *start as usize + size)
+                .then(|| rva - shift)
+        };
+
+        let data_directories = &mut self.optional_header.data_directories;
+        for directory_name in SectionName::ALL {
+            // The certificate table is a file offset, not an RVA.
+            if directory_name == SectionName::CertificateTable {
+                continue;
+            }
+            let mut directory = data_directories.get_directory(directory_name);
+            if directory.is_null() {
+                continue;
+            }
+            if directory.virtual_address >= removed_start
+                && (directory.virtual_address as usize) < removed_end
+            {
+                data_directories.set_directory(directory_name, Default::default());
+            } else if let Some(rva) = moved_rva(directory.virtual_address) {
+                directory.virtual_address = rva;
+                data_directories.set_directory(directory_name, directory);
+            }
+        }
+
+        let standard_fields = &mut self.optional_header.standard_fields;
+        if let Some(rva) = moved_rva(standard_fields.address_of_entry_point) {
+            standard_fields.address_of_entry_point = rva;
+        }
+
+        for heap in self.sections.iter_sections_mut() {
+            if heap.virtual_address > removed_start {
+                heap.virtual_address -= shift;
+            }
+        }
+        Some(removed)
+    }
+
+    /// The number of bytes needed for the dos header, stub, PE headers and section table.
+    pub fn required_size_of_headers(&self) -> usize {
+        self.dos_header.e_lfanew as usize
             + PEFile::SIGNATURE.len()
             + CoffFileHeader::SIZE
             + self.optional_header.size()
-            +  (SectionTableRow::SIZE * self.sections.count())) as u32
-        );
+            + (SectionTableRow::SIZE * self.sections.count())
+    }
 
-        self.optional_header.windows_specific_fields.set_size_of_image(self.output_size() as u32);
+    pub fn fix_headers(&mut self) {
+        let file_alignment = self.optional_header.windows_specific_fields.file_alignment() as usize;
 
-        let number_of_data_directories =
-            SectionName::ALL
-                .iter()
-                .enumerate()
-                .fold(0, |acc, (i, dir)| {
-                    if self
-                        .optional_header
-                        .data_directories
-                        .get_directory(*dir)
-                        .is_null()
-                    {
-                        (i+1) as u32
-                    } else {
-                        acc
-                    }
-                });
+        // Never shrink the existing headers, tools may have placed data there.
+        let size_of_headers = self
+            .required_size_of_headers()
+            .max(self.optional_header.windows_specific_fields.size_of_headers() as usize);
+        self.optional_header
+            .windows_specific_fields
+            .set_size_of_headers(align_up(size_of_headers, file_alignment) as u32);
 
+        // The loader reserves everything from the headers up to the end of the last section.
+        let section_alignment = self
+            .optional_header
+            .windows_specific_fields
+            .section_alignment();
+        let section_table = SectionTable::from_rows(
+            self.sections
+                .iter_sections()
+                .map(|s| SectionTableRow {
+                    virtual_address: s.virtual_address,
+                    virtual_size: s.memory_size() as u32,
+                    ..Default::default()
+                })
+                .collect(),
+        );
         self.optional_header
             .windows_specific_fields
-            .set_number_of_rva_and_sizes(
-                self.optional_header
-                    .windows_specific_fields
-                    .number_of_rva_and_sizes()
-                    .max(number_of_data_directories),
+            .set_size_of_image(
+                section_table.compute_size_of_image(size_of_headers as u32, section_alignment),
             );
-/*
-        if let Some(init_data) = &self.sections.data {
-            self.optional_header.standard_fields.size_of_initilized_data = self
-                .optional_header
-                .standard_fields
-                .size_of_initilized_data
-                .max(init_data.data.len() as u32);
+
+        // Include every directory up to the last one that is set.
+        let number_of_data_directories = SectionName::ALL
+            .iter()
+            .rposition(|dir| {
+                !self
+                    .optional_header
+                    .data_directories
+                    .get_directory(*dir)
+                    .is_null()
+            })
+            .map_or(0, |i| (i + 1) as u32);
+        self.optional_header
+            .ensure_directory_count(number_of_data_directories);
+
+        // The sizes are the sum of the raw data of each kind of section. Uninitialized data has
+        // no raw data, so its size once loaded is used instead.
+        let mut size_of_code = 0;
+        let mut size_of_initilized_data = 0;
+        let mut size_of_unitilized_data = 0;
+        for section in self.sections.iter_sections() {
+            let characteristics = section.characteristics;
+            if characteristics.contains(SectionFlags::CNT_CODE) {
+                size_of_code += align_up(section.data.len(), file_alignment);
+            }
+            if characteristics.contains(SectionFlags::CNT_INITIALIZED_DATA) {
+                size_of_initilized_data += align_up(section.data.len(), file_alignment);
+            }
+            if characteristics.contains(SectionFlags::CNT_UNINITIALIZED_DATA) {
+                size_of_unitilized_data += align_up(section.memory_size(), file_alignment);
+            }
         }
+        let standard_fields = &mut self.optional_header.standard_fields;
+        standard_fields.size_of_code = size_of_code as u32;
+        standard_fields.size_of_initilized_data = size_of_initilized_data as u32;
+        standard_fields.size_of_unitilized_data = size_of_unitilized_data as u32;
 
-        if let Some(code) = &self.sections.text {
-            self.optional_header.standard_fields.size_of_code = self
-                .optional_header
-                .standard_fields
-                .size_of_code
-                .max(code.data.len() as u32);
-        } */
+        // Only set when there is a matching section, so a value set by hand is kept.
+        let first_section_with = |flags: SectionFlags| {
+            self.sections
+                .iter_sections()
+                .filter(|section| section.characteristics.intersects(flags))
+                .map(|section| section.virtual_address)
+                .min()
+        };
+        let base_of_code = first_section_with(SectionFlags::CNT_CODE);
+        let base_of_data = first_section_with(
+            SectionFlags::CNT_INITIALIZED_DATA | SectionFlags::CNT_UNINITIALIZED_DATA,
+        );
+        let standard_fields = &mut self.optional_header.standard_fields;
+        if let Some(base_of_code) = base_of_code {
+            standard_fields.base_of_code = base_of_code;
+        }
+        // PE32+ has no base_of_data.
+        if standard_fields.magic != OptionalHeaderMagic::PE32Plus {
+            standard_fields.base_of_data = base_of_data.or(standard_fields.base_of_data);
+        }
     }
 
     pub fn output_size(&self) -> usize {
@@ -144,24 +393,39 @@ impl PEImageDef {
         //   acc
         //});
         let file_alignment = self.optional_header.windows_specific_fields.section_alignment() as usize;
-        let allign_section = |addr: usize| {
-            (addr + file_alignment) & (!(file_alignment - 1))
-        };
 
             self.optional_header.windows_specific_fields.size_of_headers() as usize
             + self
                 .sections
                 .iter_sections()
-                .map(|s| {
-                    if (s.data.len() & (file_alignment-1)) != 0 {
-                        allign_section( s.data.len())
-                    }else {
-                        s.data.len()
-                    }
-                })
+                .map(|s| align_up(s.memory_size(), file_alignment))
                 .sum::<usize>()
     }
 
+    /// The [`PEFile`] that [`PEImageDef::write_no_fix`] would produce, without writing it out.
+    ///
+    /// Section data is borrowed from the definition and placed at the same file offsets.
+    /// The headers are not fixed first, and [`special_sections`](PEFile::special_sections) are
+    /// left empty; use the `read_*` methods of [`PEFile`] to parse data directories.
+    pub fn to_pe_file(&self) -> PEFile<'_> {
+        let headers_len = ImageDosHeader::SIZE.max(self.dos_header.e_lfanew as usize)
+            + PEFile::SIGNATURE.len()
+            + CoffFileHeader::SIZE
+            + self.optional_header.size();
+        let sections = self
+            .section_layout(headers_len)
+            .into_iter()
+            .map(|(data, row)| SectionRow { row, data })
+            .collect();
+        PEFile {
+            dos_header: self.dos_header.clone(),
+            coff_header: self.coff_header(),
+            optional_header: Some(self.optional_header.clone()),
+            sections: Sections(Table(sections)),
+            special_sections: Default::default(),
+        }
+    }
+
     pub fn write_file(&mut self) -> Result<crate::vec::Vec<u8>> {
         self.fix_headers();
         self.write_no_fix()
@@ -179,35 +443,57 @@ impl PEImageDef {
         self.dos_header.write_to(&mut buffer)?;
 
         // TODO: Write dos stub
-        while buffer.len() < self.dos_header.e_lfanew as usize {
-            buffer.write(0u8)?;
-        }
-       // buffer.write([0u8; self.dos_header.e_lfanew - ImageDosHeader::SIZE])?;
+        buffer.write_zeros((self.dos_header.e_lfanew as usize).saturating_sub(buffer.len()))?;
         PEFile::SIGNATURE.write_to(&mut buffer)?;
 
+        self.coff_header().write_to(&mut buffer)?;
+
+        self.optional_header.write_to(&mut buffer)?;
+
+        let sections = self.section_layout(buffer.len());
+
+        for (_, sec) in &sections {
+            sec.write_to(&mut buffer)?;
+        }
+
+        for (sec_data, sec) in sections {
+            buffer.write_zeros((sec.pointer_to_raw_data as usize).saturating_sub(buffer.len()))?;
+            buffer.write_slice(sec_data)?;
+        }
+
+        Ok(buffer)
+    }
+
+    /// The COFF header written by [`PEImageDef::write_no_fix`].
+    fn coff_header(&self) -> CoffFileHeader {
         CoffFileHeader {
-            machine: ImageFileMachine::Amd64,
+            machine: self.machine,
             characteristics: self.file_characteristics,
             size_of_optional_header: self.optional_header.size() as u16,
             number_of_sections: self.sections.count() as u16,
             ..Default::default()
         }
-        .write_to(&mut buffer)?;
-
-        self.optional_header.write_to(&mut buffer)?;
+    }
 
+    /// The data and section table row of each section, laid out after `headers_len` bytes of
+    /// headers (everything before the section table).
+    fn section_layout(&self, headers_len: usize) -> Vec<(&[u8], SectionTableRow)> {
         let file_alignment = self.optional_header.windows_specific_fields.file_alignment() as usize;
-        let allign_section = |addr: usize| {
-            (addr + file_alignment) & (!(file_alignment - 1))
-        };
 
-        let mut data_offset = allign_section(buffer.len() + (SectionTableRow::SIZE * self.sections.count()));
+        let headers_end = (headers_len + (SectionTableRow::SIZE * self.sections.count()))
+            .max(self.optional_header.windows_specific_fields.size_of_headers() as usize);
+        let mut data_offset = align_up(headers_end, file_alignment);
 
-        let sections: Vec<_> = self
-            .sections
+        self.sections
             .iter_sections()
             .map(|sec| {
-                (&sec.data, {
+                // Uninitialized data takes no space in the file.
+                let sec_data: &[u8] = if sec.is_uninitialized_data() {
+                    &[]
+                } else {
+                    &sec.data
+                };
+                (sec_data, {
                     let name = {
                         let mut name_buffer = [0u8; 8];
                         for (c, buff) in sec.name.chars().take(8).zip(name_buffer.iter_mut()) {
@@ -215,39 +501,35 @@ impl PEImageDef {
                         }
                         name_buffer
                     };
-                    let pointer_to_raw_data = data_offset as u32;
-                    data_offset  += sec.data.len();
-                    
-                    if (data_offset & (file_alignment-1)) != 0 {
-                        data_offset =  allign_section( data_offset);
-                    }
+                    let pointer_to_raw_data = if sec_data.is_empty() {
+                        0
+                    } else {
+                        data_offset as u32
+                    };
+                    data_offset = align_up(data_offset + sec_data.len(), file_alignment);
 
                     SectionTableRow {
                         name,
-                        virtual_size: sec.virtual_size.max(sec.data.len() as u32),
+                        virtual_size: sec.memory_size() as u32,
                         virtual_address: sec.virtual_address,
                         characteristics: sec.characteristics,
                         pointer_to_raw_data,
-                        size_of_raw_data: sec.data.len() as u32,
+                        size_of_raw_data: sec_data.len() as u32,
                         ..Default::default()
                     }
                 })
             })
-            .collect();
-
-        for (_, sec) in &sections {
-            sec.write_to(&mut buffer)?;
-        }
-
-        for (sec_data, sec) in sections {
-            while buffer.len() < sec.pointer_to_raw_data as usize {
-                buffer.write(0u8)?;
-            }
-            buffer.write_slice(sec_data)?;
-        }
+            .collect()
+    }
+}
 
-        Ok(buffer)
+/// Rounds `value` up to the next multiple of `alignment`.
+/// An `alignment` of zero leaves `value` unchanged.
+pub(crate) fn align_up(value: usize, alignment: usize) -> usize {
+    if alignment == 0 {
+        return value;
     }
+    value.div_ceil(alignment) * alignment
 }
 
 /// If a section has a `virtual_address` of 0, it
@@ -294,10 +576,42 @@ impl SectionDefinitions {
             .filter(|heap| heap.virtual_address != 0)
     }
 
+    /// Iters all sections that have a non-zero `virtual_address`, mutably.
+    pub fn iter_sections_mut(&mut self) -> impl Iterator<Item = &mut SectionHeap> {
+        [
+            &mut self.text,
+            &mut self.rdata,
+            &mut self.data,
+            &mut self.pdata,
+            &mut self.reloc,
+        ]
+        .into_iter()
+        .flat_map(|f| f.as_mut())
+        .chain(self.other.iter_mut())
+        .filter(|heap| heap.virtual_address != 0)
+    }
+
     pub fn count(&self) -> usize {
         self.iter_sections().count()
     }
 
+    /// Removes the first section named `name`, checking the named fields before `other`.
+    pub fn remove(&mut self, name: &str) -> Option<SectionHeap> {
+        for field in [
+            &mut self.text,
+            &mut self.rdata,
+            &mut self.data,
+            &mut self.pdata,
+            &mut self.reloc,
+        ] {
+            if field.as_ref().is_some_and(|heap| heap.name == name) {
+                return field.take();
+            }
+        }
+        let index = self.other.iter().position(|heap| heap.name == name)?;
+        Some(self.other.remove(index))
+    }
+
     /// Finds the section that contains the given virtual address
     #[inline(always)]
     pub fn find_rva(&self, virtual_address: usize) -> Option<&SectionHeap> {
@@ -306,7 +620,7 @@ impl SectionDefinitions {
         }
         self.iter_sections().find(|heap| {
             virtual_address >= (heap.virtual_address as usize)
-                && virtual_address < (heap.virtual_address as usize + heap.data.len())
+                && virtual_address < (heap.virtual_address as usize + heap.memory_size())
         })
     }
 
@@ -336,7 +650,7 @@ impl SectionDefinitions {
     /// Finds the next availible virtual address
     pub fn next_virtual_address(&self) -> usize {
         let v_addr = self.iter_sections().fold(0, |accumulator, heap| {
-            let heap_end_addr = heap.virtual_address as usize + heap.data.len();
+            let heap_end_addr = heap.virtual_address as usize + heap.memory_size();
             if heap_end_addr > accumulator {
                 heap_end_addr
             } else {
@@ -373,6 +687,19 @@ impl SectionHeap {
         addr
     }
 
+    /// Returns true if the section is marked [`SectionFlags::CNT_UNINITIALIZED_DATA`].
+    /// These sections are written with no raw data, only a `virtual_size`.
+    pub fn is_uninitialized_data(&self) -> bool {
+        self.characteristics
+            .contains(SectionFlags::CNT_UNINITIALIZED_DATA)
+    }
+
+    /// The number of bytes the section occupies once loaded,
+    /// the larger of `virtual_size` and the length of `data`.
+    pub fn memory_size(&self) -> usize {
+        (self.virtual_size as usize).max(self.data.len())
+    }
+
     pub fn virtual_size(&self) -> usize {
         SectionDefinitions::allign_to_next_section(self.data.len())
     }
@@ -382,3 +709,420 @@ impl SectionHeap {
         self.virtual_size() - self.data.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::optional_header::data_directories::ImageDataDirectory;
+    use crate::pe::optional_header::{
+        OptionalHeader, OptionalHeaderStandardFields, OptionalHeaderWindowsSpecific,
+        OptionalHeaderWindowsSpecificFields,
+    };
+    use crate::pe::sections::idata::ImportTableRow;
+
+    fn test_image_def() -> PEImageDef {
+        PEImageDef {
+            dos_header: ImageDosHeader {
+                e_magic: ImageDosHeader::MAGIC_CONSTANT,
+                e_lfanew: 0x80,
+                ..Default::default()
+            },
+            machine: ImageFileMachine::I386,
+            file_characteristics: ImageFileCharacteristics::EXECUTABLE_IMAGE,
+            optional_header: OptionalHeader {
+                standard_fields: OptionalHeaderStandardFields {
+                    magic: OptionalHeaderMagic::PE32,
+                    ..Default::default()
+                },
+                windows_specific_fields: OptionalHeaderWindowsSpecific::PE32(
+                    OptionalHeaderWindowsSpecificFields {
+                        image_base: 0x400000,
+                        section_alignment: 0x1000,
+                        file_alignment: 0x200,
+                        ..Default::default()
+                    },
+                ),
+                ..Default::default()
+            },
+            sections: SectionDefinitions::default(),
+            imports: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn align_up_rounds_to_alignment() {
+        assert_eq!(align_up(0x200, 0x200), 0x200);
+        assert_eq!(align_up(0x201, 0x200), 0x400);
+        assert_eq!(align_up(0x10, 0), 0x10);
+    }
+
+    #[test]
+    fn fix_headers_keeps_bloated_size_of_headers() {
+        let mut def = test_image_def();
+        def.optional_header
+            .windows_specific_fields
+            .set_size_of_headers(0x1000);
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3]);
+
+        let data = def.write_file().unwrap();
+        let pe = PEFile::parse(&data).unwrap();
+        let optional_header = pe.optional_header.unwrap();
+
+        assert_eq!(
+            optional_header.windows_specific_fields.size_of_headers(),
+            0x1000
+        );
+        assert_eq!(pe.sections.0[0].row.pointer_to_raw_data, 0x1000);
+        assert_eq!(pe.sections.0[0].data[0], 0xC3);
+    }
+
+    #[test]
+    fn fix_headers_sums_section_sizes() {
+        let mut def = test_image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3; 0x210]);
+        def.new_section(".data", SectionFlags::CNT_INITIALIZED_DATA)
+            .add_data(&[1; 0x10]);
+        def.fix_headers();
+
+        let standard_fields = &def.optional_header.standard_fields;
+        assert_eq!(standard_fields.size_of_code, 0x400);
+        assert_eq!(standard_fields.size_of_initilized_data, 0x200);
+        assert_eq!(standard_fields.size_of_unitilized_data, 0);
+    }
+
+    #[test]
+    fn fix_headers_sets_base_of_code_and_data() {
+        let mut def = test_image_def();
+        def.new_section(".rdata", SectionFlags::CNT_INITIALIZED_DATA)
+            .add_data(&[1; 0x10]);
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3]);
+        def.fix_headers();
+
+        let standard_fields = &def.optional_header.standard_fields;
+        assert_eq!(standard_fields.base_of_code, 0x2000);
+        assert_eq!(standard_fields.base_of_data, Some(0x1000));
+
+        let data = def.write_file().unwrap();
+        let pe = PEFile::parse(&data).unwrap();
+        let text = pe.sections.get_by_name(".text").unwrap();
+        assert_eq!(
+            pe.optional_header.unwrap().standard_fields.base_of_code,
+            text.row.virtual_address
+        );
+    }
+
+    #[test]
+    fn fix_headers_grows_small_size_of_headers() {
+        let mut def = test_image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3]);
+        def.fix_headers();
+
+        let size_of_headers = def.optional_header.windows_specific_fields.size_of_headers();
+        assert_eq!(size_of_headers, 0x200);
+        assert!(size_of_headers as usize >= def.required_size_of_headers());
+    }
+
+    #[test]
+    fn add_import_reparses() {
+        let mut def = test_image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3]);
+        def.add_import(
+            "KERNEL32.dll",
+            &[
+                ImportName::ByName("ExitProcess".into()),
+                ImportName::ByOrdinal(5),
+            ],
+        )
+        .unwrap();
+        def.add_import("USER32.dll", &[ImportName::ByName("MessageBoxA".into())])
+            .unwrap();
+        assert_eq!(def.sections.other.len(), 2);
+
+        let data = def.write_file().unwrap();
+        let pe = PEFile::parse(&data).unwrap();
+        let imports = pe.special_sections.import_table.unwrap();
+
+        let names: Vec<_> = imports
+            .entries
+            .iter()
+            .map(|e| e.dll_name.as_str())
+            .collect();
+        assert_eq!(names, ["KERNEL32.dll", "USER32.dll"]);
+
+        let kernel32 = &imports.entries[0].import_lookup_table;
+        assert!(
+            matches!(&kernel32[0], ImportTableRow::HintName { name, .. } if name == "ExitProcess")
+        );
+        assert_eq!(kernel32[1], ImportTableRow::Ordinal(5));
+        let user32 = &imports.entries[1].import_lookup_table;
+        assert_eq!(user32.len(), 1);
+        assert!(
+            matches!(&user32[0], ImportTableRow::HintName { name, .. } if name == "MessageBoxA")
+        );
+
+        let ita = pe.optional_header.unwrap().data_directories.ita;
+        assert_eq!(ita.size, 5 * 4);
+        assert_eq!(
+            imports.entries[0]
+                .import_directory_table
+                .import_address_table_rva,
+            ita.virtual_address
+        );
+    }
+
+    #[test]
+    fn set_exports_reparses() {
+        let mut def = test_image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3; 0x30]);
+        let exports = [
+            (String::from("Zeta"), 0x1000),
+            (String::from("Alpha"), 0x1010),
+            (String::from("Mid"), 0x1020),
+        ];
+        def.set_exports("TEST.dll", &exports).unwrap();
+        // Replacing the exports does not leave the old section behind.
+        def.set_exports("TEST.dll", &exports).unwrap();
+        assert_eq!(def.sections.other.len(), 2);
+
+        let data = def.write_file().unwrap();
+        let pe = PEFile::parse(&data).unwrap();
+        let export_table = pe.special_sections.export_table.unwrap();
+        let directory = &export_table.export_directory_table;
+        assert_eq!(directory.ordinal_base, 1);
+
+        assert_eq!(
+            export_table.export_name_table.0,
+            ["Alpha", "Mid", "Zeta"].map(String::from)
+        );
+        for (name, ordinal) in export_table
+            .export_name_table
+            .iter()
+            .zip(export_table.export_ordinal_table.iter())
+        {
+            let (_, rva) = exports.iter().find(|(n, _)| n == name).unwrap();
+            assert_eq!(
+                export_table.export_address_table[ordinal.0 as usize].export_rva,
+                *rva
+            );
+        }
+        // Ordinals follow the order the exports were given in.
+        assert_eq!(export_table.export_ordinal_table[2].0, 0);
+    }
+
+    #[test]
+    fn generate_relocations_reparses() {
+        let mut def = test_image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3; 0x30]);
+        def.file_characteristics |= ImageFileCharacteristics::RELOCS_STRIPPED;
+        let sites = [
+            (0x2008, BaseRelocationType::HighLow),
+            (0x1010, BaseRelocationType::HighLow),
+            (0x1020, BaseRelocationType::Dir64),
+            (0x1004, BaseRelocationType::HighLow),
+        ];
+        def.generate_relocations(&sites).unwrap();
+        // Regenerating does not leave the old section behind.
+        def.generate_relocations(&sites).unwrap();
+        assert_eq!(def.sections.other.len(), 2);
+        assert!(def
+            .generate_relocations(&[(0x1000, BaseRelocationType::HighAdj)])
+            .is_err());
+
+        let data = def.write_file().unwrap();
+        let pe = PEFile::parse(&data).unwrap();
+        assert!(!pe
+            .coff_header
+            .characteristics
+            .contains(ImageFileCharacteristics::RELOCS_STRIPPED));
+        let relocations = pe.special_sections.relocation_table.unwrap();
+        assert_eq!(relocations.relocations.len(), 2);
+        // The single entry for 0x2000 is padded to keep the block 32-bit aligned.
+        assert_eq!(relocations.relocations[1].block_size, 12);
+
+        let mut expected = Vec::from(sites);
+        expected.sort_by_key(|(rva, _)| *rva);
+        assert_eq!(relocations.iter_rvas().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn remove_middle_section_compacts_layout() {
+        let mut def = test_image_def();
+        for name in [".a", ".b", ".c"] {
+            def.new_section(
+                name,
+                SectionFlags::CNT_INITIALIZED_DATA | SectionFlags::MEM_READ,
+            )
+            .add_data(&[0xCC; 0x10]);
+        }
+        let data_directories = &mut def.optional_header.data_directories;
+        data_directories.architecture = ImageDataDirectory {
+            virtual_address: 0x3004,
+            size: 4,
+        };
+        data_directories.ita = ImageDataDirectory {
+            virtual_address: 0x2000,
+            size: 8,
+        };
+        def.optional_header.standard_fields.address_of_entry_point = 0x3008;
+
+        let removed = def.remove_section(".b").unwrap();
+        assert_eq!(removed.virtual_address, 0x2000);
+        assert!(def.remove_section(".b").is_none());
+
+        let data_directories = &def.optional_header.data_directories;
+        assert_eq!(data_directories.architecture.virtual_address, 0x2004);
+        assert!(data_directories.ita.is_null());
+        assert_eq!(
+            def.optional_header.standard_fields.address_of_entry_point,
+            0x2008
+        );
+
+        let data = def.write_file().unwrap();
+        let pe = PEFile::parse(&data).unwrap();
+        assert_eq!(pe.sections.0.len(), 2);
+        assert_eq!(pe.sections.0[0].row.virtual_address, 0x1000);
+        assert_eq!(pe.sections.0[1].row.name_str(), ".c");
+        assert_eq!(pe.sections.0[1].row.virtual_address, 0x2000);
+    }
+
+    #[test]
+    fn machine_is_preserved() {
+        let mut def = test_image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3]);
+        def.machine = ImageFileMachine::I386;
+        let data = def.write_file().unwrap();
+
+        let mut def = PEImageDef::from_pe_file(PEFile::parse(&data).unwrap());
+        assert_eq!(def.machine, ImageFileMachine::I386);
+        let data = def.write_file().unwrap();
+        let pe = PEFile::parse(&data).unwrap();
+        assert_eq!(pe.coff_header.machine, ImageFileMachine::I386);
+    }
+
+    #[test]
+    fn strip_certificate_removes_signature() {
+        let mut def = test_image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3]);
+        let unsigned_len = def.write_file().unwrap().len();
+
+        let offset = unsigned_len.next_multiple_of(8);
+        def.optional_header.data_directories.certificate_table =
+            optional_header::data_directories::ImageDataDirectory {
+                virtual_address: offset as u32,
+                size: 16,
+            };
+        let mut signed = def.write_file().unwrap();
+        signed.resize(offset, 0);
+        signed.write(16u32).unwrap();
+        signed.write(0x0200u16).unwrap();
+        signed.write(0x0002u16).unwrap();
+        signed.write_slice(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+
+        let pe = PEFile::parse(&signed).unwrap();
+        assert!(pe.special_sections.certificate_table.is_some());
+        let size_of_image = pe
+            .optional_header
+            .as_ref()
+            .unwrap()
+            .windows_specific_fields
+            .size_of_image();
+
+        let mut def = PEImageDef::from_pe_file(pe);
+        def.strip_certificate();
+        let stripped = def.write_file().unwrap();
+        assert_eq!(stripped.len(), unsigned_len);
+
+        let pe = PEFile::parse(&stripped).unwrap();
+        assert!(pe.special_sections.certificate_table.is_none());
+        assert!(pe.read_certificate_table(&stripped).unwrap().is_none());
+        let optional_header = pe.optional_header.unwrap();
+        assert!(optional_header.data_directories.certificate_table.is_null());
+        assert_eq!(
+            optional_header.windows_specific_fields.size_of_image(),
+            size_of_image
+        );
+    }
+
+    #[test]
+    fn bss_section_takes_no_file_space() {
+        let mut def = test_image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3]);
+        let without_bss = def.write_file().unwrap();
+        let size_of_image = def.optional_header.windows_specific_fields.size_of_image();
+
+        def.new_section(
+            ".bss",
+            SectionFlags::CNT_UNINITIALIZED_DATA | SectionFlags::MEM_READ | SectionFlags::MEM_WRITE,
+        )
+        .virtual_size = 0x3000;
+        let data = def.write_file().unwrap();
+        assert_eq!(data.len(), without_bss.len());
+        assert_eq!(
+            def.optional_header.windows_specific_fields.size_of_image(),
+            size_of_image + 0x3000
+        );
+
+        let pe = PEFile::parse(&data).unwrap();
+        let bss = &pe.sections.0[1].row;
+        assert_eq!(bss.name_str(), ".bss");
+        assert_eq!(bss.virtual_address, 0x2000);
+        assert_eq!(bss.virtual_size, 0x3000);
+        assert_eq!(bss.size_of_raw_data, 0);
+        assert_eq!(bss.pointer_to_raw_data, 0);
+    }
+
+    #[test]
+    fn fix_headers_size_of_image_covers_last_section() {
+        let mut def = test_image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3; 0x1800]);
+        def.new_section(".data", SectionFlags::CNT_INITIALIZED_DATA)
+            .add_data(&[1; 0x10]);
+
+        let data = def.write_file().unwrap();
+        let pe = PEFile::parse_headers(&data).unwrap();
+        let size_of_image = pe
+            .optional_header
+            .unwrap()
+            .windows_specific_fields
+            .size_of_image();
+        assert_eq!(size_of_image, 0x4000);
+        assert_eq!(
+            pe.section_table.compute_size_of_image(0x400, 0x1000),
+            size_of_image
+        );
+    }
+
+    #[test]
+    fn to_pe_file_matches_written_file() {
+        let mut def = test_image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3, 0xCC]);
+        def.new_section(".data", SectionFlags::CNT_INITIALIZED_DATA)
+            .add_data(&[1, 2, 3]);
+        def.fix_headers();
+
+        let pe = def.to_pe_file();
+        let data = def.write_no_fix().unwrap();
+        let written = PEFile::parse(&data).unwrap();
+        assert_eq!(pe.coff_header, written.coff_header);
+        assert_eq!(pe.optional_header, written.optional_header);
+        assert_eq!(pe.sections.0.len(), 2);
+        for (section, written_section) in pe.sections.0.iter().zip(written.sections.0.iter()) {
+            assert_eq!(section.row, written_section.row);
+            assert!(written_section.data.starts_with(section.data));
+        }
+        assert_eq!(pe.sections.0[1].row.name_str(), ".data");
+        assert_eq!(pe.sections.0[1].data, [1, 2, 3]);
+    }
+}
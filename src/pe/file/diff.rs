@@ -0,0 +1,173 @@
+//! Field level comparison of two parsed images.
+use crate::{
+    pe::optional_header::{data_directories::SectionName, OptionalHeader},
+    std::format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use super::PEFile;
+
+/// A header field that differs between two images. Returned by [`PEFile::diff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderDiff {
+    /// The name of the field, such as `"address_of_entry_point"` or `"section.virtual_address"`.
+    pub field: &'static str,
+    /// The index of the section for `section.*` fields.
+    pub index: Option<usize>,
+    /// The value in `self`.
+    pub left: String,
+    /// The value in `other`.
+    pub right: String,
+}
+
+struct DiffBuilder(Vec<HeaderDiff>);
+
+impl DiffBuilder {
+    fn push<T: PartialEq>(
+        &mut self,
+        field: &'static str,
+        index: Option<usize>,
+        left: T,
+        right: T,
+        format: impl Fn(&T) -> String,
+    ) {
+        if left != right {
+            self.0.push(HeaderDiff {
+                field,
+                index,
+                left: format(&left),
+                right: format(&right),
+            });
+        }
+    }
+}
+
+fn hex(value: &u32) -> String {
+    format!("0x{:X}", value)
+}
+
+impl<'a> PEFile<'a> {
+    /// Lists the header fields that differ between `self` and `other`.
+    ///
+    /// This compares the machine, subsystem, entry point, every data directory and the
+    /// characteristics, address and sizes of each section. Sections are compared by index.
+    pub fn diff(&self, other: &PEFile) -> Vec<HeaderDiff> {
+        let mut diff = DiffBuilder(Vec::new());
+        diff.push(
+            "machine",
+            None,
+            self.coff_header.machine,
+            other.coff_header.machine,
+            |v| format!("{:?}", v),
+        );
+
+        let default_optional_header = OptionalHeader::default();
+        let left = self
+            .optional_header
+            .as_ref()
+            .unwrap_or(&default_optional_header);
+        let right = other
+            .optional_header
+            .as_ref()
+            .unwrap_or(&default_optional_header);
+        diff.push(
+            "subsystem",
+            None,
+            left.windows_specific_fields.subsystem(),
+            right.windows_specific_fields.subsystem(),
+            |v| format!("{:?}", v),
+        );
+        diff.push(
+            "address_of_entry_point",
+            None,
+            left.standard_fields.address_of_entry_point,
+            right.standard_fields.address_of_entry_point,
+            hex,
+        );
+        for name in SectionName::ALL {
+            diff.push(
+                name.field_name(),
+                None,
+                left.data_directories.get_directory(name),
+                right.data_directories.get_directory(name),
+                |v| format!("0x{:X}+0x{:X}", v.virtual_address, v.size),
+            );
+        }
+
+        diff.push(
+            "number_of_sections",
+            None,
+            self.sections.0.len(),
+            other.sections.0.len(),
+            ToString::to_string,
+        );
+        for (index, (left, right)) in self
+            .sections
+            .0
+            .iter()
+            .zip(other.sections.0.iter())
+            .enumerate()
+        {
+            let (left, right) = (&left.row, &right.row);
+            diff.push(
+                "section.characteristics",
+                Some(index),
+                left.characteristics,
+                right.characteristics,
+                |v| format!("0x{:X}", v.bits()),
+            );
+            diff.push(
+                "section.virtual_address",
+                Some(index),
+                left.virtual_address,
+                right.virtual_address,
+                hex,
+            );
+            diff.push(
+                "section.virtual_size",
+                Some(index),
+                left.virtual_size,
+                right.virtual_size,
+                hex,
+            );
+            diff.push(
+                "section.size_of_raw_data",
+                Some(index),
+                left.size_of_raw_data,
+                right.size_of_raw_data,
+                hex,
+            );
+        }
+        diff.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_entry_point_is_the_only_diff() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let left = PEFile::parse(data).unwrap();
+        let mut right = left.clone();
+        right
+            .optional_header
+            .as_mut()
+            .unwrap()
+            .standard_fields
+            .address_of_entry_point = 0x1001;
+
+        assert!(left.diff(&left).is_empty());
+        assert_eq!(
+            left.diff(&right),
+            [HeaderDiff {
+                field: "address_of_entry_point",
+                index: None,
+                left: "0x1000".into(),
+                right: "0x1001".into(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Parsing data directories on first access.
+//!
+//! [`PEFile::parse`] parses every data directory up front. [`LazyPEFile`] only parses the
+//! headers and section table, and parses each directory the first time it is asked for.
+use core::cell::OnceCell;
+
+use crate::{
+    error::Result,
+    pe::{
+        optional_header::data_directories::SectionName,
+        sections::{
+            base_relocation, bound_import, certificate, cor20, debug, delay_import, edata, idata,
+            load_config, pdata, rsrc, tls,
+        },
+    },
+};
+
+use super::PEFile;
+
+/// A [`PEFile`] whose data directories are parsed when they are first accessed.
+///
+/// Parsed directories are cached, so each one is parsed at most once. Errors are not cached.
+pub struct LazyPEFile<'a> {
+    data: &'a [u8],
+    pe_file: PEFile<'a>,
+    export_table: OnceCell<Option<edata::ExportTableDataDirectory>>,
+    import_table: OnceCell<Option<idata::ImportTableDataDirectory>>,
+    resource_table: OnceCell<Option<rsrc::ResourceDataDirectory>>,
+    exception_table: OnceCell<Option<pdata::ExceptionHandlerDataDirectory>>,
+    certificate_table: OnceCell<Option<certificate::CertificateDataDirectory>>,
+    relocation_table: OnceCell<Option<base_relocation::BaseRelocationDataDitectory>>,
+    debug: OnceCell<Option<debug::DebugDataDirectory>>,
+    tls_table: OnceCell<Option<tls::TlsDirectory>>,
+    load_config_table: OnceCell<Option<load_config::LoadConfigDataDirectory>>,
+    bound_import: OnceCell<Option<bound_import::BoundImportDataDirectory>>,
+    import_address_table: OnceCell<Option<idata::ImportAddressTableDataDirectory>>,
+    delay_import_table: OnceCell<Option<delay_import::DelayImportDataDirectory>>,
+    clr_runtime_header: OnceCell<Option<cor20::ImageCor20Header>>,
+    /// The directories that were parsed, in order.
+    #[cfg(test)]
+    parsed: core::cell::RefCell<crate::vec::Vec<SectionName>>,
+}
+
+impl<'a> LazyPEFile<'a> {
+    /// Parse the headers and sections of `data` with [`PEFile::parse_minimal`].
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        Ok(Self {
+            data,
+            pe_file: PEFile::parse_minimal(data)?,
+            export_table: OnceCell::new(),
+            import_table: OnceCell::new(),
+            resource_table: OnceCell::new(),
+            exception_table: OnceCell::new(),
+            certificate_table: OnceCell::new(),
+            relocation_table: OnceCell::new(),
+            debug: OnceCell::new(),
+            tls_table: OnceCell::new(),
+            load_config_table: OnceCell::new(),
+            bound_import: OnceCell::new(),
+            import_address_table: OnceCell::new(),
+            delay_import_table: OnceCell::new(),
+            clr_runtime_header: OnceCell::new(),
+            #[cfg(test)]
+            parsed: Default::default(),
+        })
+    }
+
+    /// The headers and sections. [`special_sections`](PEFile::special_sections) is always empty.
+    pub fn pe_file(&self) -> &PEFile<'a> {
+        &self.pe_file
+    }
+
+    pub fn export_table(&self) -> Result<Option<&edata::ExportTableDataDirectory>> {
+        self.get_or_parse(&self.export_table, SectionName::ExportTable, || {
+            self.pe_file.read_export_table()
+        })
+    }
+
+    pub fn import_table(&self) -> Result<Option<&idata::ImportTableDataDirectory>> {
+        self.get_or_parse(&self.import_table, SectionName::ImportTable, || {
+            self.pe_file.read_import_table()
+        })
+    }
+
+    pub fn resource_table(&self) -> Result<Option<&rsrc::ResourceDataDirectory>> {
+        self.get_or_parse(&self.resource_table, SectionName::ResourceTable, || {
+            self.pe_file.read_resource_directory()
+        })
+    }
+
+    pub fn exception_table(&self) -> Result<Option<&pdata::ExceptionHandlerDataDirectory>> {
+        self.get_or_parse(&self.exception_table, SectionName::ExceptionTable, || {
+            self.pe_file.read_exeption_table()
+        })
+    }
+
+    pub fn certificate_table(&self) -> Result<Option<&certificate::CertificateDataDirectory>> {
+        self.get_or_parse(
+            &self.certificate_table,
+            SectionName::CertificateTable,
+            || self.pe_file.read_certificate_table(self.data),
+        )
+    }
+
+    pub fn relocation_table(
+        &self,
+    ) -> Result<Option<&base_relocation::BaseRelocationDataDitectory>> {
+        self.get_or_parse(
+            &self.relocation_table,
+            SectionName::BaseRelocationTable,
+            || self.pe_file.read_base_relocation_table(),
+        )
+    }
+
+    pub fn debug(&self) -> Result<Option<&debug::DebugDataDirectory>> {
+        self.get_or_parse(&self.debug, SectionName::Debug, || {
+            self.pe_file.read_debug_directory()
+        })
+    }
+
+    pub fn tls_table(&self) -> Result<Option<&tls::TlsDirectory>> {
+        self.get_or_parse(&self.tls_table, SectionName::TlsTable, || {
+            self.pe_file.read_tls_table()
+        })
+    }
+
+    pub fn load_config_table(&self) -> Result<Option<&load_config::LoadConfigDataDirectory>> {
+        self.get_or_parse(
+            &self.load_config_table,
+            SectionName::LoadConfigTable,
+            || self.pe_file.read_load_config_table(),
+        )
+    }
+
+    pub fn bound_import(&self) -> Result<Option<&bound_import::BoundImportDataDirectory>> {
+        self.get_or_parse(&self.bound_import, SectionName::BoundImport, || {
+            self.pe_file.read_bound_import_table()
+        })
+    }
+
+    pub fn import_address_table(&self) -> Result<Option<&idata::ImportAddressTableDataDirectory>> {
+        self.get_or_parse(&self.import_address_table, SectionName::Ita, || {
+            self.pe_file.read_import_address_table()
+        })
+    }
+
+    pub fn delay_import_table(&self) -> Result<Option<&delay_import::DelayImportDataDirectory>> {
+        self.get_or_parse(
+            &self.delay_import_table,
+            SectionName::DelayImportDescriptor,
+            || self.pe_file.read_delay_import_table(),
+        )
+    }
+
+    pub fn clr_runtime_header(&self) -> Result<Option<&cor20::ImageCor20Header>> {
+        self.get_or_parse(
+            &self.clr_runtime_header,
+            SectionName::ClrRuntimeHeader,
+            || self.pe_file.read_clr_runtime_header(),
+        )
+    }
+
+    /// Returns the value in `cell`, parsing directory `name` and storing it first if the cell is
+    /// empty.
+    fn get_or_parse<'s, T>(
+        &self,
+        cell: &'s OnceCell<Option<T>>,
+        #[cfg_attr(not(test), allow(unused_variables))] name: SectionName,
+        parse: impl FnOnce() -> Result<Option<T>>,
+    ) -> Result<Option<&'s T>> {
+        if let Some(value) = cell.get() {
+            return Ok(value.as_ref());
+        }
+        #[cfg(test)]
+        self.parsed.borrow_mut().push(name);
+        let value = parse()?;
+        Ok(cell.get_or_init(|| value).as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_table_does_not_parse_export_table() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let pe = LazyPEFile::parse(data).unwrap();
+        assert!(pe.pe_file().special_sections.import_table.is_none());
+        let import_table = pe.import_table().unwrap().unwrap();
+        assert_eq!(import_table.entries[0].dll_name, "KERNEL32.dll");
+        assert!(pe.import_table().unwrap().is_some());
+        assert_eq!(*pe.parsed.borrow(), [SectionName::ImportTable]);
+
+        assert!(pe.export_table().unwrap().is_none());
+        assert!(pe.import_address_table().unwrap().is_some());
+        assert!(pe.debug().unwrap().is_none());
+        assert_eq!(
+            *pe.parsed.borrow(),
+            [
+                SectionName::ImportTable,
+                SectionName::ExportTable,
+                SectionName::Ita,
+                SectionName::Debug
+            ]
+        );
+    }
+}
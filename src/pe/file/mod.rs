@@ -1,16 +1,28 @@
 pub mod definition;
+#[cfg(feature = "std")]
+pub mod diff;
+pub mod lazy;
+pub mod patcher;
+pub mod validation;
 
 use crate::{
     error::{PewterError, Result},
-    io::{ReadData, Reader},
+    io::{ReadData, Reader, Writer},
+    vec,
+    vec::Vec,
 };
 
-use crate::pe::{coff, dos, optional_header, options::Options, sections::SectionTable};
+use crate::pe::{
+    coff, dos, optional_header,
+    options::{Options, ParseSectionFlags},
+    sections::SectionTable,
+};
 
 use super::{
-    optional_header::data_directories::{DataDirectories, ImageDataDirectory},
+    optional_header::data_directories::SectionName,
     sections::{
-        base_relocation, certificate, edata, idata, pdata, rsrc,cor20, ParseSectionData, Sections,
+        base_relocation, bound_import, certificate, cor20, debug, delay_import, edata, idata,
+        load_config, pdata, rsrc, tls, ParseSectionData, Sections, SpecialSections,
     },
 };
 
@@ -20,6 +32,45 @@ pub struct PEFile<'a> {
     pub coff_header: coff::CoffFileHeader,
     pub optional_header: Option<optional_header::OptionalHeader>,
     pub sections: Sections<'a>,
+    /// Data directories parsed according to [Options::parse_special_sections].
+    pub special_sections: SpecialSections,
+}
+
+/// The headers of a PE file, without any section data.
+/// Returned by [`PEFile::parse_headers`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PEHeaders {
+    pub dos_header: dos::ImageDosHeader,
+    pub coff_header: coff::CoffFileHeader,
+    pub optional_header: Option<optional_header::OptionalHeader>,
+    pub section_table: SectionTable,
+}
+
+/// The kind of image, see [`PEFile::kind`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PeKind {
+    /// A program that can be run, usually a `.exe`.
+    Executable,
+    /// A dynamic-link library.
+    Dll,
+    /// A kernel-mode driver, usually a `.sys`.
+    Driver,
+    /// An object file, which has no optional header.
+    Object,
+    /// An image that is not marked as executable.
+    Unknown,
+}
+
+/// Returns the bytes from `rva` to the end of the section that contains it.
+///
+/// Only the headers and section table are parsed, see [`PEFile::parse_headers`].
+pub fn read_rva(file_bytes: &[u8], rva: usize) -> Result<&[u8]> {
+    let headers = PEFile::parse_headers(file_bytes)?;
+    u32::try_from(rva)
+        .ok()
+        .and_then(|virtual_address| headers.section_table.find_rva(virtual_address))
+        .and_then(|row| row.try_get_data(file_bytes, rva))
+        .ok_or_else(|| PewterError::invalid_image_format("RVA is not inside a section"))
 }
 
 impl<'a> PEFile<'a> {
@@ -38,96 +89,1017 @@ impl<'a> PEFile<'a> {
         Self::parse_with_options(data, Options::minimal())
     }
 
-    pub fn parse_with_options(data: &'a [u8], _parse_options: Options) -> Result<Self> {
-        let dos_header = dos::ImageDosHeader::read(&mut data.as_ref())?;
-        let pe_offset = dos_header.e_lfanew as usize;
+    /// Parse with only the data directories in `flags`.
+    ///
+    /// ```
+    /// use pewter::{pe::options::ParseSectionFlags, PEFile};
+    ///
+    /// let data = std::fs::read("fixtures/minimal_pe32.exe").unwrap();
+    /// let pe = PEFile::parse_with_flags(&data, ParseSectionFlags::IMPORT_TABLE).unwrap();
+    ///
+    /// let imports = pe.special_sections.import_table.unwrap();
+    /// assert_eq!(imports.entries[0].dll_name, "KERNEL32.dll");
+    /// assert!(pe.special_sections.export_table.is_none());
+    /// ```
+    #[inline(always)]
+    pub fn parse_with_flags(data: &'a [u8], flags: ParseSectionFlags) -> Result<Self> {
+        Self::parse_with_options(
+            data,
+            Options {
+                parse_special_sections: flags,
+                ..Default::default()
+            },
+        )
+    }
 
-        if data.len() < pe_offset {
-            return Err(PewterError::not_enough_data(pe_offset));
+    /// Parse only the DOS header, COFF header, optional header and section table.
+    ///
+    /// No section data is read, so this works on files that are truncated after the headers.
+    #[inline(always)]
+    pub fn parse_headers(data: &[u8]) -> Result<PEHeaders> {
+        Self::parse_headers_with_options(data, &Options::default())
+    }
+
+    /// Like [`PEFile::parse_headers`], but honors [`Options::force_optional_header`].
+    pub fn parse_headers_with_options(data: &[u8], options: &Options) -> Result<PEHeaders> {
+        let dos_header =
+            dos::ImageDosHeader::read(&mut data.as_ref()).map_err(|e| e.with_offset(0))?;
+        dos_header
+            .validate(data.len())
+            .map_err(|e| e.with_offset(0))?;
+        let pe_offset = dos_header.e_lfanew as usize;
+        if pe_offset + Self::SIGNATURE.len() > data.len() {
+            return Err(PewterError::not_enough_data(Self::SIGNATURE.len()).with_offset(pe_offset));
         }
 
         let read_ptr = &mut data[pe_offset..].as_ref();
-        let signature: [u8; 4] = read_ptr.read()?;
+        // The offset in `data` that `read_ptr` is currently at.
+        let offset = |read_ptr: &&[u8]| data.len() - read_ptr.len();
+
+        let signature: [u8; 4] = read_ptr.read().map_err(|e| e.with_offset(pe_offset))?;
 
         if signature != Self::SIGNATURE {
-            return Err(PewterError::invalid_image_format("Bad PE signature."));
+            return Err(PewterError::invalid_image_format_at(
+                "Bad PE signature.",
+                pe_offset,
+            ));
         }
 
-        let coff_header: coff::CoffFileHeader = read_ptr.read()?;
+        let coff_header_offset = offset(read_ptr);
+        let coff_header: coff::CoffFileHeader = read_ptr
+            .read()
+            .map_err(|e| e.with_offset(coff_header_offset))?;
 
+        let optional_header_offset = offset(read_ptr);
         let optional_header: Option<optional_header::OptionalHeader> =
-            (coff_header.size_of_optional_header > 0)
+            (coff_header.size_of_optional_header > 0 || options.force_optional_header)
                 // should probaby limit this read to size_of_optional_header
                 .then(|| read_ptr.read())
-                .transpose()?;
+                .transpose()
+                .map_err(|e| e.with_offset(optional_header_offset))?;
 
-        let sections = {
-            let section_table =
-                SectionTable::new_linear(read_ptr, coff_header.number_of_sections as usize)?;
-            Sections::parse(data, section_table)?
-        };
+        if coff_header.number_of_sections as usize > options.max_sections {
+            return Err(PewterError::limit_exceeded(
+                "sections",
+                options.max_sections,
+            ));
+        }
+
+        let section_table_offset = offset(read_ptr);
+        let section_table =
+            SectionTable::new_linear(read_ptr, coff_header.number_of_sections as usize)
+                .map_err(|e| e.with_offset(section_table_offset))?;
+
+        Ok(PEHeaders {
+            dos_header,
+            coff_header,
+            optional_header,
+            section_table,
+        })
+    }
+
+    pub fn parse_with_options(data: &'a [u8], parse_options: Options) -> Result<Self> {
+        let PEHeaders {
+            dos_header,
+            coff_header,
+            optional_header,
+            section_table,
+        } = Self::parse_headers_with_options(data, &parse_options)?;
+
+        let sections = Sections::parse(data, section_table)?;
+
+        let special_sections = optional_header
+            .as_ref()
+            .map(|optional_header| {
+                SpecialSections::parse_tables(
+                    data,
+                    &sections,
+                    optional_header,
+                    &coff_header,
+                    &parse_options,
+                )
+            })
+            .transpose()?
+            .unwrap_or_default();
 
         Ok(Self {
             dos_header,
             coff_header,
             optional_header,
             sections,
+            special_sections,
         })
     }
 
-    fn read_section_data<T: ParseSectionData>(
+    /// The raw bytes of the data directory `name`, `size` bytes starting at its `virtual_address`.
+    ///
+    /// [`SectionName::CertificateTable`] is read at its file offset. Returns `None` if the
+    /// directory is null or not backed by data in `file_bytes`.
+    pub fn data_directory_bytes<'b>(
         &self,
-        data_dir_fn: impl FnOnce(&DataDirectories) -> &ImageDataDirectory,
-    ) -> Result<Option<T>> {
+        file_bytes: &'b [u8],
+        name: SectionName,
+    ) -> Option<&'b [u8]> {
+        let data_directory = self
+            .optional_header
+            .as_ref()?
+            .data_directories
+            .get_directory(name);
+        if data_directory.is_null() {
+            return None;
+        }
+        let start = if name == SectionName::CertificateTable {
+            data_directory.virtual_address as usize
+        } else {
+            self.sections
+                .find_rva(data_directory.virtual_address)?
+                .row
+                .rva_to_file_offset(data_directory.virtual_address as u64)?
+        };
+        file_bytes.get(start..start.checked_add(data_directory.size as usize)?)
+    }
+
+    /// The `(virtual_address, size)` of the data directory `name`, without parsing or mapping it.
+    ///
+    /// Returns `None` if the directory is null or past
+    /// [`number_of_rva_and_sizes`](optional_header::OptionalHeaderWindowsSpecificFields::number_of_rva_and_sizes).
+    pub fn data_directory_range(&self, name: SectionName) -> Option<(u32, u32)> {
+        self.optional_header
+            .as_ref()?
+            .try_get_data_directory(name)
+            .filter(|data_directory| !data_directory.is_null())
+            .map(|data_directory| (data_directory.virtual_address, data_directory.size))
+    }
+
+    /// The `(name, virtual_address, size)` of every data directory that is not null.
+    ///
+    /// The address of [`SectionName::CertificateTable`] is a file offset, not an RVA.
+    pub fn directory_regions(&self) -> Vec<(SectionName, u32, u32)> {
+        let Some(optional_header) = &self.optional_header else {
+            return Vec::new();
+        };
+        SectionName::ALL
+            .iter()
+            .map(|name| (*name, optional_header.data_directories.get_directory(*name)))
+            .filter(|(_, data_directory)| !data_directory.is_null())
+            .map(|(name, data_directory)| {
+                (name, data_directory.virtual_address, data_directory.size)
+            })
+            .collect()
+    }
+
+    /// The bytes between the end of the headers (`size_of_headers`) and the raw data of the first section.
+    ///
+    /// Nothing loads this gap, so it is sometimes used to hide data.
+    /// Returns `None` if there is no gap or it is not inside `file_bytes`.
+    pub fn header_slack<'b>(&self, file_bytes: &'b [u8]) -> Option<&'b [u8]> {
+        let size_of_headers = self
+            .optional_header
+            .as_ref()?
+            .windows_specific_fields
+            .size_of_headers() as usize;
+        let first_section_data = self
+            .sections
+            .0
+            .iter()
+            .filter(|section| section.row.size_of_raw_data != 0)
+            .map(|section| section.row.pointer_to_raw_data as usize)
+            .min()?;
+        if first_section_data <= size_of_headers {
+            return None;
+        }
+        file_bytes.get(size_of_headers..first_section_data)
+    }
+
+    /// The target machine from the COFF header.
+    ///
+    /// ```
+    /// use pewter::{pe::coff::ImageFileMachine, PEFile};
+    ///
+    /// let data = std::fs::read("fixtures/minimal_pe32_plus.dll").unwrap();
+    /// let pe = PEFile::parse(&data).unwrap();
+    /// assert_eq!(pe.machine(), ImageFileMachine::Amd64);
+    /// ```
+    #[inline(always)]
+    pub fn machine(&self) -> coff::ImageFileMachine {
+        self.coff_header.machine
+    }
+
+    /// The subsystem from the optional header, or `None` if there is no optional header.
+    ///
+    /// ```
+    /// use pewter::{pe::optional_header::ImageSubsystem, PEFile};
+    ///
+    /// let data = std::fs::read("fixtures/minimal_pe32.exe").unwrap();
+    /// let pe = PEFile::parse(&data).unwrap();
+    /// assert_eq!(pe.subsystem(), Some(ImageSubsystem::WindowsCui));
+    /// ```
+    #[inline(always)]
+    pub fn subsystem(&self) -> Option<optional_header::ImageSubsystem> {
         self.optional_header
             .as_ref()
-            .map(|optional_header| {
-                let data_dir = data_dir_fn(&optional_header.data_directories);
+            .map(|optional_header| optional_header.windows_specific_fields.subsystem())
+    }
+
+    /// Classifies the image from its [`ImageFileCharacteristics`](coff::ImageFileCharacteristics)
+    /// and optional header.
+    ///
+    /// Files without an optional header are [`PeKind::Object`]. Images with
+    /// [`FILE_SYSTEM`](coff::ImageFileCharacteristics::FILE_SYSTEM) set or the
+    /// [`Native`](optional_header::ImageSubsystem::Native) subsystem are [`PeKind::Driver`]; this
+    /// is a heuristic, native user-mode programs such as `smss.exe` are classified as drivers too.
+    /// Otherwise [`FILE_DLL`](coff::ImageFileCharacteristics::FILE_DLL) makes a [`PeKind::Dll`] and
+    /// [`EXECUTABLE_IMAGE`](coff::ImageFileCharacteristics::EXECUTABLE_IMAGE) a [`PeKind::Executable`].
+    pub fn kind(&self) -> PeKind {
+        let Some(optional_header) = &self.optional_header else {
+            return PeKind::Object;
+        };
+        let characteristics = self.coff_header.characteristics;
+        if characteristics.contains(coff::ImageFileCharacteristics::FILE_SYSTEM)
+            || optional_header.windows_specific_fields.subsystem()
+                == optional_header::ImageSubsystem::Native
+        {
+            PeKind::Driver
+        } else if characteristics.contains(coff::ImageFileCharacteristics::FILE_DLL) {
+            PeKind::Dll
+        } else if characteristics.contains(coff::ImageFileCharacteristics::EXECUTABLE_IMAGE) {
+            PeKind::Executable
+        } else {
+            PeKind::Unknown
+        }
+    }
+
+    /// The size the file should be: the end of the last section's raw data or of the certificate
+    /// table, whichever is further.
+    ///
+    /// Compare it to the length of the file to find truncated files, or files with data appended.
+    pub fn expected_file_size(&self) -> u64 {
+        let sections_end = self
+            .sections
+            .0
+            .iter()
+            .map(|section| {
+                section.row.pointer_to_raw_data as u64 + section.row.size_of_raw_data as u64
+            })
+            .max()
+            .unwrap_or(0);
+        // The certificate table is addressed by file offset and is usually at the end of the file.
+        let certificate_table_end = self
+            .optional_header
+            .as_ref()
+            .map(|optional_header| optional_header.data_directories.certificate_table)
+            .filter(|certificate_table| !certificate_table.is_null())
+            .map_or(0, |certificate_table| {
+                certificate_table.virtual_address as u64 + certificate_table.size as u64
+            });
+        sections_end.max(certificate_table_end)
+    }
+
+    /// The raw bytes of the architecture data directory.
+    ///
+    /// The spec reserves this directory and requires it to be zero, but some old toolchains used it.
+    #[inline(always)]
+    pub fn architecture_bytes<'b>(&self, file_bytes: &'b [u8]) -> Option<&'b [u8]> {
+        self.data_directory_bytes(file_bytes, SectionName::Architecture)
+    }
+
+    /// Calls `f` with the resource table bytes borrowed from `file_bytes`.
+    ///
+    /// Unlike [`PEFile::read_resource_directory`], the resource data is not copied.
+    #[inline(always)]
+    pub fn with_resource_bytes<R>(
+        &self,
+        file_bytes: &[u8],
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> Option<R> {
+        self.data_directory_bytes(file_bytes, SectionName::ResourceTable)
+            .map(f)
+    }
+
+    /// A hash of the shape of the headers, for grouping similar files.
+    ///
+    /// This is a 64-bit FNV-1a hash of the machine, the subsystem, the name and characteristics of
+    /// each section and which data directories are present. Timestamps, sizes and addresses are left
+    /// out, so rebuilds of the same program usually get the same fingerprint.
+    pub fn header_fingerprint(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash.write(&self.coff_header.machine.to_u16().to_le_bytes());
+        match &self.optional_header {
+            Some(optional_header) => {
+                let subsystem = optional_header.windows_specific_fields.subsystem();
+                hash.write(&subsystem.to_u16().to_le_bytes());
+
+                let data_directories = &optional_header.data_directories;
+                let present_directories = SectionName::ALL
+                    .iter()
+                    .filter(|name| !data_directories.get_directory(**name).is_null())
+                    .fold(0u16, |present, name| present | 1 << *name as u16);
+                hash.write(&present_directories.to_le_bytes());
+            }
+            None => hash.write(&[0xFF; 4]),
+        }
+        for section in self.sections.0.iter() {
+            hash.write(&section.row.name);
+            hash.write(&section.row.characteristics.bits().to_le_bytes());
+        }
+        hash.finish()
+    }
+
+    /// Write the headers and section data back out.
+    ///
+    /// Sections are written at their original `pointer_to_raw_data`. [`PEFile`] doesn't keep the
+    /// bytes outside of the headers and sections, so this does not round-trip them: the DOS stub and
+    /// Rich header between the DOS header and `e_lfanew` are written as zeros, and any overlay or
+    /// certificate table after the last section is left out. Use [`PEFile::to_bytes_over`] to keep them.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        self.to_bytes_over(&[])
+    }
+
+    /// Like [`PEFile::to_bytes`], but starts from a copy of `file_bytes`, so bytes that are not part
+    /// of the headers or sections, such as the DOS stub and overlay, are kept.
+    ///
+    /// The headers may overlap the DOS header when `e_lfanew` is less than its size, as in some very
+    /// small images. The overlapping bytes must be the same in both, or this returns an error.
+    pub fn to_bytes_over(&self, file_bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut dos_header = Vec::new();
+        dos_header.write(&self.dos_header)?;
+
+        let mut headers = Vec::new();
+        headers.write(Self::SIGNATURE)?;
+        headers.write(&self.coff_header)?;
+        let optional_header_end = headers.len() + self.coff_header.size_of_optional_header as usize;
+        if let Some(optional_header) = &self.optional_header {
+            headers.write(optional_header)?;
+        }
+        headers.resize(headers.len().max(optional_header_end), 0);
+        for section in self.sections.0.iter() {
+            headers.write(&section.row)?;
+        }
+
+        let e_lfanew = self.dos_header.e_lfanew as usize;
+        if let Some(overlap) = dos_header.get(e_lfanew..) {
+            let overlap = &overlap[..overlap.len().min(headers.len())];
+            if *overlap != headers[..overlap.len()] {
+                return Err(PewterError::invalid_image_format(
+                    "The DOS header and the PE headers overlap with different values.",
+                ));
+            }
+        }
+
+        let file_end = self
+            .sections
+            .0
+            .iter()
+            .map(|section| section.row.pointer_to_raw_data as usize + section.data.len())
+            .fold(e_lfanew + headers.len(), usize::max)
+            .max(dos_header.len())
+            .max(file_bytes.len());
+        let mut buffer = vec![0u8; file_end];
+        buffer[..file_bytes.len()].copy_from_slice(file_bytes);
+        buffer[..dos_header.len()].copy_from_slice(&dos_header);
+        // Section data is written after the headers, so it wins if the two overlap.
+        buffer[e_lfanew..e_lfanew + headers.len()].copy_from_slice(&headers);
+        for section in self.sections.0.iter() {
+            let start = section.row.pointer_to_raw_data as usize;
+            buffer[start..start + section.data.len()].copy_from_slice(section.data);
+        }
+        Ok(buffer)
+    }
+
+    /// Computes the image checksum of `file_bytes`, as stored in
+    /// [`check_sum`](optional_header::OptionalHeaderWindowsSpecificFields::check_sum).
+    ///
+    /// The checksum field itself, at its offset from this file's `e_lfanew`, is skipped.
+    pub fn compute_checksum(&self, file_bytes: &[u8]) -> u32 {
+        /// Offset of `check_sum` in the optional header. This is the same for PE32 and PE32+.
+        const CHECK_SUM_OFFSET: usize = 64;
+        let check_sum_offset = self.dos_header.e_lfanew as usize
+            + Self::SIGNATURE.len()
+            + coff::CoffFileHeader::SIZE
+            + CHECK_SUM_OFFSET;
+
+        let mut sum = 0u32;
+        for (index, word) in file_bytes.chunks(2).enumerate() {
+            let offset = index * 2;
+            if (check_sum_offset..check_sum_offset + 4).contains(&offset) {
+                continue;
+            }
+            let word = u16::from_le_bytes([word[0], *word.get(1).unwrap_or(&0)]);
+            sum += word as u32;
+            sum = (sum & 0xFFFF) + (sum >> 16);
+        }
+        sum.wrapping_add(file_bytes.len() as u32)
+    }
+
+    /// Parses the data directory `name`.
+    /// Directories past `number_of_rva_and_sizes` are treated as absent.
+    fn read_section_data<T: ParseSectionData>(&self, name: SectionName) -> Result<Option<T>> {
+        self.optional_header
+            .as_ref()
+            .and_then(|optional_header| {
+                let data_dir = optional_header.try_get_data_directory(name)?;
                 self.sections
-                    .find_data_directory_data_map(data_dir, |data| {
+                    .find_data_directory_data_map(&data_dir, |data| {
                         T::parse(data, &self.sections, optional_header, &self.coff_header)
                     })
+                    .transpose()
             })
             .transpose()
-            .map(|x| x.flatten())
     }
 
     #[inline(always)]
     pub fn read_export_table(&self) -> Result<Option<edata::ExportTableDataDirectory>> {
-        self.read_section_data(|dirs| &dirs.export_table)
+        self.read_section_data(SectionName::ExportTable)
     }
 
     #[inline(always)]
     pub fn read_import_table(&self) -> Result<Option<idata::ImportTableDataDirectory>> {
-        self.read_section_data(|dirs| &dirs.import_table)
+        self.read_section_data(SectionName::ImportTable)
     }
 
     #[inline(always)]
     pub fn read_resource_directory(&self) -> Result<Option<rsrc::ResourceDataDirectory>> {
-        self.read_section_data(|dirs: &DataDirectories| &dirs.resource_table)
+        self.read_section_data(SectionName::ResourceTable)
     }
 
     #[inline(always)]
     pub fn read_exeption_table(&self) -> Result<Option<pdata::ExceptionHandlerDataDirectory>> {
-        self.read_section_data(|dirs| &dirs.exception_table)
+        self.read_section_data(SectionName::ExceptionTable)
+    }
+
+    /// The file offset of the attribute certificate table, if the image has one.
+    ///
+    /// The `virtual_address` of the certificate table data directory is a file offset, not an RVA.
+    pub fn certificate_table_offset(&self) -> Option<usize> {
+        self.optional_header
+            .as_ref()
+            .map(|optional_header| optional_header.data_directories.certificate_table)
+            .filter(|certificate_table| !certificate_table.is_null())
+            .map(|certificate_table| certificate_table.virtual_address as usize)
     }
 
+    /// Read the certificate table from `file_bytes`, the same data this file was parsed from.
     #[inline(always)]
-    pub fn read_certificate_table(&self) -> Result<Option<certificate::CertificateDataDirectory>> {
-        self.read_section_data(|dirs| &dirs.certificate_table)
+    pub fn read_certificate_table(
+        &self,
+        file_bytes: &[u8],
+    ) -> Result<Option<certificate::CertificateDataDirectory>> {
+        self.optional_header
+            .as_ref()
+            .and_then(|optional_header| {
+                optional_header.try_get_data_directory(SectionName::CertificateTable)
+            })
+            .map(|certificate_table| {
+                certificate::CertificateDataDirectory::parse_at_file_offset(
+                    file_bytes,
+                    &certificate_table,
+                )
+            })
+            .transpose()
+            .map(|x| x.flatten())
     }
 
     #[inline(always)]
     pub fn read_base_relocation_table(
         &self,
     ) -> Result<Option<base_relocation::BaseRelocationDataDitectory>> {
-        self.read_section_data(|dirs| &dirs.base_relocation_table)
+        self.read_section_data(SectionName::BaseRelocationTable)
+    }
+
+    #[inline(always)]
+    pub fn read_debug_directory(&self) -> Result<Option<debug::DebugDataDirectory>> {
+        self.read_section_data(SectionName::Debug)
+    }
+
+    #[inline(always)]
+    pub fn read_tls_table(&self) -> Result<Option<tls::TlsDirectory>> {
+        self.read_section_data(SectionName::TlsTable)
+    }
+
+    #[inline(always)]
+    pub fn read_load_config_table(&self) -> Result<Option<load_config::LoadConfigDataDirectory>> {
+        self.read_section_data(SectionName::LoadConfigTable)
+    }
+
+    #[inline(always)]
+    pub fn read_bound_import_table(
+        &self,
+    ) -> Result<Option<bound_import::BoundImportDataDirectory>> {
+        self.read_section_data(SectionName::BoundImport)
+    }
+
+    #[inline(always)]
+    pub fn read_import_address_table(
+        &self,
+    ) -> Result<Option<idata::ImportAddressTableDataDirectory>> {
+        self.read_section_data(SectionName::Ita)
+    }
+
+    #[inline(always)]
+    pub fn read_delay_import_table(
+        &self,
+    ) -> Result<Option<delay_import::DelayImportDataDirectory>> {
+        self.read_section_data(SectionName::DelayImportDescriptor)
     }
 
     #[inline(always)]
     pub fn read_clr_runtime_header(
         &self,
     ) -> Result<Option<cor20::ImageCor20Header>> {
-        self.read_section_data(|dirs| &dirs.clr_runtime_header)
+        self.read_section_data(SectionName::ClrRuntimeHeader)
+    }
+}
+
+/// 64-bit FNV-1a, used by [`PEFile::header_fingerprint`] because it is the same on every platform.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        io::Writer,
+        pe::{
+            coff::CoffFileHeader, dos::ImageDosHeader, file::definition::PEImageDef,
+            optional_header::data_directories::ImageDataDirectory, sections::SectionFlags,
+        },
+        vec::Vec,
+    };
+
+    /// Parses `bytes`, writes it back with [`PEFile::to_bytes`] and checks that the headers and
+    /// section table parse back the same.
+    ///
+    /// `to_bytes` drops the DOS stub and overlay, which changes the checksum, so the checksum is
+    /// checked on [`PEFile::to_bytes_over`], which must give back the original bytes.
+    pub(crate) fn assert_roundtrips(bytes: &[u8]) {
+        let pe = PEFile::parse(bytes).unwrap();
+        let written = pe.to_bytes().unwrap();
+        let reparsed = PEFile::parse(&written).unwrap();
+
+        assert_eq!(reparsed.dos_header, pe.dos_header);
+        assert_eq!(reparsed.coff_header, pe.coff_header);
+        assert_eq!(reparsed.optional_header, pe.optional_header);
+        assert_eq!(reparsed.sections, pe.sections);
+        assert_eq!(reparsed.special_sections, pe.special_sections);
+
+        let written_over = pe.to_bytes_over(bytes).unwrap();
+        assert_eq!(written_over, bytes);
+        let check_sum = pe
+            .optional_header
+            .as_ref()
+            .unwrap()
+            .windows_specific_fields
+            .check_sum();
+        if check_sum != 0 {
+            assert_eq!(pe.compute_checksum(bytes), check_sum);
+        }
+        assert_eq!(
+            reparsed.compute_checksum(&written_over),
+            pe.compute_checksum(bytes)
+        );
+    }
+
+    #[test]
+    fn minimal_pe32_roundtrips() {
+        assert_roundtrips(include_bytes!("../../../fixtures/minimal_pe32.exe"));
+    }
+
+    #[test]
+    fn minimal_pe32_plus_roundtrips() {
+        assert_roundtrips(include_bytes!("../../../fixtures/minimal_pe32_plus.dll"));
+    }
+
+    #[test]
+    fn stub_and_overlay_roundtrip_with_to_bytes_over() {
+        let bytes = include_bytes!("../../../fixtures/stub_overlay_pe32.exe");
+        assert_roundtrips(bytes);
+
+        let pe = PEFile::parse(bytes).unwrap();
+        let written = pe.to_bytes().unwrap();
+        assert!(
+            written[ImageDosHeader::SIZE..pe.dos_header.e_lfanew as usize]
+                .iter()
+                .all(|b| *b == 0)
+        );
+        assert!(written.len() < bytes.len());
+    }
+
+    #[test]
+    fn pe_headers_overlapping_dos_header() {
+        // Puts `e_lfanew` (at 0x3C) on the optional header's `base_of_code` (at 0x14).
+        const E_LFANEW: usize = 0x3C - PEFile::SIGNATURE.len() - CoffFileHeader::SIZE - 0x14;
+        let bytes = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let mut pe = PEFile::parse(bytes).unwrap();
+        pe.dos_header.e_lfanew = E_LFANEW as u32;
+        assert!(pe.to_bytes().is_err());
+
+        let optional_header = pe.optional_header.as_mut().unwrap();
+        optional_header.standard_fields.base_of_code = E_LFANEW as u32;
+        let mut headers = Vec::new();
+        headers.write(PEFile::SIGNATURE).unwrap();
+        headers.write(&pe.coff_header).unwrap();
+        headers.write(&*optional_header).unwrap();
+        let mut dos_header = Vec::new();
+        dos_header.write(&pe.dos_header).unwrap();
+        dos_header[E_LFANEW..].copy_from_slice(&headers[..ImageDosHeader::SIZE - E_LFANEW]);
+        pe.dos_header = ImageDosHeader::read(&mut dos_header.as_slice()).unwrap();
+        assert_eq!(pe.dos_header.e_lfanew, E_LFANEW as u32);
+
+        let written = pe.to_bytes().unwrap();
+        let reparsed = PEFile::parse(&written).unwrap();
+        assert_eq!(reparsed.dos_header, pe.dos_header);
+        assert_eq!(reparsed.optional_header, pe.optional_header);
+    }
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        let hash = |bytes: &[u8]| {
+            let mut hash = Fnv1a::new();
+            hash.write(bytes);
+            hash.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn header_fingerprint_ignores_timestamps() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let pe = PEFile::parse(data).unwrap();
+
+        let mut rebuilt = pe.clone();
+        rebuilt.coff_header.date_time_stamp = pe.coff_header.date_time_stamp.wrapping_add(1);
+        assert_eq!(rebuilt.header_fingerprint(), pe.header_fingerprint());
+
+        rebuilt.sections.0[0].row.characteristics |= SectionFlags::MEM_WRITE;
+        assert_ne!(rebuilt.header_fingerprint(), pe.header_fingerprint());
+    }
+
+    #[test]
+    fn directory_regions_of_samples() {
+        let imports = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let pe = PEFile::parse(imports).unwrap();
+        let data_directories = &pe.optional_header.as_ref().unwrap().data_directories;
+        let import_table = data_directories.import_table;
+        assert!(pe.directory_regions().contains(&(
+            SectionName::ImportTable,
+            import_table.virtual_address,
+            import_table.size
+        )));
+
+        let exports = include_bytes!("../../../fixtures/minimal_pe32_plus.dll");
+        let pe = PEFile::parse(exports).unwrap();
+        let data_directories = &pe.optional_header.as_ref().unwrap().data_directories;
+        let export_table = data_directories.export_table;
+        assert!(pe.directory_regions().contains(&(
+            SectionName::ExportTable,
+            export_table.virtual_address,
+            export_table.size
+        )));
+    }
+
+    #[test]
+    fn directories_past_number_of_rva_and_sizes_are_absent() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let mut pe = PEFile::parse(data).unwrap();
+        assert!(pe.read_import_table().unwrap().is_some());
+
+        let optional_header = pe.optional_header.as_mut().unwrap();
+        optional_header
+            .windows_specific_fields
+            .set_number_of_rva_and_sizes(0);
+        assert!(optional_header
+            .try_get_data_directory(SectionName::ImportTable)
+            .is_none());
+        assert!(pe.read_import_table().unwrap().is_none());
+    }
+
+    #[test]
+    fn header_slack_between_headers_and_first_section() {
+        let original = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let mut pe = PEFile::parse(original).unwrap();
+        assert_eq!(pe.header_slack(original), None);
+
+        // Shrink the headers to leave 16 bytes before the section data.
+        let first_section_data = pe.sections.0[0].row.pointer_to_raw_data as usize;
+        let mut data = original.to_vec();
+        data[first_section_data - 16..first_section_data].fill(0xAA);
+        pe.optional_header
+            .as_mut()
+            .unwrap()
+            .windows_specific_fields
+            .set_size_of_headers(first_section_data as u32 - 16);
+        assert_eq!(pe.header_slack(&data), Some([0xAA; 16].as_slice()));
+    }
+
+    #[test]
+    fn force_optional_header_ignores_zero_size() {
+        let mut data = include_bytes!("../../../fixtures/minimal_pe32.exe").to_vec();
+        let e_lfanew = PEFile::parse_headers(&data).unwrap().dos_header.e_lfanew;
+        // `size_of_optional_header` is followed only by `characteristics` in the COFF header.
+        let coff_header_end =
+            e_lfanew as usize + PEFile::SIGNATURE.len() + coff::CoffFileHeader::SIZE;
+        let size_of_optional_header_offset = coff_header_end - 4;
+        data[size_of_optional_header_offset..][..2].fill(0);
+
+        let headers = PEFile::parse_headers(&data).unwrap();
+        assert!(headers.optional_header.is_none());
+
+        let options = Options {
+            force_optional_header: true,
+            ..Default::default()
+        };
+        let pe = PEFile::parse_with_options(&data, options).unwrap();
+        assert_eq!(pe.coff_header.size_of_optional_header, 0);
+        let optional_header = pe.optional_header.unwrap();
+        assert_eq!(
+            optional_header.standard_fields.magic,
+            optional_header::OptionalHeaderMagic::PE32
+        );
+        assert_eq!(pe.sections.0[0].row.name_str(), ".text");
+    }
+
+    #[test]
+    fn too_many_sections_is_an_error() {
+        let mut data = include_bytes!("../../../fixtures/minimal_pe32.exe").to_vec();
+        let e_lfanew = PEFile::parse_headers(&data).unwrap().dos_header.e_lfanew;
+        let number_of_sections_offset = e_lfanew as usize + PEFile::SIGNATURE.len() + 2;
+        data[number_of_sections_offset..][..2].copy_from_slice(&u16::MAX.to_le_bytes());
+        data.truncate(0x200);
+
+        assert!(matches!(
+            PEFile::parse_headers(&data),
+            Err(PewterError::LimitExceeded {
+                limit_name: "sections",
+                limit: Options::DEFAULT_MAX_SECTIONS,
+            })
+        ));
+    }
+
+    #[test]
+    fn kind_of_exe_and_dll() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let mut pe = PEFile::parse(data).unwrap();
+        assert_eq!(pe.kind(), PeKind::Executable);
+
+        pe.coff_header.characteristics |= coff::ImageFileCharacteristics::FILE_DLL;
+        assert_eq!(pe.kind(), PeKind::Dll);
+
+        let optional_header = pe.optional_header.as_mut().unwrap();
+        optional_header
+            .windows_specific_fields
+            .set_subsystem(optional_header::ImageSubsystem::Native);
+        assert_eq!(pe.kind(), PeKind::Driver);
+
+        pe.coff_header.characteristics = coff::ImageFileCharacteristics::empty();
+        pe.optional_header
+            .as_mut()
+            .unwrap()
+            .windows_specific_fields
+            .set_subsystem(optional_header::ImageSubsystem::WindowsGui);
+        assert_eq!(pe.kind(), PeKind::Unknown);
+        pe.optional_header = None;
+        assert_eq!(pe.kind(), PeKind::Object);
+    }
+
+    #[test]
+    fn expected_file_size_of_truncated_file() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let pe = PEFile::parse(data).unwrap();
+        assert_eq!(pe.expected_file_size(), data.len() as u64);
+
+        // Cut the file in the middle of the last section.
+        let truncated = &data[..data.len() - 0x10];
+        assert!(pe.expected_file_size() > truncated.len() as u64);
+        let headers = PEFile::parse_headers(truncated).unwrap();
+        let last_section = &headers.section_table[headers.section_table.len() - 1];
+        assert_eq!(
+            last_section.pointer_to_raw_data as u64 + last_section.size_of_raw_data as u64,
+            pe.expected_file_size()
+        );
+    }
+
+    #[test]
+    fn read_rva_in_code_section() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        assert_eq!(crate::read_rva(data, 0x1001).unwrap(), [0xC0, 0xC3]);
+        assert!(crate::read_rva(data, 0x5000).is_err());
+    }
+
+    #[test]
+    fn with_resource_bytes_borrows_directory() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let mut pe = PEFile::parse_minimal(data).unwrap();
+        let idata = &pe.sections.0[1].row;
+        let (virtual_address, offset) = (idata.virtual_address, idata.pointer_to_raw_data);
+        pe.optional_header
+            .as_mut()
+            .unwrap()
+            .data_directories
+            .resource_table = ImageDataDirectory {
+            virtual_address: virtual_address + 0x10,
+            size: 0x20,
+        };
+
+        let resource_bytes = pe.with_resource_bytes(data, |bytes| bytes.as_ptr_range());
+        let offset = offset as usize + 0x10;
+        assert_eq!(
+            resource_bytes,
+            Some(data[offset..offset + 0x20].as_ptr_range())
+        );
+    }
+
+    #[test]
+    fn data_directory_range_of_debug_directory() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let mut pe = PEFile::parse(data).unwrap();
+        let optional_header = pe.optional_header.as_mut().unwrap();
+        optional_header.data_directories.debug = ImageDataDirectory {
+            virtual_address: 0x1010,
+            size: 0x1C,
+        };
+        optional_header.ensure_directory_count(SectionName::Debug as u32 + 1);
+        assert_eq!(
+            pe.data_directory_range(SectionName::Debug),
+            Some((0x1010, 0x1C))
+        );
+        assert_eq!(pe.data_directory_range(SectionName::TlsTable), None);
+
+        pe.optional_header
+            .as_mut()
+            .unwrap()
+            .windows_specific_fields
+            .set_number_of_rva_and_sizes(SectionName::Debug as u32);
+        assert_eq!(pe.data_directory_range(SectionName::Debug), None);
+    }
+
+    #[test]
+    fn data_directory_bytes_by_name() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe").as_slice();
+        let pe = PEFile::parse(data).unwrap();
+        let import_table = pe
+            .optional_header
+            .as_ref()
+            .unwrap()
+            .data_directories
+            .import_table;
+
+        let import_table_bytes = pe
+            .data_directory_bytes(data, SectionName::ImportTable)
+            .unwrap();
+        assert_eq!(import_table_bytes.len(), import_table.size as usize);
+        assert_eq!(
+            import_table_bytes,
+            &pe.sections
+                .find_rva_data(import_table.virtual_address as usize)
+                .unwrap()[..import_table.size as usize]
+        );
+        assert!(pe
+            .data_directory_bytes(data, SectionName::Architecture)
+            .is_none());
+    }
+
+    fn dos_header_bytes(e_magic: u16, e_lfanew: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.write(&dos::ImageDosHeader {
+            e_magic,
+            e_lfanew,
+            ..Default::default()
+        })
+        .unwrap();
+        data
+    }
+
+    #[test]
+    fn parse_rejects_bad_e_magic() {
+        let mut data = dos_header_bytes(0x1234, 0x40);
+        data.write_slice(&PEFile::SIGNATURE).unwrap();
+        assert!(matches!(
+            PEFile::parse(&data),
+            Err(PewterError::InvalidImageFormat { .. })
+        ));
+    }
+
+    #[test]
+    fn e_lfanew_at_eof_is_not_enough_data() {
+        let data = dos_header_bytes(dos::ImageDosHeader::MAGIC_CONSTANT, 0x40);
+        assert_eq!(data.len(), 0x40);
+        assert!(matches!(
+            PEFile::parse_headers(&data),
+            Err(PewterError::NotEnoughDataLeft {
+                attempted_read: 4,
+                offset: Some(0x40),
+            })
+        ));
+    }
+
+    #[test]
+    fn bad_signature_reports_e_lfanew() {
+        let mut data = dos_header_bytes(dos::ImageDosHeader::MAGIC_CONSTANT, 0xF0);
+        data.resize(0xF0, 0);
+        data.write_slice(b"NE\0\0").unwrap();
+
+        let error = PEFile::parse(&data).unwrap_err();
+        assert_eq!(error.offset(), Some(0xF0));
+        assert_eq!(
+            crate::string::ToString::to_string(&error),
+            "Invalid image format: Bad PE signature. (at 0xF0)"
+        );
+    }
+
+    #[test]
+    fn parse_rejects_e_lfanew_past_eof() {
+        let data = dos_header_bytes(dos::ImageDosHeader::MAGIC_CONSTANT, 0x1000);
+        assert!(matches!(
+            PEFile::parse(&data),
+            Err(PewterError::InvalidImageFormat { .. })
+        ));
+    }
+
+    #[test]
+    fn parse_headers_without_section_data() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let pe = PEFile::parse(data).unwrap();
+        let optional_header = pe.optional_header.as_ref().unwrap();
+        let size_of_headers = optional_header.windows_specific_fields.size_of_headers();
+        let data = &data[..size_of_headers as usize];
+
+        assert!(PEFile::parse_minimal(data).is_err());
+        let headers = PEFile::parse_headers(data).unwrap();
+        assert_eq!(headers.coff_header.number_of_sections, 2);
+        assert_eq!(headers.section_table[0].name_str(), ".text");
+        assert_eq!(headers.section_table[0].size_of_raw_data, 3);
+    }
+
+    #[test]
+    fn certificate_table_is_read_at_file_offset() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let mut def = PEImageDef::from_pe_file(PEFile::parse(data).unwrap());
+        let unsigned_len = def.write_file().unwrap().len();
+
+        let mut certificate = Vec::new();
+        certificate.write(16u32).unwrap();
+        certificate.write(0x0200u16).unwrap();
+        certificate.write(0x0002u16).unwrap();
+        certificate.write_slice(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let offset = unsigned_len.next_multiple_of(8);
+        def.optional_header.data_directories.certificate_table = ImageDataDirectory {
+            virtual_address: offset as u32,
+            size: certificate.len() as u32,
+        };
+
+        let mut data = def.write_file().unwrap();
+        data.resize(offset, 0);
+        data.write_slice(&certificate).unwrap();
+
+        let pe = PEFile::parse(&data).unwrap();
+        assert_eq!(pe.certificate_table_offset(), Some(offset));
+        assert!(pe.sections.find_rva(offset as u32).is_none());
+
+        let certificate_table = pe.special_sections.certificate_table.as_ref().unwrap();
+        let authenticode = certificate_table.authenticode().unwrap();
+        assert_eq!(authenticode.certificate, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(
+            pe.read_certificate_table(&data).unwrap().as_ref(),
+            Some(certificate_table)
+        );
     }
 }
@@ -0,0 +1,342 @@
+//! Append-only editing of an existing image.
+//!
+//! [`PEImageDef::from_pe_file`](super::definition::PEImageDef::from_pe_file) copies every section
+//! into an owned buffer. [`PEPatcher`] instead borrows the original file and only records the
+//! changes, so the output can be streamed straight from the original bytes.
+use crate::{
+    error::{PewterError, Result},
+    io::{WriteData, Writer},
+    pe::{
+        coff::CoffFileHeader,
+        sections::{SectionFlags, SectionTableRow},
+    },
+    string::String,
+    vec::Vec,
+};
+
+use super::{definition::align_up, PEFile, PEHeaders};
+
+/// Records byte patches and appended sections for an existing image.
+pub struct PEPatcher<'a> {
+    data: &'a [u8],
+    headers: PEHeaders,
+    patches: Vec<(usize, Vec<u8>)>,
+    appended_sections: Vec<AppendedSection>,
+}
+
+struct AppendedSection {
+    row: SectionTableRow,
+    data: Vec<u8>,
+}
+
+impl<'a> PEPatcher<'a> {
+    /// Offset of `number_of_sections` in the COFF header.
+    const NUMBER_OF_SECTIONS_OFFSET: usize = 2;
+    /// Offset of `size_of_image` in the optional header. This is the same for PE32 and PE32+.
+    const SIZE_OF_IMAGE_OFFSET: usize = 56;
+
+    /// Parses the headers of `data`. Section data is not copied.
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        let headers = PEFile::parse_headers(data)?;
+        if headers.optional_header.is_none() {
+            return Err(PewterError::invalid_image_format(
+                "PEPatcher requires an image with an optional header.",
+            ));
+        }
+        Ok(Self {
+            data,
+            headers,
+            patches: Vec::new(),
+            appended_sections: Vec::new(),
+        })
+    }
+
+    /// The headers of the original file.
+    pub fn headers(&self) -> &PEHeaders {
+        &self.headers
+    }
+
+    /// Replace the bytes at `offset` in the original file with `bytes`.
+    ///
+    /// Patches must be inside the original file and must not overlap each other.
+    pub fn patch(&mut self, offset: usize, bytes: &[u8]) -> Result<()> {
+        let end = offset
+            .checked_add(bytes.len())
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| PewterError::invalid_image_format("Patch is outside of the file."))?;
+        let overlaps = self
+            .patches
+            .iter()
+            .any(|(start, patch)| offset < start + patch.len() && *start < end);
+        if overlaps {
+            return Err(PewterError::invalid_image_format(
+                "Patch overlaps an existing patch.",
+            ));
+        }
+        self.patches.push((offset, Vec::from(bytes)));
+        Ok(())
+    }
+
+    /// Append a new section after the last section, returning its virtual address.
+    ///
+    /// The section table must have room for another row before the first section's data,
+    /// inside the original file. The section is loaded with a size of at least `virtual_size`.
+    pub fn append_section(
+        &mut self,
+        name: impl Into<String>,
+        characteristics: SectionFlags,
+        data: Vec<u8>,
+        virtual_size: u32,
+    ) -> Result<u32> {
+        let row_offset =
+            self.section_table_offset() + SectionTableRow::SIZE * (self.section_count() + 1);
+        if row_offset > self.first_section_data_offset() {
+            return Err(PewterError::invalid_image_format(
+                "Not enough space in the headers for another section.",
+            ));
+        }
+        // The row is written as a patch over the original bytes, so it must be inside the file.
+        if row_offset > self.data.len() {
+            return Err(PewterError::invalid_image_format(
+                "The file ends before the space for another section.",
+            ));
+        }
+
+        let windows_specific_fields = &self.optional_header().windows_specific_fields;
+        let section_alignment = windows_specific_fields.section_alignment() as usize;
+        let file_alignment = windows_specific_fields.file_alignment() as usize;
+
+        let virtual_address = align_up(self.image_end(), section_alignment);
+        let pointer_to_raw_data = align_up(self.file_end(), file_alignment);
+        let size_of_raw_data = align_up(data.len(), file_alignment);
+
+        let name = {
+            let name: String = name.into();
+            let mut name_buffer = [0u8; 8];
+            for (c, buff) in name.bytes().take(8).zip(name_buffer.iter_mut()) {
+                *buff = c;
+            }
+            name_buffer
+        };
+
+        let row = SectionTableRow {
+            name,
+            virtual_size: virtual_size.max(data.len() as u32),
+            virtual_address: u32::try_from(virtual_address)
+                .map_err(|_| PewterError::invalid_image_format("Image exceeds 4GB."))?,
+            size_of_raw_data: size_of_raw_data as u32,
+            pointer_to_raw_data: u32::try_from(pointer_to_raw_data)
+                .map_err(|_| PewterError::invalid_image_format("File exceeds 4GB."))?,
+            characteristics,
+            ..Default::default()
+        };
+        let virtual_address = row.virtual_address;
+        self.appended_sections.push(AppendedSection { row, data });
+        Ok(virtual_address)
+    }
+
+    /// Write the patched file to `writer`, streaming the original bytes.
+    pub fn write_to(&self, writer: &mut impl Writer) -> Result<()> {
+        let header_patches = self.header_patches()?;
+
+        let mut patches: Vec<(usize, &[u8])> = self
+            .patches
+            .iter()
+            .map(|(offset, bytes)| (*offset, bytes.as_slice()))
+            .chain(
+                header_patches
+                    .iter()
+                    .map(|(offset, bytes)| (*offset, bytes.as_slice())),
+            )
+            .collect();
+        patches.sort_by_key(|(offset, _)| *offset);
+
+        let mut position = 0;
+        for (offset, bytes) in patches {
+            if offset < position {
+                return Err(PewterError::invalid_image_format(
+                    "Patch overlaps the headers updated by PEPatcher.",
+                ));
+            }
+            writer.write_slice(&self.data[position..offset])?;
+            writer.write_slice(bytes)?;
+            position = offset + bytes.len();
+        }
+        writer.write_slice(&self.data[position..])?;
+
+        let mut file_end = self.data.len();
+        for section in &self.appended_sections {
+            let pointer_to_raw_data = section.row.pointer_to_raw_data as usize;
+            writer.write_zeros(pointer_to_raw_data - file_end)?;
+            writer.write_slice(&section.data)?;
+            writer.write_zeros(section.row.size_of_raw_data as usize - section.data.len())?;
+            file_end = pointer_to_raw_data + section.row.size_of_raw_data as usize;
+        }
+        Ok(())
+    }
+
+    /// Write the patched file into a new buffer.
+    pub fn write_file(&self) -> Result<Vec<u8>> {
+        let mut buffer = Vec::with_capacity(self.file_end());
+        self.write_to(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// The section table rows and the header fields that change when sections are appended.
+    fn header_patches(&self) -> Result<Vec<(usize, Vec<u8>)>> {
+        if self.appended_sections.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let coff_header_offset =
+            self.headers.dos_header.e_lfanew as usize + PEFile::SIGNATURE.len();
+        let optional_header_offset = coff_header_offset + CoffFileHeader::SIZE;
+        let section_alignment = self
+            .optional_header()
+            .windows_specific_fields
+            .section_alignment() as usize;
+
+        let number_of_sections = u16::try_from(self.section_count())
+            .map_err(|_| PewterError::invalid_image_format("Too many sections."))?;
+        let size_of_image = align_up(self.image_end(), section_alignment) as u32;
+
+        let mut rows = Vec::new();
+        for section in &self.appended_sections {
+            section.row.write_to(&mut rows)?;
+        }
+
+        Ok(Vec::from([
+            (
+                coff_header_offset + Self::NUMBER_OF_SECTIONS_OFFSET,
+                Vec::from(number_of_sections.to_le_bytes()),
+            ),
+            (
+                optional_header_offset + Self::SIZE_OF_IMAGE_OFFSET,
+                Vec::from(size_of_image.to_le_bytes()),
+            ),
+            (
+                self.section_table_offset()
+                    + SectionTableRow::SIZE * self.headers.section_table.len(),
+                rows,
+            ),
+        ]))
+    }
+
+    fn optional_header(&self) -> &crate::pe::optional_header::OptionalHeader {
+        // Checked in `new`.
+        self.headers.optional_header.as_ref().unwrap()
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &SectionTableRow> {
+        self.headers
+            .section_table
+            .iter()
+            .chain(self.appended_sections.iter().map(|s| &s.row))
+    }
+
+    fn section_count(&self) -> usize {
+        self.headers.section_table.len() + self.appended_sections.len()
+    }
+
+    fn section_table_offset(&self) -> usize {
+        self.headers.dos_header.e_lfanew as usize
+            + PEFile::SIGNATURE.len()
+            + CoffFileHeader::SIZE
+            + self.headers.coff_header.size_of_optional_header as usize
+    }
+
+    /// The first byte after the headers that is used by a section.
+    fn first_section_data_offset(&self) -> usize {
+        self.headers
+            .section_table
+            .iter()
+            .filter(|row| row.size_of_raw_data != 0)
+            .map(|row| row.pointer_to_raw_data as usize)
+            .chain([self
+                .optional_header()
+                .windows_specific_fields
+                .size_of_headers() as usize])
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn image_end(&self) -> usize {
+        self.rows()
+            .map(|row| row.virtual_address as usize + row.virtual_size as usize)
+            .max()
+            .unwrap_or(0)
+            .max(
+                self.optional_header()
+                    .windows_specific_fields
+                    .size_of_headers() as usize,
+            )
+    }
+
+    fn file_end(&self) -> usize {
+        self.appended_sections
+            .iter()
+            .map(|s| s.row.pointer_to_raw_data as usize + s.row.size_of_raw_data as usize)
+            .max()
+            .unwrap_or(0)
+            .max(self.data.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patch_byte_and_append_section() {
+        let original = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let mut patcher = PEPatcher::new(original).unwrap();
+        let text_offset = patcher.headers().section_table[0].pointer_to_raw_data as usize;
+        patcher.patch(text_offset, &[0x90]).unwrap();
+        assert!(patcher.patch(text_offset, &[0x90]).is_err());
+        let virtual_address = patcher
+            .append_section(
+                ".new",
+                SectionFlags::CNT_INITIALIZED_DATA | SectionFlags::MEM_READ,
+                Vec::from([1, 2, 3]),
+                0x1800,
+            )
+            .unwrap();
+        assert_eq!(virtual_address, 0x3000);
+
+        let patched = patcher.write_file().unwrap();
+        let pe = PEFile::parse(&patched).unwrap();
+        assert_eq!(pe.coff_header.number_of_sections, 3);
+        assert_eq!(pe.sections.0[0].data, [0x90, 0xC0, 0xC3]);
+
+        let new_section = &pe.sections.0[2];
+        assert_eq!(new_section.row.name_str(), ".new");
+        assert_eq!(new_section.row.virtual_address, 0x3000);
+        assert_eq!(new_section.row.virtual_size, 0x1800);
+        assert_eq!(new_section.row.pointer_to_raw_data, 0x600);
+        assert_eq!(&new_section.data[..3], [1, 2, 3]);
+        assert_eq!(patched.len(), 0x800);
+
+        let optional_header = pe.optional_header.unwrap();
+        assert_eq!(
+            optional_header.windows_specific_fields.size_of_image(),
+            0x5000
+        );
+    }
+
+    #[test]
+    fn append_section_to_truncated_headers_is_an_error() {
+        let original = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let headers = PEFile::parse_headers(original).unwrap();
+        let section_table_end = headers.dos_header.e_lfanew as usize
+            + PEFile::SIGNATURE.len()
+            + CoffFileHeader::SIZE
+            + headers.coff_header.size_of_optional_header as usize
+            + SectionTableRow::SIZE * headers.section_table.len();
+
+        let mut patcher = PEPatcher::new(&original[..section_table_end]).unwrap();
+        assert!(patcher
+            .append_section(".new", SectionFlags::MEM_READ, Vec::from([1]), 0)
+            .is_err());
+        assert_eq!(patcher.write_file().unwrap(), original[..section_table_end]);
+    }
+}
@@ -0,0 +1,310 @@
+//! Checks for spec violations that don't stop an image from being parsed.
+use crate::{
+    pe::{
+        coff::ImageFileCharacteristics,
+        optional_header::{data_directories::SectionName, OptionalHeaderMagic},
+        sections::SectionFlags,
+    },
+    vec::Vec,
+};
+
+use super::{definition::PEImageDef, PEFile};
+
+/// A spec violation found by [`PEFile::validate`] or [`PEImageDef::validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationWarning {
+    /// The optional header magic is not PE32 or PE32+.
+    /// The optional header was read with the PE32 layout.
+    UnknownOptionalHeaderMagic { magic: u16 },
+    /// The size of the global pointer data directory must be zero.
+    NonZeroGlobalPtrSize { size: u32 },
+    /// The architecture data directory is reserved and must be zero.
+    NonZeroArchitecture,
+    /// An executable image (not a DLL) has no section that is both
+    /// [`SectionFlags::CNT_CODE`] and [`SectionFlags::MEM_EXECUTE`], so there is no code to run.
+    NoExecutableSection,
+    /// The section table is not in ascending order of virtual address.
+    /// See [`Sections::is_sorted_by_rva`](crate::pe::sections::Sections::is_sorted_by_rva).
+    SectionsNotSortedByRva,
+    /// The entry point is not inside a [`SectionFlags::MEM_EXECUTE`] section.
+    /// Common in packed or tampered images.
+    EntryPointNotExecutable { address_of_entry_point: u32 },
+    /// A data directory's `virtual_address` is not inside any section or the headers.
+    DataDirectoryOutsideSections {
+        name: SectionName,
+        virtual_address: u32,
+    },
+}
+
+impl<'a> PEFile<'a> {
+    /// Checks the parsed headers for values that break the spec but can still be parsed.
+    pub fn validate(&self) -> Vec<ValidationWarning> {
+        let mut warnings = Vec::new();
+        if let Some(optional_header) = &self.optional_header {
+            if let OptionalHeaderMagic::Other(magic) = optional_header.standard_fields.magic {
+                warnings.push(ValidationWarning::UnknownOptionalHeaderMagic { magic });
+            }
+
+            let global_ptr = optional_header.data_directories.global_ptr;
+            if global_ptr.size != 0 {
+                warnings.push(ValidationWarning::NonZeroGlobalPtrSize {
+                    size: global_ptr.size,
+                });
+            }
+
+            if !optional_header.data_directories.architecture.is_null() {
+                warnings.push(ValidationWarning::NonZeroArchitecture);
+            }
+
+            // DLLs may have no entry point.
+            let address_of_entry_point = optional_header.standard_fields.address_of_entry_point;
+            let entry_point_is_executable = self
+                .sections
+                .find_rva(address_of_entry_point)
+                .is_some_and(|section| {
+                    section
+                        .row
+                        .characteristics
+                        .contains(SectionFlags::MEM_EXECUTE)
+                });
+            if address_of_entry_point != 0 && !entry_point_is_executable {
+                warnings.push(ValidationWarning::EntryPointNotExecutable {
+                    address_of_entry_point,
+                });
+            }
+
+            // The bound import table is usually stored in the headers, which are mapped too.
+            let size_of_headers = optional_header.windows_specific_fields.size_of_headers();
+            for (name, virtual_address, _) in self.directory_regions() {
+                if name != SectionName::CertificateTable
+                    && virtual_address >= size_of_headers
+                    && self.sections.find_rva(virtual_address).is_none()
+                {
+                    warnings.push(ValidationWarning::DataDirectoryOutsideSections {
+                        name,
+                        virtual_address,
+                    });
+                }
+            }
+        }
+
+        if !self.sections.is_sorted_by_rva() {
+            warnings.push(ValidationWarning::SectionsNotSortedByRva);
+        }
+        warnings
+    }
+
+    /// The RVA of the value stored in the global pointer register, if the image uses one.
+    pub fn global_pointer_rva(&self) -> Option<u32> {
+        self.optional_header
+            .as_ref()
+            .map(|optional_header| optional_header.data_directories.global_ptr.virtual_address)
+            .filter(|virtual_address| *virtual_address != 0)
+    }
+}
+
+impl PEImageDef {
+    /// Checks the definition for mistakes that would produce an image loaders reject.
+    pub fn validate(&self) -> Vec<ValidationWarning> {
+        let mut warnings = Vec::new();
+        let is_executable = self
+            .file_characteristics
+            .contains(ImageFileCharacteristics::EXECUTABLE_IMAGE)
+            && !self
+                .file_characteristics
+                .contains(ImageFileCharacteristics::FILE_DLL);
+        let has_code = self.sections.iter_sections().any(|section| {
+            section
+                .characteristics
+                .contains(SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+        });
+        if is_executable && !has_code {
+            warnings.push(ValidationWarning::NoExecutableSection);
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::{
+        coff::ImageFileMachine,
+        dos::ImageDosHeader,
+        file::definition::SectionDefinitions,
+        optional_header::{
+            data_directories::ImageDataDirectory, OptionalHeader, OptionalHeaderStandardFields,
+            OptionalHeaderWindowsSpecific, OptionalHeaderWindowsSpecificFields,
+        },
+    };
+
+    /// An empty PE32 executable to add sections to.
+    fn image_def() -> PEImageDef {
+        PEImageDef {
+            dos_header: ImageDosHeader {
+                e_magic: ImageDosHeader::MAGIC_CONSTANT,
+                e_lfanew: 0x80,
+                ..Default::default()
+            },
+            machine: ImageFileMachine::I386,
+            file_characteristics: ImageFileCharacteristics::EXECUTABLE_IMAGE,
+            optional_header: OptionalHeader {
+                standard_fields: OptionalHeaderStandardFields {
+                    magic: OptionalHeaderMagic::PE32,
+                    ..Default::default()
+                },
+                windows_specific_fields: OptionalHeaderWindowsSpecific::PE32(
+                    OptionalHeaderWindowsSpecificFields {
+                        image_base: 0x400000,
+                        section_alignment: 0x1000,
+                        file_alignment: 0x200,
+                        ..Default::default()
+                    },
+                ),
+                ..Default::default()
+            },
+            sections: SectionDefinitions::default(),
+            imports: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn non_zero_global_ptr_size_is_reported() {
+        let mut def = image_def();
+        def.new_section(".sdata", SectionFlags::CNT_INITIALIZED_DATA)
+            .add_data(&[0; 0x10]);
+        let data = def.write_file().unwrap();
+        let pe = PEFile::parse(&data).unwrap();
+        assert!(pe.validate().is_empty());
+        assert_eq!(pe.global_pointer_rva(), None);
+
+        def.optional_header.data_directories.global_ptr = ImageDataDirectory {
+            virtual_address: 0x1008,
+            size: 4,
+        };
+        let data = def.write_file().unwrap();
+        let pe = PEFile::parse(&data).unwrap();
+        assert_eq!(
+            pe.validate(),
+            [ValidationWarning::NonZeroGlobalPtrSize { size: 4 }]
+        );
+        assert_eq!(pe.global_pointer_rva(), Some(0x1008));
+    }
+
+    #[test]
+    fn non_null_architecture_is_reported() {
+        let mut def = image_def();
+        def.new_section(".rdata", SectionFlags::CNT_INITIALIZED_DATA)
+            .add_data(&[1, 2, 3, 4]);
+        def.optional_header.data_directories.architecture = ImageDataDirectory {
+            virtual_address: 0x1001,
+            size: 2,
+        };
+        let data = def.write_file().unwrap();
+        let pe = PEFile::parse(&data).unwrap();
+        assert_eq!(pe.validate(), [ValidationWarning::NonZeroArchitecture]);
+        assert_eq!(pe.architecture_bytes(&data), Some([2, 3].as_slice()));
+    }
+
+    #[test]
+    fn unknown_magic_is_reported() {
+        let mut def = image_def();
+        def.optional_header.standard_fields.magic = OptionalHeaderMagic::Other(0x107);
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3]);
+        let data = def.write_file().unwrap();
+        let pe = PEFile::parse(&data).unwrap();
+        assert_eq!(
+            pe.validate(),
+            [ValidationWarning::UnknownOptionalHeaderMagic { magic: 0x107 }]
+        );
+    }
+
+    #[test]
+    fn out_of_order_sections_are_reported() {
+        let mut def = image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3]);
+        def.new_section(".rdata", SectionFlags::CNT_INITIALIZED_DATA)
+            .add_data(&[0; 4]);
+        let data = def.write_file().unwrap();
+        assert!(PEFile::parse(&data).unwrap().validate().is_empty());
+
+        def.sections.other.swap(0, 1);
+        let data = def.write_file().unwrap();
+        assert_eq!(
+            PEFile::parse(&data).unwrap().validate(),
+            [ValidationWarning::SectionsNotSortedByRva]
+        );
+    }
+
+    #[test]
+    fn entry_point_in_data_is_reported() {
+        let mut def = image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3]);
+        def.new_section(
+            ".data",
+            SectionFlags::CNT_INITIALIZED_DATA | SectionFlags::MEM_WRITE,
+        )
+        .add_data(&[0; 4]);
+        def.optional_header.standard_fields.address_of_entry_point = 0x1000;
+        let data = def.write_file().unwrap();
+        assert!(PEFile::parse(&data).unwrap().validate().is_empty());
+
+        def.optional_header.standard_fields.address_of_entry_point = 0x2000;
+        let data = def.write_file().unwrap();
+        assert_eq!(
+            PEFile::parse(&data).unwrap().validate(),
+            [ValidationWarning::EntryPointNotExecutable {
+                address_of_entry_point: 0x2000
+            }]
+        );
+    }
+
+    #[test]
+    fn data_directory_in_gap_is_reported() {
+        let mut def = image_def();
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3]);
+        // Stored in the headers, which is allowed.
+        def.optional_header.data_directories.bound_import = ImageDataDirectory {
+            virtual_address: 0x180,
+            size: 0x10,
+        };
+        let data = def.write_file().unwrap();
+        assert!(PEFile::parse_minimal(&data).unwrap().validate().is_empty());
+
+        // Past the end of `.text`, which is the last section.
+        def.optional_header.data_directories.debug = ImageDataDirectory {
+            virtual_address: 0x3000,
+            size: 0x1C,
+        };
+        let data = def.write_file().unwrap();
+        assert_eq!(
+            PEFile::parse_minimal(&data).unwrap().validate(),
+            [ValidationWarning::DataDirectoryOutsideSections {
+                name: SectionName::Debug,
+                virtual_address: 0x3000,
+            }]
+        );
+    }
+
+    #[test]
+    fn executable_definition_without_code_is_reported() {
+        let mut def = image_def();
+        assert_eq!(def.validate(), [ValidationWarning::NoExecutableSection]);
+
+        def.new_section(".rdata", SectionFlags::CNT_INITIALIZED_DATA)
+            .add_data(&[0; 4]);
+        assert_eq!(def.validate(), [ValidationWarning::NoExecutableSection]);
+
+        def.file_characteristics |= ImageFileCharacteristics::FILE_DLL;
+        assert!(def.validate().is_empty());
+
+        def.file_characteristics -= ImageFileCharacteristics::FILE_DLL;
+        def.new_section(".text", SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE)
+            .add_data(&[0xC3]);
+        assert!(def.validate().is_empty());
+    }
+}
@@ -1,6 +1,8 @@
+pub mod address;
 pub mod coff;
 pub mod dos;
 pub mod file;
+pub mod object;
 pub mod optional_header;
 pub mod options;
 pub mod sections;
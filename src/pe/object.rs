@@ -0,0 +1,208 @@
+//! Object files (`.obj`).
+//!
+//! Object files have no DOS header, PE signature or optional header. They start with the
+//! [`CoffFileHeader`], followed by the section table. The symbol table and string table are
+//! found through [`CoffFileHeader::pointer_to_symbol_table`].
+use crate::{
+    error::{PewterError, Result},
+    io::Reader,
+    pe::{
+        coff::{CoffFileHeader, CoffSymbol},
+        sections::{SectionTable, SectionTableRow, Sections},
+    },
+    vec::Vec,
+};
+
+/// A parsed COFF object file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoffObject<'a> {
+    pub coff_header: CoffFileHeader,
+    pub sections: Sections<'a>,
+    /// The symbol table. Each symbol holds its auxiliary records, so a
+    /// [`CoffRelocation::symbol_table_index`](crate::pe::coff::CoffRelocation::symbol_table_index)
+    /// is not an index into this list, see [`CoffObject::symbol_at`].
+    pub symbols: Vec<CoffSymbol>,
+    /// The string table, starting with its 4 byte size. Empty if the file has no symbol table.
+    pub string_table: &'a [u8],
+}
+
+impl<'a> CoffObject<'a> {
+    /// Parse an object file, starting with the COFF header at the start of `data`.
+    ///
+    /// An optional header, which object files should not have, is skipped.
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let read_ptr = &mut &data[..];
+        let coff_header: CoffFileHeader = read_ptr.read()?;
+        read_ptr.read_slice(coff_header.size_of_optional_header as usize)?;
+
+        let section_table_offset = data.len() - read_ptr.len();
+        // Checked before reading so a corrupt count can't cause a huge allocation.
+        let section_table_size = coff_header.number_of_sections as usize * SectionTableRow::SIZE;
+        if section_table_size > read_ptr.len() {
+            return Err(
+                PewterError::not_enough_data(section_table_size).with_offset(section_table_offset)
+            );
+        }
+        let section_table =
+            SectionTable::new_linear(read_ptr, coff_header.number_of_sections as usize)?;
+        let sections = Sections::parse(data, section_table)?;
+
+        let (symbols, string_table) = if coff_header.pointer_to_symbol_table == 0 {
+            (Vec::new(), &data[..0])
+        } else {
+            Self::parse_symbols(data, &coff_header)?
+        };
+
+        Ok(Self {
+            coff_header,
+            sections,
+            symbols,
+            string_table,
+        })
+    }
+
+    fn parse_symbols(
+        data: &'a [u8],
+        coff_header: &CoffFileHeader,
+    ) -> Result<(Vec<CoffSymbol>, &'a [u8])> {
+        let symbol_table_offset = coff_header.pointer_to_symbol_table as usize;
+        let symbol_table_size =
+            (coff_header.number_of_symbols as usize).saturating_mul(CoffSymbol::SIZE);
+        let mut symbol_data = data
+            .get(symbol_table_offset..)
+            .and_then(|symbol_data| symbol_data.get(..symbol_table_size))
+            .ok_or_else(|| {
+                PewterError::invalid_image_format_at(
+                    "Symbol table is outside of the file",
+                    symbol_table_offset,
+                )
+            })?;
+
+        let mut symbols = Vec::new();
+        while !symbol_data.is_empty() {
+            symbols.push(symbol_data.read()?);
+        }
+
+        // The string table immediately follows the symbol table, its size includes the size field.
+        let string_table_offset = symbol_table_offset + symbol_table_size;
+        let string_table = data
+            .get(string_table_offset..)
+            .and_then(|mut string_table| {
+                let size = string_table.peek::<u32>().ok()? as usize;
+                string_table.get(..size)
+            })
+            .unwrap_or_default();
+        Ok((symbols, string_table))
+    }
+
+    /// The symbol at `index` in the symbol table, counting auxiliary records, as used by
+    /// [`CoffRelocation::symbol_table_index`](crate::pe::coff::CoffRelocation::symbol_table_index).
+    /// Returns `None` if `index` is an auxiliary record or past the end of the table.
+    pub fn symbol_at(&self, index: u32) -> Option<&CoffSymbol> {
+        let mut record_index = 0;
+        for symbol in &self.symbols {
+            if record_index == index {
+                return Some(symbol);
+            }
+            record_index += 1 + symbol.number_of_aux_symbols as u32;
+            if record_index > index {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// The name of `symbol`, read from the string table if it is longer than 8 bytes.
+    pub fn symbol_name<'s>(&'s self, symbol: &'s CoffSymbol) -> Option<&'s str> {
+        let name = match symbol.string_table_offset() {
+            Some(offset) => self.string_table.get(offset as usize..)?,
+            None => &symbol.name[..],
+        };
+        let null_terminator = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+        core::str::from_utf8(&name[..null_terminator]).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        io::Writer,
+        pe::{coff::ImageFileMachine, sections::SectionFlags},
+    };
+
+    #[test]
+    fn parse_minimal_object() {
+        const TEXT_OFFSET: u32 = (CoffFileHeader::SIZE + SectionTableRow::SIZE) as u32;
+        const SYMBOL_TABLE_OFFSET: u32 = TEXT_OFFSET + 4;
+        let long_name = "a_long_function_name";
+
+        let mut data = Vec::new();
+        data.write(&CoffFileHeader {
+            machine: ImageFileMachine::Amd64,
+            number_of_sections: 1,
+            pointer_to_symbol_table: SYMBOL_TABLE_OFFSET,
+            number_of_symbols: 3,
+            ..Default::default()
+        })
+        .unwrap();
+        data.write(&SectionTableRow {
+            name: *b".text\0\0\0",
+            size_of_raw_data: 4,
+            pointer_to_raw_data: TEXT_OFFSET,
+            characteristics: SectionFlags::CNT_CODE | SectionFlags::MEM_EXECUTE,
+            ..Default::default()
+        })
+        .unwrap();
+        data.write_slice(&[0x31, 0xC0, 0xC3, 0xCC]).unwrap();
+        // The section symbol and its auxiliary record, then a function with a long name.
+        data.write(&CoffSymbol {
+            name: *b".text\0\0\0",
+            section_number: 1,
+            storage_class: 3,
+            number_of_aux_symbols: 1,
+            aux_symbols: Vec::from([[0; CoffSymbol::SIZE]]),
+            ..Default::default()
+        })
+        .unwrap();
+        let mut name = [0; 8];
+        name[4..].copy_from_slice(&4u32.to_le_bytes());
+        data.write(&CoffSymbol {
+            name,
+            value: 1,
+            section_number: 1,
+            type_: 0x20,
+            storage_class: 2,
+            ..Default::default()
+        })
+        .unwrap();
+        data.write(4 + long_name.len() as u32 + 1).unwrap();
+        data.write_slice(long_name.as_bytes()).unwrap();
+        data.write(0u8).unwrap();
+
+        let object = CoffObject::parse(&data).unwrap();
+        assert_eq!(object.coff_header.machine, ImageFileMachine::Amd64);
+        assert_eq!(object.sections.0.len(), 1);
+        assert_eq!(object.sections.0[0].row.name_str(), ".text");
+        assert_eq!(object.sections.0[0].data, [0x31, 0xC0, 0xC3, 0xCC]);
+
+        assert_eq!(object.symbols.len(), 2);
+        assert_eq!(object.symbol_name(&object.symbols[0]), Some(".text"));
+        let function = object.symbol_at(2).unwrap();
+        assert_eq!(function.value, 1);
+        assert_eq!(object.symbol_name(function), Some(long_name));
+        assert!(object.symbol_at(1).is_none());
+        assert!(object.symbol_at(3).is_none());
+    }
+
+    #[test]
+    fn too_many_sections_is_an_error() {
+        let mut data = Vec::new();
+        data.write(&CoffFileHeader {
+            number_of_sections: u16::MAX,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(CoffObject::parse(&data).is_err());
+    }
+}
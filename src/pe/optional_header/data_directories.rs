@@ -1,5 +1,8 @@
 //! Optional Header Data Directories (Image Only)
-use crate::io::{ReadData, WriteData};
+use crate::{
+    error::Result,
+    io::{ReadData, Reader, WriteData, Writer},
+};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum SectionName {
@@ -40,6 +43,28 @@ impl SectionName {
         SectionName::ClrRuntimeHeader,
         SectionName::Reserved,
     ];
+
+    /// The name of the matching field in [`DataDirectories`].
+    pub const fn field_name(self) -> &'static str {
+        match self {
+            SectionName::ExportTable => "export_table",
+            SectionName::ImportTable => "import_table",
+            SectionName::ResourceTable => "resource_table",
+            SectionName::ExceptionTable => "exception_table",
+            SectionName::CertificateTable => "certificate_table",
+            SectionName::BaseRelocationTable => "base_relocation_table",
+            SectionName::Debug => "debug",
+            SectionName::Architecture => "architecture",
+            SectionName::GlobalPtr => "global_ptr",
+            SectionName::TlsTable => "tls_table",
+            SectionName::LoadConfigTable => "load_config_table",
+            SectionName::BoundImport => "bound_import",
+            SectionName::Ita => "ita",
+            SectionName::DelayImportDescriptor => "delay_import_descriptor",
+            SectionName::ClrRuntimeHeader => "clr_runtime_header",
+            SectionName::Reserved => "reserved",
+        }
+    }
 }
 
 /// All the possable data directories.
@@ -81,6 +106,24 @@ pub struct DataDirectories {
 }
 
 impl DataDirectories {
+    /// Reads `count` data directories in the order of [`SectionName::ALL`].
+    /// Directories past `count` are left as default. At most 16 directories are read.
+    pub fn read_n(reader: &mut impl Reader, count: usize) -> Result<Self> {
+        let mut data_directories = Self::default();
+        for data_dir_name in SectionName::ALL.into_iter().take(count) {
+            data_directories.set_directory(data_dir_name, reader.read()?);
+        }
+        Ok(data_directories)
+    }
+
+    /// Writes the first `count` data directories in the order of [`SectionName::ALL`].
+    pub fn write_n(&self, writer: &mut impl Writer, count: usize) -> Result<()> {
+        for data_dir_name in SectionName::ALL.into_iter().take(count) {
+            writer.write(self.get_directory(data_dir_name))?;
+        }
+        Ok(())
+    }
+
     pub fn get_directory(&self, name: SectionName) -> ImageDataDirectory {
         match name {
             SectionName::ExportTable => self.export_table,
@@ -144,6 +187,16 @@ impl ImageDataDirectory {
         self.virtual_address == 0 &&
             self.size == 0
     }
+
+    /// The first RVA after the table, saturating at `u32::MAX`.
+    pub fn end(&self) -> u32 {
+        self.virtual_address.saturating_add(self.size)
+    }
+
+    /// Returns true if `rva` is inside `virtual_address..virtual_address + size`.
+    pub fn contains_rva(&self, rva: u32) -> bool {
+        rva >= self.virtual_address && rva - self.virtual_address < self.size
+    }
 }
 
 impl ReadData for ImageDataDirectory {
@@ -162,3 +215,56 @@ impl WriteData for ImageDataDirectory {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec::Vec;
+
+    #[test]
+    fn read_n_leaves_remaining_directories_default() {
+        let mut buffer = Vec::new();
+        for i in 1..=3u32 {
+            buffer
+                .write(ImageDataDirectory {
+                    virtual_address: i * 0x1000,
+                    size: i,
+                })
+                .unwrap();
+        }
+
+        let mut reader = buffer.as_slice();
+        let data_directories = DataDirectories::read_n(&mut reader, 3).unwrap();
+        assert!(reader.is_empty());
+        assert_eq!(data_directories.export_table.virtual_address, 0x1000);
+        assert_eq!(data_directories.import_table.virtual_address, 0x2000);
+        assert_eq!(data_directories.resource_table.size, 3);
+        for name in &SectionName::ALL[3..] {
+            assert!(data_directories.get_directory(*name).is_null());
+        }
+
+        let mut written = Vec::new();
+        data_directories.write_n(&mut written, 3).unwrap();
+        assert_eq!(written, buffer);
+    }
+
+    #[test]
+    fn contains_rva_at_boundaries() {
+        let directory = ImageDataDirectory {
+            virtual_address: 0x2000,
+            size: 0x100,
+        };
+        assert_eq!(directory.end(), 0x2100);
+        assert!(!directory.contains_rva(0x1FFF));
+        assert!(directory.contains_rva(0x2000));
+        assert!(directory.contains_rva(0x20FF));
+        assert!(!directory.contains_rva(0x2100));
+
+        let at_end_of_address_space = ImageDataDirectory {
+            virtual_address: u32::MAX - 1,
+            size: 0x10,
+        };
+        assert_eq!(at_end_of_address_space.end(), u32::MAX);
+        assert!(at_end_of_address_space.contains_rva(u32::MAX));
+    }
+}
@@ -22,7 +22,9 @@ pub enum OptionalHeaderMagic {
     #[default]
     PE32 = 0x10B,
     PE32Plus = 0x20B,
-    // Other(u16),
+    /// An unknown magic, such as 0x107 for ROM images.
+    /// The rest of the optional header is read and written with the PE32 layout.
+    Other(u16),
 }
 
 impl OptionalHeaderMagic {
@@ -32,10 +34,7 @@ impl OptionalHeaderMagic {
         match sig {
             0x10B => Ok(Self::PE32),
             0x20B => Ok(Self::PE32Plus),
-            //n => Self::Other(n),
-            _ => Err(PewterError::invalid_image_format(
-                "Bad optional header magic number",
-            )),
+            n => Ok(Self::Other(n)),
         }
     }
 
@@ -43,7 +42,16 @@ impl OptionalHeaderMagic {
         match self {
             Self::PE32 => 0x10b,
             Self::PE32Plus => 0x20b,
-            // Self::Other(n) => *n,
+            Self::Other(n) => *n,
+        }
+    }
+
+    /// The size in bytes of addresses and pointer-sized table entries, such as import lookup
+    /// table entries: 8 for PE32+, 4 otherwise.
+    pub fn pointer_size(&self) -> usize {
+        match self {
+            Self::PE32Plus => 8,
+            Self::PE32 | Self::Other(_) => 4,
         }
     }
 }
@@ -96,6 +104,19 @@ pub struct OptionalHeaderStandardFields {
 impl OptionalHeaderStandardFields {
     pub const SIZE_PE: usize = 28;
     pub const SIZE_PE_PLUS: usize = 24;
+
+    /// Like the [`WriteData`] impl, but fails if a PE32 header has no `base_of_data`
+    /// instead of writing it as zero.
+    ///
+    /// A missing `base_of_data` on PE32 means the struct was built inconsistently.
+    pub fn write_strict(&self, writer: &mut impl crate::io::Writer) -> Result<()> {
+        if self.magic != OptionalHeaderMagic::PE32Plus && self.base_of_data.is_none() {
+            return Err(PewterError::invalid_image_format(
+                "PE32 optional header is missing base_of_data.",
+            ));
+        }
+        writer.write(self)
+    }
 }
 
 impl ReadData for OptionalHeaderStandardFields {
@@ -110,7 +131,7 @@ impl ReadData for OptionalHeaderStandardFields {
             size_of_unitilized_data: reader.read()?,
             address_of_entry_point: reader.read()?,
             base_of_code: reader.read()?,
-            base_of_data: (magic == OptionalHeaderMagic::PE32)
+            base_of_data: (magic != OptionalHeaderMagic::PE32Plus)
                 .then(|| reader.read())
                 .transpose()?,
         })
@@ -127,7 +148,7 @@ impl WriteData for &OptionalHeaderStandardFields {
         writer.write(self.size_of_unitilized_data)?;
         writer.write(self.address_of_entry_point)?;
         writer.write(self.base_of_code)?;
-        if self.magic == OptionalHeaderMagic::PE32 {
+        if self.magic != OptionalHeaderMagic::PE32Plus {
             writer.write(self.base_of_data.unwrap_or(0))?;
         }
         Ok(())
@@ -212,6 +233,28 @@ impl ImageSubsystem {
             n => Self::Other(n),
         }
     }
+
+    /// A human readable name for the subsystem, for display.
+    ///
+    /// [`ImageSubsystem::Unknown`] and [`ImageSubsystem::Other`] are both `"Unknown"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Unknown | Self::Other(_) => "Unknown",
+            Self::Native => "Native",
+            Self::WindowsGui => "Windows GUI",
+            Self::WindowsCui => "Windows CUI",
+            Self::Os2Cui => "OS/2 CUI",
+            Self::PosixCui => "POSIX CUI",
+            Self::NativeWindows => "Native Win9x driver",
+            Self::WindowsCeGui => "Windows CE GUI",
+            Self::EfiApplication => "EFI Application",
+            Self::EbiBootServiceDriver => "EFI Boot Service Driver",
+            Self::EfiRuntimeDriver => "EFI Runtime Driver",
+            Self::EfiRom => "EFI ROM",
+            Self::Xbox => "Xbox",
+            Self::WindowsBootApplication => "Windows Boot Application",
+        }
+    }
 }
 
 bitflags! {
@@ -346,7 +389,7 @@ impl CalulateOptVariantSize<Pe32> for OptionalHeaderWindowsSpecificFields<Pe32>
     }
 }
 
-impl CalulateOptVariantSize<Pe32Plus> for OptionalHeaderWindowsSpecificFields<Pe32> {
+impl CalulateOptVariantSize<Pe32Plus> for OptionalHeaderWindowsSpecificFields<Pe32Plus> {
     fn calculate_size() -> usize {
         Self::SIZE
     }
@@ -835,10 +878,99 @@ impl OptionalHeaderWindowsSpecific {
     }
 }
 
+/// Setters for the fields that are 32 bits in PE32 and 64 bits in PE32+.
+impl OptionalHeaderWindowsSpecific {
+    fn check_fits(&self, value: u64, message: &'static str) -> Result<()> {
+        if self.is_pe32() && value > u32::MAX as u64 {
+            return Err(PewterError::invalid_image_format(message));
+        }
+        Ok(())
+    }
+
+    /// Like [`OptionalHeaderWindowsSpecific::set_image_base`], but fails instead of truncating
+    /// a value that doesn't fit in a PE32 header.
+    pub fn try_set_image_base(&mut self, value: u64) -> Result<()> {
+        self.check_fits(value, "Image base does not fit in 32 bits.")?;
+        self.set_image_base(value);
+        Ok(())
+    }
+
+    /// Like [`OptionalHeaderWindowsSpecific::set_size_of_stack_reserve`], but fails instead of truncating
+    /// a value that doesn't fit in a PE32 header.
+    pub fn try_set_size_of_stack_reserve(&mut self, value: u64) -> Result<()> {
+        self.check_fits(value, "Stack reserve size does not fit in 32 bits.")?;
+        self.set_size_of_stack_reserve(value);
+        Ok(())
+    }
+
+    /// Like [`OptionalHeaderWindowsSpecific::set_size_of_stack_commit`], but fails instead of truncating
+    /// a value that doesn't fit in a PE32 header.
+    pub fn try_set_size_of_stack_commit(&mut self, value: u64) -> Result<()> {
+        self.check_fits(value, "Stack commit size does not fit in 32 bits.")?;
+        self.set_size_of_stack_commit(value);
+        Ok(())
+    }
+
+    /// Like [`OptionalHeaderWindowsSpecific::set_size_of_heap_reserve`], but fails instead of truncating
+    /// a value that doesn't fit in a PE32 header.
+    pub fn try_set_size_of_heap_reserve(&mut self, value: u64) -> Result<()> {
+        self.check_fits(value, "Heap reserve size does not fit in 32 bits.")?;
+        self.set_size_of_heap_reserve(value);
+        Ok(())
+    }
+
+    /// Like [`OptionalHeaderWindowsSpecific::set_size_of_heap_commit`], but fails instead of truncating
+    /// a value that doesn't fit in a PE32 header.
+    pub fn try_set_size_of_heap_commit(&mut self, value: u64) -> Result<()> {
+        self.check_fits(value, "Heap commit size does not fit in 32 bits.")?;
+        self.set_size_of_heap_commit(value);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn try_set_image_base_over_4gb_on_pe32_is_an_error() {
+        let mut pe32 = OptionalHeaderWindowsSpecific::PE32(Default::default());
+        pe32.set_image_base(0x400000);
+        assert!(pe32.try_set_image_base(0x1_4000_0000).is_err());
+        assert_eq!(pe32.image_base(), 0x400000);
+        assert!(pe32.try_set_size_of_stack_reserve(0x1_0000_0000).is_err());
+        pe32.try_set_size_of_heap_commit(0x1000).unwrap();
+        assert_eq!(pe32.size_of_heap_commit(), 0x1000);
+
+        let mut pe32_plus = OptionalHeaderWindowsSpecific::PE32Plus(Default::default());
+        pe32_plus.try_set_image_base(0x1_4000_0000).unwrap();
+        assert_eq!(pe32_plus.image_base(), 0x1_4000_0000);
+    }
+
+    #[test]
+    fn strict_write_rejects_pe32_without_base_of_data() {
+        let mut standard_fields = OptionalHeaderStandardFields {
+            magic: OptionalHeaderMagic::PE32,
+            base_of_data: None,
+            ..Default::default()
+        };
+        let mut written = crate::vec::Vec::new();
+        assert!(standard_fields.write_strict(&mut written).is_err());
+        crate::io::Writer::write(&mut written, &standard_fields).unwrap();
+        assert_eq!(written.len(), OptionalHeaderStandardFields::SIZE_PE);
+
+        standard_fields.base_of_data = Some(0x2000);
+        written.clear();
+        standard_fields.write_strict(&mut written).unwrap();
+        assert_eq!(written.len(), OptionalHeaderStandardFields::SIZE_PE);
+
+        standard_fields.magic = OptionalHeaderMagic::PE32Plus;
+        standard_fields.base_of_data = None;
+        written.clear();
+        standard_fields.write_strict(&mut written).unwrap();
+        assert_eq!(written.len(), OptionalHeaderStandardFields::SIZE_PE_PLUS);
+    }
+
     #[test]
     fn opttional_header_magic_is_2_bytes() {
         let buffer: [u8; OptionalHeaderMagic::SIZE] = 0x10B_u16.to_le_bytes();
@@ -859,6 +991,13 @@ mod tests {
         );
     }
 
+    #[test]
+    fn pointer_size_of_magic() {
+        assert_eq!(OptionalHeaderMagic::PE32.pointer_size(), 4);
+        assert_eq!(OptionalHeaderMagic::PE32Plus.pointer_size(), 8);
+        assert_eq!(OptionalHeaderMagic::Other(0x107).pointer_size(), 4);
+    }
+
     #[test]
     fn optional_header_stand_fields_pe_is_24() {
         let mut buffer = [0u8; OptionalHeaderStandardFields::SIZE_PE];
@@ -877,6 +1016,14 @@ mod tests {
         assert_eq!(read_ptr.len(), 0);
     }
 
+    #[test]
+    fn subsystem_names() {
+        assert_eq!(ImageSubsystem::WindowsGui.name(), "Windows GUI");
+        assert_eq!(ImageSubsystem::WindowsCui.name(), "Windows CUI");
+        assert_eq!(ImageSubsystem::from_u16(10).name(), "EFI Application");
+        assert_eq!(ImageSubsystem::from_u16(0x1234).name(), "Unknown");
+    }
+
     #[test]
     fn optional_header_win_specific_pe32_is_68() {
         let buffer = [0u8; OptionalHeaderWindowsSpecificFields::<Pe32>::SIZE];
@@ -71,29 +71,41 @@ impl OptionalHeader {
 
     /// Get the dada directory if index is less than
     /// [OptionalHeaderWindowsSpecific::number_of_rva_and_sizes](OptionalHeaderWindowsSpecificFields::number_of_rva_and_sizes).
+    ///
+    /// Directories past `number_of_rva_and_sizes` are not in the file, so they are `None`.
     pub fn try_get_data_directory(&self, name: SectionName) -> Option<ImageDataDirectory> {
-        (self.windows_specific_fields.number_of_rva_and_sizes() < name as u32)
+        ((name as u32) < self.windows_specific_fields.number_of_rva_and_sizes())
             .then(|| self.data_directories.get_directory(name))
     }
+
+    /// Raise [number_of_rva_and_sizes](OptionalHeaderWindowsSpecificFields::number_of_rva_and_sizes)
+    /// to at least `at_least`, so directories set past the current count are written.
+    ///
+    /// The directories in between are written as zeros unless they are set. The count is never lowered.
+    pub fn ensure_directory_count(&mut self, at_least: u32) {
+        if self.windows_specific_fields.number_of_rva_and_sizes() < at_least {
+            self.windows_specific_fields
+                .set_number_of_rva_and_sizes(at_least);
+        }
+    }
 }
 
 impl ReadData for OptionalHeader {
     fn read(reader: &mut impl crate::io::Reader) -> crate::error::Result<Self> {
         let standard_fields: OptionalHeaderStandardFields = reader.read()?;
         let windows_specific_fields = match &standard_fields.magic {
-            OptionalHeaderMagic::PE32 => OptionalHeaderWindowsSpecific::PE32(reader.read()?),
+            OptionalHeaderMagic::PE32 | OptionalHeaderMagic::Other(_) => {
+                OptionalHeaderWindowsSpecific::PE32(reader.read()?)
+            }
             OptionalHeaderMagic::PE32Plus => {
                 OptionalHeaderWindowsSpecific::PE32Plus(reader.read()?)
             }
         };
 
-        let mut data_directories = DataDirectories::default();
-        for data_dir_name in SectionName::ALL
-            .into_iter()
-            .take(windows_specific_fields.number_of_rva_and_sizes() as usize)
-        {
-            data_directories.set_directory(data_dir_name, reader.read()?);
-        }
+        let data_directories = DataDirectories::read_n(
+            reader,
+            windows_specific_fields.number_of_rva_and_sizes() as usize,
+        )?;
 
         Ok(Self {
             standard_fields,
@@ -107,18 +119,18 @@ impl WriteData for &OptionalHeader {
     fn write_to(self, writer: &mut impl crate::io::Writer) -> crate::error::Result<()> {
         writer.write(&self.standard_fields)?;
         match (self.standard_fields.magic, &self.windows_specific_fields) {
-            (OptionalHeaderMagic::PE32, OptionalHeaderWindowsSpecific::PE32(pe)) => writer.write(pe)?,
+            (
+                OptionalHeaderMagic::PE32 | OptionalHeaderMagic::Other(_),
+                OptionalHeaderWindowsSpecific::PE32(pe),
+            ) => writer.write(pe)?,
             (OptionalHeaderMagic::PE32Plus, OptionalHeaderWindowsSpecific::PE32Plus(pe)) => writer.write(pe)?,
             _ => return Err(PewterError::invalid_image_format("Mismatching Optiional Header standard_fields.magic value and windows_specific_fields variant."))
         }
 
-        for data_dir_name in SectionName::ALL
-            .into_iter()
-            .take(self.windows_specific_fields.number_of_rva_and_sizes() as usize)
-        {
-            writer.write(self.data_directories.get_directory(data_dir_name))?;
-        }
-        Ok(())
+        self.data_directories.write_n(
+            writer,
+            self.windows_specific_fields.number_of_rva_and_sizes() as usize,
+        )
     }
 }
 
@@ -134,6 +146,24 @@ mod tests {
         assert_eq!(read_ptr.len(), 0);
     }
 
+    #[test]
+    fn other_magic_uses_pe32_layout_and_is_preserved() {
+        let mut buffer = [0u8; OptionalHeader::size_pe32()];
+        buffer[..2].copy_from_slice(&0x010Cu16.to_le_bytes());
+        let read_ptr = &mut buffer.as_slice();
+        let optional_header = OptionalHeader::read(read_ptr).unwrap();
+        assert_eq!(read_ptr.len(), 0);
+        assert_eq!(
+            optional_header.standard_fields.magic,
+            OptionalHeaderMagic::Other(0x010C)
+        );
+        assert!(optional_header.windows_specific_fields.is_pe32());
+
+        let mut written = crate::vec::Vec::new();
+        crate::io::Writer::write(&mut written, &optional_header).unwrap();
+        assert_eq!(written, buffer);
+    }
+
     #[test]
     fn optional_header_pe32_plus_is_112() {
         let mut buffer = [0u8; OptionalHeader::size_pe32_plus()];
@@ -142,4 +172,98 @@ mod tests {
         OptionalHeader::read(read_ptr).unwrap();
         assert_eq!(read_ptr.len(), 0);
     }
+
+    #[test]
+    fn pe32_plus_round_trip_uses_64_bit_fields() {
+        let optional_header = OptionalHeader {
+            standard_fields: OptionalHeaderStandardFields {
+                magic: OptionalHeaderMagic::PE32Plus,
+                address_of_entry_point: 0x1000,
+                ..Default::default()
+            },
+            windows_specific_fields: OptionalHeaderWindowsSpecific::PE32Plus(
+                OptionalHeaderWindowsSpecificFields {
+                    image_base: 0x1_4000_0000,
+                    section_alignment: 0x1000,
+                    file_alignment: 0x200,
+                    size_of_stack_reserve: 0x10_0000_0000,
+                    size_of_heap_commit: 0x1_0000_1000,
+                    number_of_rva_and_sizes: 16,
+                    ..Default::default()
+                },
+            ),
+            data_directories: DataDirectories {
+                import_table: ImageDataDirectory {
+                    virtual_address: 0x2000,
+                    size: 0x28,
+                },
+                ..Default::default()
+            },
+        };
+
+        let mut written = crate::vec::Vec::new();
+        crate::io::Writer::write(&mut written, &optional_header).unwrap();
+        assert_eq!(written.len(), optional_header.size());
+        assert_eq!(
+            written.len(),
+            OptionalHeader::size_pe32_plus() + 16 * ImageDataDirectory::SIZE
+        );
+        assert_eq!(written[24..32], 0x1_4000_0000u64.to_le_bytes());
+
+        let read_back = OptionalHeader::read(&mut written.as_slice()).unwrap();
+        assert_eq!(read_back, optional_header);
+    }
+
+    #[test]
+    fn ensure_directory_count_includes_clr_header() {
+        let mut optional_header = OptionalHeader {
+            standard_fields: OptionalHeaderStandardFields {
+                magic: OptionalHeaderMagic::PE32,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        optional_header
+            .windows_specific_fields
+            .set_number_of_rva_and_sizes(10);
+        let clr_runtime_header = ImageDataDirectory {
+            virtual_address: 0x2000,
+            size: 0x48,
+        };
+        optional_header.data_directories.clr_runtime_header = clr_runtime_header;
+        assert!(optional_header
+            .try_get_data_directory(SectionName::ClrRuntimeHeader)
+            .is_none());
+
+        optional_header.ensure_directory_count(SectionName::ClrRuntimeHeader as u32 + 1);
+        assert_eq!(
+            optional_header
+                .windows_specific_fields
+                .number_of_rva_and_sizes(),
+            15
+        );
+        optional_header.ensure_directory_count(1);
+        assert_eq!(
+            optional_header
+                .windows_specific_fields
+                .number_of_rva_and_sizes(),
+            15
+        );
+
+        let mut written = crate::vec::Vec::new();
+        crate::io::Writer::write(&mut written, &optional_header).unwrap();
+        assert_eq!(
+            written.len(),
+            OptionalHeader::size_pe32() + 15 * ImageDataDirectory::SIZE
+        );
+        let read_back = OptionalHeader::read(&mut written.as_slice()).unwrap();
+        assert_eq!(
+            read_back.try_get_data_directory(SectionName::ClrRuntimeHeader),
+            Some(clr_runtime_header)
+        );
+        assert_eq!(
+            read_back.try_get_data_directory(SectionName::TlsTable),
+            Some(ImageDataDirectory::default())
+        );
+    }
 }
@@ -1,6 +1,8 @@
 use bitflags::bitflags;
 
 bitflags! {
+    /// Each flag is `1 << n` where `n` is the index of the data directory
+    /// (see [`SectionName`](super::optional_header::data_directories::SectionName)).
     #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
     pub struct ParseSectionFlags : u16 {
         const NONE = 0;
@@ -13,13 +15,13 @@ bitflags! {
         const DEBUG = 1<<6;
         const ARCHITECTURE = 1<<7;
         const GLOBAL_PTR = 1<<8;
-        const TLS_TABLE = 1<<8;
-        const LOAD_CONFIG_TABLE = 1<<9;
-        const BOUND_TABLE = 1<<10;
-        const ITA = 1<<11;
-        const DELAY_IMPORT_DESCRIPTOR = 1<<12;
-        const CLR_RUNTIME_HEADER = 1<<13;
-        const RESERVED = 1<<14;
+        const TLS_TABLE = 1<<9;
+        const LOAD_CONFIG_TABLE = 1<<10;
+        const BOUND_TABLE = 1<<11;
+        const ITA = 1<<12;
+        const DELAY_IMPORT_DESCRIPTOR = 1<<13;
+        const CLR_RUNTIME_HEADER = 1<<14;
+        const RESERVED = 1<<15;
 
         const ALL = Self::EXPORT_TABLE.bits() |
             Self::IMPORT_TABLE.bits() |
@@ -46,13 +48,32 @@ pub struct Options {
     /// Specifies what special sections to parse.
     /// default: [`ParseSectionFlags::ALL`]
     pub parse_special_sections: ParseSectionFlags,
+    /// The most entries read from the import lookup table of a single DLL.
+    /// default: [`Options::DEFAULT_MAX_IMPORTS_PER_DLL`]
+    pub max_imports_per_dll: usize,
+    /// The most entries read from the export address and name pointer tables.
+    /// default: [`Options::DEFAULT_MAX_EXPORTS`]
+    pub max_exports: usize,
+    /// The most rows read from the section table.
+    /// default: [`Options::DEFAULT_MAX_SECTIONS`]
+    pub max_sections: usize,
+    /// Read an optional header even if the COFF header's `size_of_optional_header` is zero.
+    /// Useful for recovering images with a corrupt COFF header.
+    /// default: `false`
+    pub force_optional_header: bool,
 }
 
 impl Options {
+    pub const DEFAULT_MAX_IMPORTS_PER_DLL: usize = 65536;
+    pub const DEFAULT_MAX_EXPORTS: usize = 65536;
+    /// The Windows loader refuses images with more than 96 sections.
+    pub const DEFAULT_MAX_SECTIONS: usize = 96;
+
     /// Does the least ammount of parsing.
     pub fn minimal() -> Self {
         Self {
             parse_special_sections: ParseSectionFlags::NONE,
+            ..Default::default()
         }
     }
 }
@@ -61,6 +82,62 @@ impl Default for Options {
     fn default() -> Self {
         Self {
             parse_special_sections: ParseSectionFlags::ALL,
+            max_imports_per_dll: Self::DEFAULT_MAX_IMPORTS_PER_DLL,
+            max_exports: Self::DEFAULT_MAX_EXPORTS,
+            max_sections: Self::DEFAULT_MAX_SECTIONS,
+            force_optional_header: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::optional_header::data_directories::SectionName;
+
+    #[test]
+    fn parse_section_flags_are_disjoint() {
+        let flags = [
+            ParseSectionFlags::EXPORT_TABLE,
+            ParseSectionFlags::IMPORT_TABLE,
+            ParseSectionFlags::RESOURCE_TABLE,
+            ParseSectionFlags::EXCEPTION_TABLE,
+            ParseSectionFlags::CERTIFICATE_TABLE,
+            ParseSectionFlags::BASE_RELOCATION_TABLE,
+            ParseSectionFlags::DEBUG,
+            ParseSectionFlags::ARCHITECTURE,
+            ParseSectionFlags::GLOBAL_PTR,
+            ParseSectionFlags::TLS_TABLE,
+            ParseSectionFlags::LOAD_CONFIG_TABLE,
+            ParseSectionFlags::BOUND_TABLE,
+            ParseSectionFlags::ITA,
+            ParseSectionFlags::DELAY_IMPORT_DESCRIPTOR,
+            ParseSectionFlags::CLR_RUNTIME_HEADER,
+            ParseSectionFlags::RESERVED,
+        ];
+        assert_eq!(flags.len(), SectionName::ALL.len());
+        assert_eq!(
+            flags
+                .iter()
+                .fold(0, |acc, flag| acc + flag.bits().count_ones()),
+            ParseSectionFlags::ALL.bits().count_ones()
+        );
+        for (i, a) in flags.iter().enumerate() {
+            for b in &flags[i + 1..] {
+                assert!(!a.intersects(*b), "{:?} overlaps {:?}", a, b);
+            }
         }
     }
+
+    #[test]
+    fn parse_section_flags_match_data_directory_index() {
+        assert_eq!(
+            ParseSectionFlags::TLS_TABLE.bits(),
+            1 << SectionName::TlsTable as u16
+        );
+        assert_eq!(
+            ParseSectionFlags::RESERVED.bits(),
+            1 << SectionName::Reserved as u16
+        );
+    }
 }
@@ -8,7 +8,8 @@
 use crate::{
     containers::Table,
     error::{PewterError, Result},
-    io::{ReadData, WriteData},
+    io::{ReadData, Reader, WriteData},
+    vec::Vec,
 };
 
 use super::ParseSectionData;
@@ -34,6 +35,129 @@ impl ParseSectionData for BaseRelocationDataDitectory {
     }
 }
 
+impl BaseRelocationDataDitectory {
+    /// Iterates every relocation as `(rva, type)`, where `rva` is the block's `base_rva` plus the entry offset.
+    ///
+    /// [`BaseRelocationType::Absolute`] padding entries are skipped.
+    pub fn iter_rvas(&self) -> impl Iterator<Item = (u32, BaseRelocationType)> + '_ {
+        self.relocations.iter().flat_map(|block| block.iter_rvas())
+    }
+
+    /// Calls `f` with every relocation in `section_data` as `(rva, type)`, without building the
+    /// tables that [`ParseSectionData::parse`] does.
+    ///
+    /// Yields the same relocations as [`BaseRelocationDataDitectory::iter_rvas`], so
+    /// [`BaseRelocationType::Absolute`] padding entries and the slot following a
+    /// [`BaseRelocationType::HighAdj`] entry are skipped. Unlike `iter_rvas`, an RVA that
+    /// overflows a `u32` is an error rather than skipped.
+    pub fn for_each_relocation(
+        section_data: &[u8],
+        mut f: impl FnMut(u32, BaseRelocationType),
+    ) -> Result<()> {
+        let mut data_ptr = section_data;
+        while !data_ptr.is_empty() {
+            let base_rva: u32 = data_ptr.read()?;
+            let block_size: u32 = data_ptr.read()?;
+            let entries_size = (block_size as usize)
+                .checked_sub(BaseRelocationBlockHeader::SIZE)
+                .ok_or_else(|| {
+                    PewterError::invalid_image_format("Base relocation block is too small.")
+                })?;
+
+            let mut entries = data_ptr.read_slice(entries_size)?;
+            while !entries.is_empty() {
+                let entry = BaseRelocationBlockOffsets::read(&mut entries)?;
+                if entry.relocation_type == BaseRelocationType::Absolute {
+                    continue;
+                }
+                if entry.relocation_type == BaseRelocationType::HighAdj && !entries.is_empty() {
+                    entries.read::<u16>()?;
+                }
+                let rva = base_rva.checked_add(entry.offset as u32).ok_or_else(|| {
+                    PewterError::invalid_image_format("Base relocation RVA overflows.")
+                })?;
+                f(rva, entry.relocation_type);
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies every relocation to `image`, the image as it is mapped in memory (indexed by RVA).
+    ///
+    /// `delta` is the new image base minus the preferred one, wrapping. The slot following a
+    /// [`BaseRelocationType::HighAdj`] entry is used as the low 16 bits of the adjusted value.
+    /// Machine specific relocation types are not supported and are an error.
+    pub fn apply(&self, image: &mut [u8], delta: u64) -> Result<()> {
+        for block in self.relocations.iter() {
+            block.apply(image, delta)?;
+        }
+        Ok(())
+    }
+
+    /// Builds the relocation blocks for `sites`, given as `(rva, type)`.
+    ///
+    /// Sites are sorted and grouped into a block per 4K page. Blocks with an odd number of
+    /// entries are padded with a [`BaseRelocationType::Absolute`] entry so each block stays
+    /// 32-bit aligned. [`BaseRelocationType::Absolute`] sites are skipped.
+    ///
+    /// [`BaseRelocationType::HighAdj`] needs the low 16 bits of the target in the following slot,
+    /// which can't be given here, so it is an error.
+    pub fn from_rvas(sites: &[(u32, BaseRelocationType)]) -> Result<Self> {
+        const PAGE_MASK: u32 = !0xFFF;
+        if sites
+            .iter()
+            .any(|(_, relocation_type)| *relocation_type == BaseRelocationType::HighAdj)
+        {
+            return Err(PewterError::invalid_image_format(
+                "HighAdj base relocations can't be generated.",
+            ));
+        }
+
+        let mut sites: Vec<_> = sites
+            .iter()
+            .filter(|(_, relocation_type)| *relocation_type != BaseRelocationType::Absolute)
+            .copied()
+            .collect();
+        sites.sort_by_key(|(rva, _)| *rva);
+
+        let mut relocations: Table<BaseRelocationBlockHeader> = Table::new();
+        for (rva, relocation_type) in sites {
+            let base_rva = rva & PAGE_MASK;
+            if relocations.last().map(|block| block.base_rva) != Some(base_rva) {
+                relocations.push(BaseRelocationBlockHeader {
+                    base_rva,
+                    ..Default::default()
+                });
+            }
+            // Just pushed if it didn't already exist.
+            let block = relocations.last_mut().unwrap();
+            block.table.push(BaseRelocationBlockOffsets {
+                relocation_type,
+                offset: (rva - base_rva) as u16,
+            });
+        }
+
+        for block in relocations.iter_mut() {
+            if block.table.len() % 2 != 0 {
+                block.table.push(BaseRelocationBlockOffsets::default());
+            }
+            block.block_size = (BaseRelocationBlockHeader::SIZE
+                + block.table.len() * BaseRelocationBlockOffsets::SIZE)
+                as u32;
+        }
+        Ok(Self { relocations })
+    }
+}
+
+impl WriteData for &BaseRelocationDataDitectory {
+    fn write_to(self, writer: &mut impl crate::io::Writer) -> Result<()> {
+        for block in self.relocations.iter() {
+            writer.write(block)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub struct BaseRelocationBlockHeader {
     /// The image base plus the page RVA is added to each offset to create the VA where the base relocation must be applied.
@@ -45,6 +169,112 @@ pub struct BaseRelocationBlockHeader {
     pub table: Table<BaseRelocationBlockOffsets>,
 }
 
+impl BaseRelocationBlockHeader {
+    /// The size of `base_rva` and `block_size`, not including the entries that follow.
+    pub const SIZE: usize = 8;
+
+    /// Iterates the relocations in this block as `(rva, type)`.
+    ///
+    /// [`BaseRelocationType::Absolute`] padding entries are skipped, and the slot following a
+    /// [`BaseRelocationType::HighAdj`] entry is consumed as its low 16 bits rather than treated as a relocation.
+    /// Entries whose RVA would overflow a `u32` are skipped.
+    pub fn iter_rvas(&self) -> impl Iterator<Item = (u32, BaseRelocationType)> + '_ {
+        self.iter_fixups()
+            .filter_map(|(rva, relocation_type, _)| Some((rva?, relocation_type)))
+    }
+
+    /// Like [`BaseRelocationBlockHeader::iter_rvas`], but also returns the raw slot following a
+    /// [`BaseRelocationType::HighAdj`] entry. It is `None` for every other type, or if the block
+    /// ends before the second slot.
+    ///
+    /// The RVA is `None` if `base_rva` plus the entry offset overflows.
+    fn iter_fixups(
+        &self,
+    ) -> impl Iterator<Item = (Option<u32>, BaseRelocationType, Option<u16>)> + '_ {
+        let mut entries = self.table.iter();
+        core::iter::from_fn(move || loop {
+            let entry = entries.next()?;
+            let adjustment = match entry.relocation_type {
+                BaseRelocationType::HighAdj => {
+                    entries.next().map(BaseRelocationBlockOffsets::to_u16)
+                }
+                _ => None,
+            };
+            if entry.relocation_type != BaseRelocationType::Absolute {
+                return Some((
+                    self.base_rva.checked_add(entry.offset as u32),
+                    entry.relocation_type,
+                    adjustment,
+                ));
+            }
+        })
+    }
+
+    /// Applies the relocations in this block. See [`BaseRelocationDataDitectory::apply`].
+    pub fn apply(&self, image: &mut [u8], delta: u64) -> Result<()> {
+        for (rva, relocation_type, adjustment) in self.iter_fixups() {
+            let rva = rva.ok_or_else(|| {
+                PewterError::invalid_image_format("Base relocation RVA overflows.")
+            })? as usize;
+            match relocation_type {
+                BaseRelocationType::Absolute => {}
+                BaseRelocationType::High => {
+                    let bytes = fixup_bytes(image, rva)?;
+                    let value =
+                        ((u16::from_le_bytes(*bytes) as u32) << 16).wrapping_add(delta as u32);
+                    *bytes = ((value >> 16) as u16).to_le_bytes();
+                }
+                BaseRelocationType::Low => {
+                    let bytes = fixup_bytes(image, rva)?;
+                    *bytes = u16::from_le_bytes(*bytes)
+                        .wrapping_add(delta as u16)
+                        .to_le_bytes();
+                }
+                BaseRelocationType::HighLow => {
+                    let bytes = fixup_bytes(image, rva)?;
+                    *bytes = u32::from_le_bytes(*bytes)
+                        .wrapping_add(delta as u32)
+                        .to_le_bytes();
+                }
+                BaseRelocationType::HighAdj => {
+                    let low = adjustment.ok_or_else(|| {
+                        PewterError::invalid_image_format(
+                            "HighAdj base relocation is missing its second slot.",
+                        )
+                    })?;
+                    let bytes = fixup_bytes(image, rva)?;
+                    // The low half is signed, and the result is rounded to the nearest high half.
+                    let value = ((u16::from_le_bytes(*bytes) as u32) << 16)
+                        .wrapping_add(low as i16 as i32 as u32)
+                        .wrapping_add(delta as u32)
+                        .wrapping_add(0x8000);
+                    *bytes = ((value >> 16) as u16).to_le_bytes();
+                }
+                BaseRelocationType::Dir64 => {
+                    let bytes = fixup_bytes(image, rva)?;
+                    *bytes = u64::from_le_bytes(*bytes).wrapping_add(delta).to_le_bytes();
+                }
+                _ => {
+                    return Err(PewterError::invalid_image_format(
+                        "Unsupported base relocation type.",
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `N` bytes of `image` at `rva` that a relocation updates.
+fn fixup_bytes<const N: usize>(image: &mut [u8], rva: usize) -> Result<&mut [u8; N]> {
+    rva.checked_add(N)
+        .and_then(|end| image.get_mut(rva..end))
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| {
+            PewterError::invalid_image_format("Base relocation is outside of the image.")
+        })
+}
+
 impl ReadData for BaseRelocationBlockHeader {
     fn read(reader: &mut impl crate::io::Reader) -> crate::error::Result<Self> {
         let base_rva = reader.read()?;
@@ -56,7 +286,15 @@ impl ReadData for BaseRelocationBlockHeader {
         let mut relocation_data = reader.read_slice(relocation_block_size)?;
         let mut table = Table::new();
         while !relocation_data.is_empty() {
-            table.push(BaseRelocationBlockOffsets::read(&mut relocation_data)?);
+            let entry = BaseRelocationBlockOffsets::read(&mut relocation_data)?;
+            let is_high_adj = entry.relocation_type == BaseRelocationType::HighAdj;
+            table.push(entry);
+            // The slot after a HighAdj is a value, not a type and offset, so any type bits are valid.
+            if is_high_adj && !relocation_data.is_empty() {
+                table.push(BaseRelocationBlockOffsets::from_adjustment(
+                    relocation_data.read()?,
+                ));
+            }
         }
 
         Ok(Self {
@@ -67,10 +305,13 @@ impl ReadData for BaseRelocationBlockHeader {
     }
 }
 
-impl WriteData for BaseRelocationBlockHeader {
+impl WriteData for &BaseRelocationBlockHeader {
     fn write_to(self, writer: &mut impl crate::io::Writer) -> crate::error::Result<()> {
         writer.write(self.base_rva)?;
         writer.write(self.block_size)?;
+        for entry in self.table.iter() {
+            writer.write(entry)?;
+        }
         Ok(())
     }
 }
@@ -152,9 +393,33 @@ pub struct BaseRelocationBlockOffsets {
     pub relocation_type: BaseRelocationType,
     /// Stored in the remaining 12 bits of the WORD, an offset from the starting address that was specified in the Page RVA field for the block.
     /// This offset specifies where the base relocation is to be applied.
+    ///
+    /// For the slot following a [`BaseRelocationType::HighAdj`], this is the whole 16-bit value.
+    /// See [`BaseRelocationBlockOffsets::from_adjustment`].
     pub offset: u16,
 }
 
+impl BaseRelocationBlockOffsets {
+    pub const SIZE: usize = 2;
+
+    /// The slot following a [`BaseRelocationType::HighAdj`] entry, which holds the low 16 bits of
+    /// the adjusted value instead of a type and offset.
+    ///
+    /// It is stored as [`BaseRelocationType::Absolute`] with all 16 bits in `offset`, so
+    /// [`BaseRelocationBlockOffsets::to_u16`] gives back the original value.
+    pub fn from_adjustment(value: u16) -> Self {
+        Self {
+            relocation_type: BaseRelocationType::Absolute,
+            offset: value,
+        }
+    }
+
+    /// The entry as the 16-bit value stored in the block.
+    pub fn to_u16(&self) -> u16 {
+        ((self.relocation_type.to_u8() as u16) << 12) | self.offset
+    }
+}
+
 impl ReadData for BaseRelocationBlockOffsets {
     fn read(reader: &mut impl crate::io::Reader) -> Result<Self> {
         const OFFSET_MASK: u16 = 0xFFFF >> 4;
@@ -167,3 +432,184 @@ impl ReadData for BaseRelocationBlockOffsets {
         })
     }
 }
+
+impl WriteData for &BaseRelocationBlockOffsets {
+    fn write_to(self, writer: &mut impl crate::io::Writer) -> Result<()> {
+        writer.write(self.to_u16())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::Writer;
+
+    fn entry(relocation_type: BaseRelocationType, offset: u16) -> BaseRelocationBlockOffsets {
+        BaseRelocationBlockOffsets {
+            relocation_type,
+            offset,
+        }
+    }
+
+    #[test]
+    fn iter_rvas_skips_absolute_padding() {
+        let relocations = BaseRelocationDataDitectory {
+            relocations: Table(Vec::from([
+                BaseRelocationBlockHeader {
+                    base_rva: 0x1000,
+                    block_size: 12,
+                    table: Table(Vec::from([
+                        entry(BaseRelocationType::Dir64, 0x10),
+                        entry(BaseRelocationType::Absolute, 0),
+                    ])),
+                },
+                BaseRelocationBlockHeader {
+                    base_rva: 0x2000,
+                    block_size: 10,
+                    table: Table(Vec::from([entry(BaseRelocationType::Dir64, 0x8)])),
+                },
+            ])),
+        };
+        let rvas: Vec<_> = relocations.iter_rvas().collect();
+        assert_eq!(
+            rvas,
+            [
+                (0x1010, BaseRelocationType::Dir64),
+                (0x2008, BaseRelocationType::Dir64)
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_high_adj_then_high_low() {
+        let block = BaseRelocationBlockHeader {
+            base_rva: 0x0,
+            block_size: 14,
+            table: Table(Vec::from([
+                entry(BaseRelocationType::HighAdj, 0x10),
+                // A negative low half, whose top bits are not a valid relocation type.
+                BaseRelocationBlockOffsets::from_adjustment(0xC000),
+                entry(BaseRelocationType::HighLow, 0x20),
+            ])),
+        };
+        let mut data = Vec::new();
+        data.write(&block).unwrap();
+        let block = BaseRelocationBlockHeader::read(&mut data.as_slice()).unwrap();
+        assert_eq!(block.table[1].to_u16(), 0xC000);
+
+        let mut image = [0u8; 0x30];
+        image[0x10..0x12].copy_from_slice(&0x1234u16.to_le_bytes());
+        image[0x20..0x24].copy_from_slice(&0x0040_1000u32.to_le_bytes());
+        block.apply(&mut image, 0x0001_8000).unwrap();
+
+        // The low half is signed: 0x1234_0000 - 0x4000 + 0x1_8000 = 0x1235_4000.
+        assert_eq!(image[0x10..0x12], 0x1235u16.to_le_bytes());
+        // The HighLow is applied by itself, not consumed as the adjustment.
+        assert_eq!(image[0x20..0x24], 0x0041_9000u32.to_le_bytes());
+
+        let truncated = BaseRelocationBlockHeader {
+            table: Table(Vec::from([entry(BaseRelocationType::HighAdj, 0x10)])),
+            ..Default::default()
+        };
+        assert!(truncated.apply(&mut image, 0x1000).is_err());
+    }
+
+    #[test]
+    fn for_each_relocation_matches_parse() {
+        let sites: Vec<_> = (0..0x3000)
+            .step_by(0x18)
+            .map(|rva| (rva, BaseRelocationType::Dir64))
+            .collect();
+        let mut section_data = Vec::new();
+        section_data
+            .write(&BaseRelocationDataDitectory::from_rvas(&sites).unwrap())
+            .unwrap();
+        let mut high_adj_block = BaseRelocationBlockHeader {
+            base_rva: 0x4000,
+            block_size: 14,
+            table: Table(Vec::from([
+                entry(BaseRelocationType::HighAdj, 0x10),
+                BaseRelocationBlockOffsets::from_adjustment(0xC000),
+                entry(BaseRelocationType::HighLow, 0x20),
+            ])),
+        };
+        section_data.write(&high_adj_block).unwrap();
+
+        let mut rvas = Vec::new();
+        BaseRelocationDataDitectory::for_each_relocation(&section_data, |rva, relocation_type| {
+            rvas.push((rva, relocation_type))
+        })
+        .unwrap();
+
+        let mut data_ptr = section_data.as_slice();
+        let mut parsed = BaseRelocationDataDitectory::default();
+        while !data_ptr.is_empty() {
+            parsed
+                .relocations
+                .push(BaseRelocationBlockHeader::read(&mut data_ptr).unwrap());
+        }
+        let expected: Vec<_> = parsed.iter_rvas().collect();
+        assert_eq!(rvas, expected);
+        assert_eq!(rvas.len(), sites.len() + 2);
+
+        high_adj_block.block_size = 4;
+        let mut truncated = Vec::new();
+        truncated.write(&high_adj_block).unwrap();
+        assert!(BaseRelocationDataDitectory::for_each_relocation(&truncated, |_, _| {}).is_err());
+    }
+
+    #[test]
+    fn for_each_relocation_rva_overflow_is_an_error() {
+        let mut section_data = Vec::new();
+        section_data
+            .write(&BaseRelocationBlockHeader {
+                base_rva: u32::MAX,
+                block_size: 12,
+                table: Table(Vec::from([
+                    entry(BaseRelocationType::HighLow, 1),
+                    entry(BaseRelocationType::Absolute, 0),
+                ])),
+            })
+            .unwrap();
+        assert!(
+            BaseRelocationDataDitectory::for_each_relocation(&section_data, |_, _| {}).is_err()
+        );
+    }
+
+    #[test]
+    fn block_rva_overflow_is_skipped_or_an_error() {
+        let block = BaseRelocationBlockHeader {
+            base_rva: u32::MAX - 0x10,
+            block_size: 12,
+            table: Table(Vec::from([
+                entry(BaseRelocationType::HighLow, 0x8),
+                entry(BaseRelocationType::HighLow, 0x20),
+            ])),
+        };
+        let rvas: Vec<_> = block.iter_rvas().collect();
+        assert_eq!(rvas, [(u32::MAX - 0x8, BaseRelocationType::HighLow)]);
+        assert!(block.apply(&mut [0u8; 0x10], 0x1000).is_err());
+    }
+
+    #[test]
+    fn iter_rvas_high_adj_uses_two_slots() {
+        let block = BaseRelocationBlockHeader {
+            base_rva: 0x1000,
+            block_size: 14,
+            table: Table(Vec::from([
+                entry(BaseRelocationType::HighAdj, 0x10),
+                // The low 16 bits of the HighAdj value, not a relocation.
+                entry(BaseRelocationType::HighLow, 0x456),
+                entry(BaseRelocationType::HighLow, 0x20),
+            ])),
+        };
+        let rvas: Vec<_> = block.iter_rvas().collect();
+        assert_eq!(
+            rvas,
+            [
+                (0x1010, BaseRelocationType::HighAdj),
+                (0x1020, BaseRelocationType::HighLow)
+            ]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! The Bound Import Table (Image Only)
+//!
+//! When an image is bound, the bound import table records the time/date stamps of the DLLs
+//! that the import address table was bound against, so the loader can tell whether the
+//! pre-resolved addresses are still valid.
+
+use crate::{
+    error::Result,
+    io::{ReadData, Reader, WriteData},
+    vec::Vec,
+};
+
+use super::ParseSectionData;
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct BoundImportDataDirectory(pub crate::vec::Vec<u8>);
+
+impl ParseSectionData for BoundImportDataDirectory {
+    fn parse(
+        section_data: &[u8],
+        _: &super::Sections,
+        _: &crate::pe::optional_header::OptionalHeader,
+        _: &crate::pe::coff::CoffFileHeader,
+    ) -> crate::error::Result<Self> {
+        Ok(Self(crate::vec::Vec::from(section_data)))
+    }
+}
+
+impl BoundImportDataDirectory {
+    /// Parses the descriptors, up to the null descriptor that ends the table.
+    ///
+    /// A table without a null descriptor ends at the end of the directory.
+    pub fn descriptors(&self) -> Result<Vec<BoundImportDescriptor>> {
+        let mut data_ptr = self.0.as_slice();
+        let mut descriptors = Vec::new();
+        while !data_ptr.is_empty() {
+            let descriptor: BoundImportDescriptor = data_ptr.read()?;
+            if descriptor.is_null() {
+                break;
+            }
+            descriptors.push(descriptor);
+        }
+        Ok(descriptors)
+    }
+
+    /// The null terminated module name at `offset_module_name`, which is an offset from the
+    /// start of the bound import table.
+    pub fn module_name(&self, offset_module_name: u16) -> Option<&str> {
+        let name = self.0.get(offset_module_name as usize..)?;
+        let null_terminator = name.iter().position(|c| *c == 0)?;
+        core::str::from_utf8(&name[..null_terminator]).ok()
+    }
+}
+
+/// A DLL the image was bound against, with the DLLs that its forwarded exports were bound to.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct BoundImportDescriptor {
+    /// The time/date stamp of the DLL the image was bound against.
+    pub time_date_stamp: u32,
+    /// The offset of the DLL name from the start of the bound import table.
+    pub offset_module_name: u16,
+    /// The `IMAGE_BOUND_FORWARDER_REF` entries that immediately follow the descriptor.
+    /// They are the same size as a descriptor, but are not descriptors themselves.
+    pub forwarder_refs: Vec<BoundForwarderRef>,
+}
+
+impl BoundImportDescriptor {
+    /// The size of the descriptor, not including its forwarder refs.
+    pub const SIZE: usize = 8;
+
+    /// Returns true for the all-zero descriptor that ends the table.
+    pub fn is_null(&self) -> bool {
+        self.time_date_stamp == 0 && self.offset_module_name == 0 && self.forwarder_refs.is_empty()
+    }
+}
+
+impl ReadData for BoundImportDescriptor {
+    fn read(reader: &mut impl Reader) -> Result<Self> {
+        let time_date_stamp = reader.read()?;
+        let offset_module_name = reader.read()?;
+        let number_of_module_forwarder_refs: u16 = reader.read()?;
+        // Exactly this many refs belong to the descriptor, the next descriptor follows them.
+        let forwarder_refs = (0..number_of_module_forwarder_refs)
+            .map(|_| reader.read())
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            time_date_stamp,
+            offset_module_name,
+            forwarder_refs,
+        })
+    }
+}
+
+impl WriteData for &BoundImportDescriptor {
+    fn write_to(self, writer: &mut impl crate::io::Writer) -> Result<()> {
+        writer.write(self.time_date_stamp)?;
+        writer.write(self.offset_module_name)?;
+        writer.write(self.forwarder_refs.len() as u16)?;
+        for forwarder_ref in &self.forwarder_refs {
+            writer.write(forwarder_ref)?;
+        }
+        Ok(())
+    }
+}
+
+/// A DLL that a forwarded export of a [`BoundImportDescriptor`] DLL was bound to.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct BoundForwarderRef {
+    /// The time/date stamp of the DLL the forwarded export was bound against.
+    pub time_date_stamp: u32,
+    /// The offset of the DLL name from the start of the bound import table.
+    pub offset_module_name: u16,
+    /// Reserved, must be zero.
+    pub reserved: u16,
+}
+
+impl BoundForwarderRef {
+    pub const SIZE: usize = 8;
+}
+
+impl ReadData for BoundForwarderRef {
+    fn read(reader: &mut impl Reader) -> Result<Self> {
+        Ok(Self {
+            time_date_stamp: reader.read()?,
+            offset_module_name: reader.read()?,
+            reserved: reader.read()?,
+        })
+    }
+}
+
+impl WriteData for &BoundForwarderRef {
+    fn write_to(self, writer: &mut impl crate::io::Writer) -> Result<()> {
+        writer.write(self.time_date_stamp)?;
+        writer.write(self.offset_module_name)?;
+        writer.write(self.reserved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::Writer;
+
+    #[test]
+    fn forwarder_refs_are_not_descriptors() {
+        let first = BoundImportDescriptor {
+            time_date_stamp: 0x1111,
+            offset_module_name: 0x28,
+            forwarder_refs: Vec::from([
+                BoundForwarderRef {
+                    time_date_stamp: 0x2222,
+                    offset_module_name: 0x35,
+                    reserved: 0,
+                },
+                BoundForwarderRef {
+                    time_date_stamp: 0x3333,
+                    offset_module_name: 0x28,
+                    reserved: 0,
+                },
+            ]),
+        };
+        let second = BoundImportDescriptor {
+            time_date_stamp: 0x4444,
+            offset_module_name: 0x35,
+            forwarder_refs: Vec::new(),
+        };
+
+        let mut data = Vec::new();
+        data.write(&first).unwrap();
+        data.write(&second).unwrap();
+        data.write(&BoundImportDescriptor::default()).unwrap();
+        assert_eq!(data.len(), 0x28);
+        data.write_slice(b"KERNEL32.dll\0ntdll.dll\0").unwrap();
+
+        let bound_imports = BoundImportDataDirectory(data);
+        let descriptors = bound_imports.descriptors().unwrap();
+        assert_eq!(descriptors, [first, second]);
+        assert_eq!(
+            bound_imports.module_name(descriptors[0].offset_module_name),
+            Some("KERNEL32.dll")
+        );
+        assert_eq!(
+            bound_imports.module_name(descriptors[0].forwarder_refs[0].offset_module_name),
+            Some("ntdll.dll")
+        );
+    }
+}
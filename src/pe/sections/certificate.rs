@@ -1,7 +1,8 @@
 //! The Attribute Certificate Table (Image Only)
 use crate::containers::Table;
-use crate::error::Result;
-use crate::io::ReadData;
+use crate::error::{PewterError, Result};
+use crate::io::{ReadData, Reader};
+use crate::pe::optional_header::data_directories::ImageDataDirectory;
 use crate::vec::Vec;
 
 use super::ParseSectionData;
@@ -22,21 +23,75 @@ impl ParseSectionData for CertificateDataDirectory {
         _: &crate::pe::optional_header::OptionalHeader,
         _: &crate::pe::coff::CoffFileHeader,
     ) -> Result<Self> {
+        Self::parse_table(section_data)
+    }
+}
+
+impl CertificateDataDirectory {
+    /// Parse the certificate table pointed to by `data_directory`.
+    ///
+    /// Unlike other data directories, the `virtual_address` of the certificate table is a file
+    /// offset, not an RVA. The table is not loaded into memory, so it is usually not part of any section.
+    pub fn parse_at_file_offset(
+        file_bytes: &[u8],
+        data_directory: &ImageDataDirectory,
+    ) -> Result<Option<Self>> {
+        if data_directory.is_null() {
+            return Ok(None);
+        }
+        let start = data_directory.virtual_address as usize;
+        let table_data = start
+            .checked_add(data_directory.size as usize)
+            .and_then(|end| file_bytes.get(start..end))
+            .ok_or_else(|| {
+                PewterError::invalid_image_format("Certificate table is outside of the file")
+            })?;
+        Self::parse_table(table_data).map(Some)
+    }
+
+    fn parse_table(section_data: &[u8]) -> Result<Self> {
         let mut offset = 0;
         let mut certificates = Table::new();
-        loop {
-            let cert = Certificate::read(&mut section_data[offset..].as_ref())?;
-            offset += cert.length as usize;
-            offset = (offset + 7) & !7;
-            certificates.push(cert);
-            if offset == section_data.len() {
+        while offset < section_data.len() {
+            let mut cert_data = &section_data[offset..];
+            // Trailing zero padding ends the table.
+            if cert_data.iter().all(|b| *b == 0) {
                 break;
             }
+            let length: u32 = cert_data.peek()?;
+            if length == 0 {
+                return Err(PewterError::invalid_image_format(
+                    "Certificate has a length of 0",
+                ));
+            }
+            let cert = Certificate::read(&mut cert_data)?;
+            // Each entry is padded to 8 bytes, but the padding after the last one may be missing.
+            offset = (offset + length as usize)
+                .next_multiple_of(8)
+                .min(section_data.len());
+            certificates.push(cert);
         }
         Ok(Self { certificates })
     }
 }
 
+impl CertificateDataDirectory {
+    /// The first Authenticode signature ([`CertificateType::PkcsSignedData`]) in the table.
+    pub fn authenticode(&self) -> Option<&Certificate> {
+        self.iter_by_type(CertificateType::PkcsSignedData).next()
+    }
+
+    /// Iterates the certificates with the given `certificate_type`.
+    pub fn iter_by_type(
+        &self,
+        certificate_type: CertificateType,
+    ) -> impl Iterator<Item = &Certificate> {
+        self.certificates
+            .iter()
+            .filter(move |cert| cert.certificate_type == certificate_type)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u16)]
 pub enum CertificateType {
@@ -116,3 +171,83 @@ impl ReadData for Certificate {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::Writer;
+
+    fn certificate_bytes(certificate: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.write(8 + certificate.len() as u32).unwrap();
+        data.write(0x0200u16).unwrap();
+        data.write(0x0002u16).unwrap();
+        data.write_slice(certificate).unwrap();
+        data
+    }
+
+    #[test]
+    fn table_past_end_of_file_is_an_error() {
+        let data = certificate_bytes(&[1, 2, 3, 4]);
+        let data_directory = ImageDataDirectory {
+            virtual_address: u32::MAX,
+            size: u32::MAX,
+        };
+        assert!(matches!(
+            CertificateDataDirectory::parse_at_file_offset(&data, &data_directory),
+            Err(PewterError::InvalidImageFormat { .. })
+        ));
+    }
+
+    #[test]
+    fn trailing_zero_padding_ends_table() {
+        let mut data = certificate_bytes(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        data.extend_from_slice(&[0; 4]);
+
+        let table = CertificateDataDirectory::parse_table(&data).unwrap();
+        assert_eq!(table.certificates.len(), 1);
+        assert_eq!(table.certificates[0].certificate, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn unpadded_last_certificate() {
+        let mut data = certificate_bytes(&[1, 2, 3, 4]);
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&certificate_bytes(&[5, 6, 7]));
+
+        let table = CertificateDataDirectory::parse_table(&data).unwrap();
+        assert_eq!(table.certificates.len(), 2);
+        assert_eq!(table.certificates[1].certificate, [5, 6, 7]);
+    }
+
+    #[test]
+    fn zero_length_certificate_is_an_error() {
+        let mut data = certificate_bytes(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        data.extend_from_slice(&[0, 0, 0, 0, 1, 0, 0, 0]);
+        assert!(CertificateDataDirectory::parse_table(&data).is_err());
+    }
+
+    #[test]
+    fn authenticode_skips_x509() {
+        let certificate = |certificate_type, certificate: &[u8]| Certificate {
+            length: 8 + certificate.len() as u32,
+            revision: CertificateRevision::Revision2_0,
+            certificate_type,
+            certificate: Vec::from(certificate),
+        };
+        let table = CertificateDataDirectory {
+            certificates: Table(Vec::from([
+                certificate(CertificateType::X509, &[1, 2, 3]),
+                certificate(CertificateType::PkcsSignedData, &[4, 5, 6]),
+            ])),
+        };
+
+        let authenticode = table.authenticode().unwrap();
+        assert_eq!(authenticode.certificate, [4, 5, 6]);
+        assert_eq!(table.iter_by_type(CertificateType::X509).count(), 1);
+        assert_eq!(
+            table.iter_by_type(CertificateType::TsStackSigned).count(),
+            0
+        );
+    }
+}
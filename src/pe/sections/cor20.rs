@@ -1,5 +1,5 @@
 use crate::{
-    error::{Result, PewterError},
+    error::{PewterError, Result},
     io::Reader,
     pe::{
         coff::CoffFileHeader,
@@ -7,7 +7,7 @@ use crate::{
     },
 };
 
-use super::ParseSectionData;
+use super::{ParseSectionData, SectionTable};
 
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub struct ImageCor20Header {
@@ -24,6 +24,34 @@ pub struct ImageCor20Header {
     pub managed_native_header: ImageDataDirectory,
 }
 
+impl ImageCor20Header {
+    /// `COMIMAGE_FLAGS_STRONGNAMESIGNED`, the image has a strong name signature.
+    pub const STRONGNAMESIGNED: u32 = 0x00000008;
+
+    /// Returns true if [`ImageCor20Header::STRONGNAMESIGNED`] is set in `flags`.
+    pub fn is_strong_named(&self) -> bool {
+        (self.flags & Self::STRONGNAMESIGNED) != 0
+    }
+
+    /// The strong name signature bytes pointed to by `strong_name_signature`.
+    /// Returns `None` if the directory is null or can't be mapped into `file_bytes`.
+    pub fn strong_name_blob<'a>(
+        &self,
+        file_bytes: &'a [u8],
+        section_table: &SectionTable,
+    ) -> Option<&'a [u8]> {
+        if self.strong_name_signature.is_null() {
+            return None;
+        }
+        section_table
+            .find_rva_data(
+                file_bytes,
+                self.strong_name_signature.virtual_address as usize,
+            )?
+            .get(..self.strong_name_signature.size as usize)
+    }
+}
+
 impl ParseSectionData for ImageCor20Header {
     fn parse(
         section_data: &[u8],
@@ -54,3 +82,42 @@ impl ParseSectionData for ImageCor20Header {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{containers::Table, pe::sections::SectionTableRow, vec::Vec};
+
+    #[test]
+    fn strong_name_blob_is_read_from_section() {
+        let mut file_bytes = Vec::from([0u8; 0x400]);
+        file_bytes[0x210..0x218].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        let section_table = SectionTable(Table(Vec::from([SectionTableRow {
+            virtual_address: 0x2000,
+            virtual_size: 0x200,
+            pointer_to_raw_data: 0x200,
+            size_of_raw_data: 0x200,
+            ..Default::default()
+        }])));
+
+        let header = ImageCor20Header {
+            flags: ImageCor20Header::STRONGNAMESIGNED,
+            strong_name_signature: ImageDataDirectory {
+                virtual_address: 0x2010,
+                size: 8,
+            },
+            ..Default::default()
+        };
+        assert!(header.is_strong_named());
+        assert_eq!(
+            header.strong_name_blob(&file_bytes, &section_table),
+            Some([1, 2, 3, 4, 5, 6, 7, 8].as_slice())
+        );
+
+        let unsigned = ImageCor20Header::default();
+        assert!(!unsigned.is_strong_named());
+        assert!(unsigned
+            .strong_name_blob(&file_bytes, &section_table)
+            .is_none());
+    }
+}
@@ -0,0 +1,339 @@
+//! The .debug Section
+//!
+//! The .debug section is used in object files to contain compiler-generated debug information
+//! and in image files to contain all of the debug information that is generated.
+//!
+//! Image files contain an optional debug directory that indicates what form of debug information
+//! is present and where it is. This directory consists of an array of debug directory entries
+//! whose location and size are indicated in the image optional header.
+use crate::{
+    containers::Table,
+    error::{PewterError, Result},
+    io::{ReadData, Reader, WriteData},
+    pe::{coff::CoffFileHeader, optional_header::OptionalHeader},
+    vec::Vec,
+};
+
+use super::{ParseSectionData, Sections};
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct DebugDataDirectory {
+    /// Each debug directory entry identifies the location and size of a block of debug information.
+    pub entries: Table<DebugDirectory>,
+    /// The data of the first [`DebugType::Repro`] entry, if there is one and its data could be read.
+    pub repro: Option<ReproInfo>,
+}
+
+impl ParseSectionData for DebugDataDirectory {
+    fn parse(
+        section_data: &[u8],
+        sections: &Sections,
+        _: &OptionalHeader,
+        _: &CoffFileHeader,
+    ) -> Result<Self> {
+        let entries: Table<DebugDirectory> = Table::new_linear(
+            &mut section_data.as_ref(),
+            section_data.len() / DebugDirectory::SIZE,
+        )?;
+        // The repro hash is extra information, so bad repro data doesn't fail the whole directory.
+        let repro = entries
+            .iter()
+            .find(|entry| entry.debug_type == DebugType::Repro)
+            .and_then(|entry| ReproInfo::parse(entry, sections).ok());
+        Ok(Self { entries, repro })
+    }
+}
+
+impl DebugDataDirectory {
+    /// The deterministic build hash from the [`DebugType::Repro`] entry.
+    ///
+    /// Returns `None` if the image has no repro entry, or its data is outside the image.
+    pub fn repro_hash(&self) -> Option<&[u8]> {
+        self.repro.as_ref().map(|repro| repro.hash.as_slice())
+    }
+}
+
+/// The data of a [`DebugType::Repro`] entry, present in images built with `/Brepro`.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct ReproInfo {
+    /// The hash that replaces the timestamps in the image.
+    /// Empty if the entry has no data, as with older linkers.
+    pub hash: Vec<u8>,
+}
+
+impl ReproInfo {
+    /// Reads the data of `entry`, which is the length of the hash followed by the hash.
+    pub fn parse(entry: &DebugDirectory, sections: &Sections) -> Result<Self> {
+        if entry.size_of_data == 0 {
+            return Ok(Self::default());
+        }
+        let mut repro_data = sections
+            .find_rva_data(entry.address_of_raw_data as usize)
+            .and_then(|data| data.get(..entry.size_of_data as usize))
+            .ok_or_else(|| {
+                PewterError::invalid_image_format("Failed to map repro debug data inside image")
+            })?;
+        let hash_size: u32 = repro_data.read()?;
+        Ok(Self {
+            hash: Vec::from(repro_data.read_slice(hash_size as usize)?),
+        })
+    }
+}
+
+/// The format of debugging information.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum DebugType {
+    /// An unknown value that is ignored by all tools.
+    #[default]
+    Unknown = 0,
+    /// The COFF debug information (line numbers, symbol table, and string table).
+    Coff = 1,
+    /// The Visual C++ debug information.
+    CodeView = 2,
+    /// The frame pointer omission (FPO) information.
+    Fpo = 3,
+    /// The location of DBG file.
+    Misc = 4,
+    /// A copy of .pdata section.
+    Exception = 5,
+    /// Reserved.
+    Fixup = 6,
+    /// The mapping from an RVA in image to an RVA in source image.
+    OmapToSrc = 7,
+    /// The mapping from an RVA in source image to an RVA in image.
+    OmapFromSrc = 8,
+    /// Reserved for Borland.
+    Borland = 9,
+    /// Reserved.
+    Reserved10 = 10,
+    /// Reserved.
+    Clsid = 11,
+    /// Visual C++ feature information.
+    VcFeature = 12,
+    /// Profile guided optimization information.
+    Pogo = 13,
+    /// Incremental link-time code generation information.
+    Iltcg = 14,
+    /// Intel memory protection extensions information.
+    Mpx = 15,
+    /// PE determinism or reproducibility.
+    Repro = 16,
+    /// Extended DLL characteristics bits.
+    ExDllCharacteristics = 20,
+    /// Other debug type
+    Other(u32),
+}
+
+impl DebugType {
+    pub fn from_u32(debug_type: u32) -> Self {
+        match debug_type {
+            0 => Self::Unknown,
+            1 => Self::Coff,
+            2 => Self::CodeView,
+            3 => Self::Fpo,
+            4 => Self::Misc,
+            5 => Self::Exception,
+            6 => Self::Fixup,
+            7 => Self::OmapToSrc,
+            8 => Self::OmapFromSrc,
+            9 => Self::Borland,
+            10 => Self::Reserved10,
+            11 => Self::Clsid,
+            12 => Self::VcFeature,
+            13 => Self::Pogo,
+            14 => Self::Iltcg,
+            15 => Self::Mpx,
+            16 => Self::Repro,
+            20 => Self::ExDllCharacteristics,
+            n => Self::Other(n),
+        }
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        match self {
+            Self::Unknown => 0,
+            Self::Coff => 1,
+            Self::CodeView => 2,
+            Self::Fpo => 3,
+            Self::Misc => 4,
+            Self::Exception => 5,
+            Self::Fixup => 6,
+            Self::OmapToSrc => 7,
+            Self::OmapFromSrc => 8,
+            Self::Borland => 9,
+            Self::Reserved10 => 10,
+            Self::Clsid => 11,
+            Self::VcFeature => 12,
+            Self::Pogo => 13,
+            Self::Iltcg => 14,
+            Self::Mpx => 15,
+            Self::Repro => 16,
+            Self::ExDllCharacteristics => 20,
+            Self::Other(n) => *n,
+        }
+    }
+}
+
+/// Debug Directory (Image Only)
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct DebugDirectory {
+    /// Reserved, must be zero.
+    pub characteristics: u32,
+    /// The time and date that the debug data was created.
+    pub time_date_stamp: u32,
+    /// The major version number of the debug data format.
+    pub major_version: u16,
+    /// The minor version number of the debug data format.
+    pub minor_version: u16,
+    /// The format of debugging information.
+    pub debug_type: DebugType,
+    /// The size of the debug data (not including the debug directory itself).
+    pub size_of_data: u32,
+    /// The address of the debug data when loaded, relative to the image base.
+    pub address_of_raw_data: u32,
+    /// The file pointer to the debug data.
+    pub pointer_to_raw_data: u32,
+}
+
+impl DebugDirectory {
+    pub const SIZE: usize = 28;
+}
+
+impl ReadData for DebugDirectory {
+    fn read(reader: &mut impl crate::io::Reader) -> Result<Self> {
+        Ok(Self {
+            characteristics: reader.read()?,
+            time_date_stamp: reader.read()?,
+            major_version: reader.read()?,
+            minor_version: reader.read()?,
+            debug_type: DebugType::from_u32(reader.read()?),
+            size_of_data: reader.read()?,
+            address_of_raw_data: reader.read()?,
+            pointer_to_raw_data: reader.read()?,
+        })
+    }
+}
+
+impl WriteData for &DebugDirectory {
+    fn write_to(self, writer: &mut impl crate::io::Writer) -> Result<()> {
+        writer.write(self.characteristics)?;
+        writer.write(self.time_date_stamp)?;
+        writer.write(self.major_version)?;
+        writer.write(self.minor_version)?;
+        writer.write(self.debug_type.to_u32())?;
+        writer.write(self.size_of_data)?;
+        writer.write(self.address_of_raw_data)?;
+        writer.write(self.pointer_to_raw_data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        io::Writer,
+        pe::sections::{SectionRow, SectionTableRow},
+    };
+
+    #[test]
+    fn debug_directory_is_28_bytes() {
+        let buffer = [0u8; DebugDirectory::SIZE];
+        let read_ptr = &mut buffer.as_slice();
+        DebugDirectory::read(read_ptr).unwrap();
+        assert!(read_ptr.is_empty());
+    }
+
+    #[test]
+    fn repro_hash_from_type_16_entry() {
+        let hash: Vec<u8> = (0..32).collect();
+        let entries = [
+            DebugDirectory {
+                debug_type: DebugType::CodeView,
+                ..Default::default()
+            },
+            DebugDirectory {
+                debug_type: DebugType::Repro,
+                size_of_data: 4 + hash.len() as u32,
+                address_of_raw_data: 0x1000 + 2 * DebugDirectory::SIZE as u32,
+                ..Default::default()
+            },
+        ];
+        let mut section_data = Vec::new();
+        for entry in &entries {
+            section_data.write(entry).unwrap();
+        }
+        section_data.write(hash.len() as u32).unwrap();
+        section_data.write_slice(&hash).unwrap();
+
+        let sections = Sections(Table(Vec::from([SectionRow {
+            row: SectionTableRow {
+                virtual_address: 0x1000,
+                virtual_size: section_data.len() as u32,
+                size_of_raw_data: section_data.len() as u32,
+                ..Default::default()
+            },
+            data: &section_data,
+        }])));
+        let directory_size = entries.len() * DebugDirectory::SIZE;
+
+        let debug = DebugDataDirectory::parse(
+            &section_data[..directory_size],
+            &sections,
+            &OptionalHeader::default(),
+            &CoffFileHeader::default(),
+        )
+        .unwrap();
+        assert_eq!(debug.entries.len(), 2);
+        assert_eq!(debug.repro_hash(), Some(hash.as_slice()));
+
+        let without_repro = DebugDataDirectory::parse(
+            &section_data[..DebugDirectory::SIZE],
+            &sections,
+            &OptionalHeader::default(),
+            &CoffFileHeader::default(),
+        )
+        .unwrap();
+        assert_eq!(without_repro.repro_hash(), None);
+    }
+
+    #[test]
+    fn unmapped_repro_data_is_not_an_error() {
+        let entries = [
+            DebugDirectory {
+                debug_type: DebugType::CodeView,
+                ..Default::default()
+            },
+            DebugDirectory {
+                debug_type: DebugType::Repro,
+                size_of_data: 36,
+                address_of_raw_data: 0x8000,
+                ..Default::default()
+            },
+        ];
+        let mut section_data = Vec::new();
+        for entry in &entries {
+            section_data.write(entry).unwrap();
+        }
+        let sections = Sections(Table(Vec::from([SectionRow {
+            row: SectionTableRow {
+                virtual_address: 0x1000,
+                virtual_size: section_data.len() as u32,
+                size_of_raw_data: section_data.len() as u32,
+                ..Default::default()
+            },
+            data: &section_data,
+        }])));
+
+        let debug = DebugDataDirectory::parse(
+            &section_data,
+            &sections,
+            &OptionalHeader::default(),
+            &CoffFileHeader::default(),
+        )
+        .unwrap();
+        assert_eq!(debug.entries.len(), 2);
+        assert_eq!(debug.repro_hash(), None);
+    }
+}
@@ -0,0 +1,223 @@
+//! Delay-Load Import Tables (Image Only)
+//!
+//! These tables were added to the image to support a uniform mechanism for applications
+//! to delay the loading of a DLL until the first call into that DLL. The layout of the tables
+//! matches that of the traditional import tables.
+use crate::{
+    containers::Table,
+    error::{PewterError, Result},
+    io::{ReadData, Reader, WriteData},
+    pe::{
+        coff::CoffFileHeader,
+        optional_header::{OptionalHeader, OptionalHeaderMagic},
+    },
+};
+
+use super::{ParseSectionData, Sections};
+
+use crate::vec::Vec;
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct DelayImportDataDirectory {
+    /// The delay-load directory table is the counterpart to the import directory table.
+    /// The last entry is empty (filled with null values), which indicates the end of the table.
+    pub descriptors: Table<DelayImportDescriptor>,
+}
+
+impl ParseSectionData for DelayImportDataDirectory {
+    fn parse(
+        section_data: &[u8],
+        sections: &Sections,
+        optional_header: &OptionalHeader,
+        _: &CoffFileHeader,
+    ) -> Result<Self> {
+        let mut reader = section_data;
+        let mut descriptors = Vec::new();
+        loop {
+            let mut descriptor: DelayImportDescriptor = reader.read()?;
+            if descriptor.is_null() {
+                break;
+            }
+            descriptor.bound_addresses = descriptor
+                .parse_bound_addresses(sections, optional_header.standard_fields.magic)?;
+            descriptors.push(descriptor);
+        }
+        Ok(Self {
+            descriptors: Table(descriptors),
+        })
+    }
+}
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct DelayImportDescriptor {
+    /// Must be zero.
+    pub attributes: u32,
+    /// The RVA of the name of the DLL to be loaded.
+    /// The name resides in the read-only data section of the image.
+    pub name_rva: u32,
+    /// The RVA of the module handle (in the data section of the image) of the DLL to be delay-loaded.
+    /// It is used for storage by the routine that is supplied to manage delay-loading.
+    pub module_handle_rva: u32,
+    /// The RVA of the delay-load import address table.
+    pub delay_import_address_table_rva: u32,
+    /// The RVA of the delay-load name table, which contains the names of the imports that might
+    /// need to be loaded. This matches the layout of the import name table.
+    pub delay_import_name_table_rva: u32,
+    /// The RVA of the bound delay-load address table, if it exists.
+    pub bound_delay_import_table_rva: u32,
+    /// The RVA of the unload delay-load address table, if it exists. This is an exact copy of the
+    /// delay import address table. If the caller unloads the DLL, this table should be copied back
+    /// over the delay import address table so that subsequent calls to the DLL continue to use the
+    /// thunking mechanism correctly.
+    pub unload_delay_import_table_rva: u32,
+    /// The timestamp of the DLL to which this image has been bound.
+    pub time_date_stamp: u32,
+    /// The addresses in the bound delay-load address table, from [`Self::bound_delay_import_table_rva`].
+    /// This parallels the delay-load name table. Empty if the imports are not bound.
+    ///
+    /// This is not part of the descriptor, and is not read or written with it.
+    pub bound_addresses: Vec<u64>,
+}
+
+impl DelayImportDescriptor {
+    pub const SIZE: usize = 32;
+
+    pub fn is_null(&self) -> bool {
+        self == &DelayImportDescriptor::default()
+    }
+
+    /// Reads the bound delay-load address table. Entries are 32-bit for PE32 and 64-bit for PE32+,
+    /// and the table ends with a zero entry.
+    fn parse_bound_addresses(
+        &self,
+        sections: &Sections,
+        magic: OptionalHeaderMagic,
+    ) -> Result<Vec<u64>> {
+        if self.bound_delay_import_table_rva == 0 {
+            return Ok(Vec::new());
+        }
+        let mut bound_table_data = sections
+            .find_rva_data(self.bound_delay_import_table_rva as usize)
+            .ok_or_else(|| {
+                PewterError::invalid_image_format(
+                    "Failed to map bound_delay_import_table_rva inside image",
+                )
+            })?;
+
+        let mut bound_addresses = Vec::new();
+        loop {
+            let address = match magic {
+                OptionalHeaderMagic::PE32 | OptionalHeaderMagic::Other(_) => {
+                    u32::read(&mut bound_table_data)? as u64
+                }
+                OptionalHeaderMagic::PE32Plus => u64::read(&mut bound_table_data)?,
+            };
+            if address == 0 {
+                break;
+            }
+            bound_addresses.push(address);
+        }
+        Ok(bound_addresses)
+    }
+}
+
+impl ReadData for DelayImportDescriptor {
+    fn read(reader: &mut impl crate::io::Reader) -> Result<Self> {
+        Ok(Self {
+            attributes: reader.read()?,
+            name_rva: reader.read()?,
+            module_handle_rva: reader.read()?,
+            delay_import_address_table_rva: reader.read()?,
+            delay_import_name_table_rva: reader.read()?,
+            bound_delay_import_table_rva: reader.read()?,
+            unload_delay_import_table_rva: reader.read()?,
+            time_date_stamp: reader.read()?,
+            bound_addresses: Vec::new(),
+        })
+    }
+}
+
+impl WriteData for &DelayImportDescriptor {
+    fn write_to(self, writer: &mut impl crate::io::Writer) -> Result<()> {
+        writer.write(self.attributes)?;
+        writer.write(self.name_rva)?;
+        writer.write(self.module_handle_rva)?;
+        writer.write(self.delay_import_address_table_rva)?;
+        writer.write(self.delay_import_name_table_rva)?;
+        writer.write(self.bound_delay_import_table_rva)?;
+        writer.write(self.unload_delay_import_table_rva)?;
+        writer.write(self.time_date_stamp)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        io::Writer,
+        pe::{
+            optional_header::OptionalHeaderStandardFields,
+            sections::{SectionRow, SectionTableRow},
+        },
+    };
+
+    #[test]
+    fn bound_table_populates_bound_addresses() {
+        // The section is mapped at 0x1000 with the descriptors at the start and the bound table at 0x1100.
+        let mut file_bytes = Vec::new();
+        let descriptors = [
+            DelayImportDescriptor {
+                attributes: 1,
+                name_rva: 0x1080,
+                bound_delay_import_table_rva: 0x1100,
+                ..Default::default()
+            },
+            DelayImportDescriptor {
+                attributes: 1,
+                name_rva: 0x1090,
+                ..Default::default()
+            },
+            DelayImportDescriptor::default(),
+        ];
+        for descriptor in &descriptors {
+            file_bytes.write(descriptor).unwrap();
+        }
+        file_bytes.resize(0x100, 0);
+        for address in [0x1_4000_1000u64, 0x1_4000_2000, 0] {
+            file_bytes.write(address).unwrap();
+        }
+        file_bytes.resize(0x200, 0);
+
+        let sections = Sections(Table(Vec::from([SectionRow {
+            row: SectionTableRow {
+                virtual_address: 0x1000,
+                virtual_size: 0x200,
+                size_of_raw_data: 0x200,
+                ..Default::default()
+            },
+            data: &file_bytes,
+        }])));
+        let optional_header = OptionalHeader {
+            standard_fields: OptionalHeaderStandardFields {
+                magic: OptionalHeaderMagic::PE32Plus,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let delay_imports = DelayImportDataDirectory::parse(
+            &file_bytes,
+            &sections,
+            &optional_header,
+            &CoffFileHeader::default(),
+        )
+        .unwrap();
+        assert_eq!(delay_imports.descriptors.len(), 2);
+        assert_eq!(
+            delay_imports.descriptors[0].bound_addresses,
+            [0x1_4000_1000, 0x1_4000_2000]
+        );
+        assert!(delay_imports.descriptors[1].bound_addresses.is_empty());
+    }
+}
@@ -7,11 +7,15 @@
 use crate::{
     containers::Table,
     error::{PewterError, Result},
-    io::{ReadData, WriteData},
-    pe::{coff::CoffFileHeader, optional_header::OptionalHeader},
+    io::{ReadData, WriteData, Writer},
+    pe::{
+        coff::CoffFileHeader,
+        optional_header::{data_directories::ImageDataDirectory, OptionalHeader},
+        options::Options,
+    },
 };
 
-use super::ParseSectionData;
+use super::{ParseSectionData, SectionTable};
 
 use crate::{string::String, vec::Vec};
 
@@ -40,54 +44,79 @@ pub struct ExportTableDataDirectory {
 
 impl ParseSectionData for ExportTableDataDirectory {
     fn parse(
+        section_data: &[u8],
+        sections: &super::Sections,
+        optional_header: &OptionalHeader,
+        coff_header: &CoffFileHeader,
+    ) -> Result<Self> {
+        Self::parse_with_options(
+            section_data,
+            sections,
+            optional_header,
+            coff_header,
+            &Options::default(),
+        )
+    }
+
+    fn parse_with_options(
         section_data: &[u8],
         sections: &super::Sections,
         _: &OptionalHeader,
         _: &CoffFileHeader,
+        options: &Options,
     ) -> Result<Self> {
         let export_directory_table = ExportDirectory::read(&mut section_data.as_ref())?;
+        if export_directory_table.address_table_entries as usize > options.max_exports
+            || export_directory_table.number_of_name_pointers as usize > options.max_exports
+        {
+            return Err(PewterError::limit_exceeded("exports", options.max_exports));
+        }
 
-        let export_address_table_data = sections
-            .find_rva_data(export_directory_table.export_address_table as usize)
-            .ok_or_else(|| {
-                PewterError::invalid_image_format(
-                    "Failed to map export_address_table_data inside image",
-                )
-            })?;
+        let table_data = |rva: u32, count: u32, message: &'static str| -> Result<&[u8]> {
+            if count == 0 {
+                return Ok(&[]);
+            }
+            sections
+                .find_rva_data(rva as usize)
+                .ok_or_else(|| PewterError::invalid_image_format(message))
+        };
 
-        let name_pointer_table_data = sections
-            .find_rva_data(export_directory_table.export_address_table as usize)
-            .ok_or_else(|| {
-                PewterError::invalid_image_format(
-                    "Failed to map name_pointer_table_data inside image",
-                )
-            })?;
+        let export_address_table_data = table_data(
+            export_directory_table.export_address_table,
+            export_directory_table.address_table_entries,
+            "Failed to map export_address_table_data inside image",
+        )?;
 
-        let export_ordinal_table_data = sections
-            .find_rva_data(export_directory_table.export_address_table as usize)
-            .ok_or_else(|| {
-                PewterError::invalid_image_format(
-                    "Failed to map export_ordinal_table_data inside image",
-                )
-            })?;
+        let name_pointer_table_data = table_data(
+            export_directory_table.name_pointer_rva,
+            export_directory_table.number_of_name_pointers,
+            "Failed to map name_pointer_table_data inside image",
+        )?;
 
-        let export_name_data = sections
-            .find_rva_data(export_directory_table.name_rva as usize)
-            .ok_or_else(|| {
-                PewterError::invalid_image_format(
-                    "Failed to map export_ordinal_table_data inside image",
-                )
-            })?;
+        let export_ordinal_table_data = table_data(
+            export_directory_table.ordinal_table_rva,
+            export_directory_table.number_of_name_pointers,
+            "Failed to map export_ordinal_table_data inside image",
+        )?;
+
+        let name_pointer_table: Table<ExportNamePointer> = Table::new_linear(
+            &mut name_pointer_table_data.as_ref(),
+            export_directory_table.number_of_name_pointers as usize,
+        )?;
 
         let export_name_table = {
-            let mut values =
-                Vec::with_capacity(export_directory_table.number_of_name_pointers as usize);
-            let mut offset = 0;
-            for _ in 0..export_directory_table.number_of_name_pointers as usize {
-                let current_str_start = &export_name_data[offset..];
-                let null_term_pos = current_str_start.iter().position(|c| *c == 0).unwrap_or(0);
-                values.push(String::from_utf8_lossy(&current_str_start[..null_term_pos]).into());
-                offset += null_term_pos;
+            let mut values = Vec::with_capacity(name_pointer_table.len());
+            for name_pointer in name_pointer_table.iter() {
+                let name_data =
+                    sections
+                        .find_rva_data(name_pointer.0 as usize)
+                        .ok_or_else(|| {
+                            PewterError::invalid_image_format(
+                                "Failed to map export name inside image",
+                            )
+                        })?;
+                let null_term_pos = name_data.iter().position(|c| *c == 0).unwrap_or(0);
+                values.push(String::from_utf8_lossy(&name_data[..null_term_pos]).into());
             }
             Table(values)
         };
@@ -97,10 +126,7 @@ impl ParseSectionData for ExportTableDataDirectory {
                 &mut export_address_table_data.as_ref(),
                 export_directory_table.address_table_entries as usize,
             )?,
-            name_pointer_table: Table::new_linear(
-                &mut name_pointer_table_data.as_ref(),
-                export_directory_table.number_of_name_pointers as usize,
-            )?,
+            name_pointer_table,
             export_ordinal_table: Table::new_linear(
                 &mut export_ordinal_table_data.as_ref(),
                 export_directory_table.number_of_name_pointers as usize,
@@ -111,6 +137,185 @@ impl ParseSectionData for ExportTableDataDirectory {
     }
 }
 
+/// The contents of an export section built by [`ExportTableDataDirectory::layout`].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct ExportSectionLayout {
+    /// The section data, starting at the `base_rva` passed to [`ExportTableDataDirectory::layout`].
+    pub data: Vec<u8>,
+    /// The location of the export data, including the tables and strings.
+    pub export_table: ImageDataDirectory,
+}
+
+impl ExportTableDataDirectory {
+    /// The ordinal of the first export written by [`ExportTableDataDirectory::layout`].
+    pub const DEFAULT_ORDINAL_BASE: u32 = 1;
+
+    /// The export address table entry for `ordinal`.
+    ///
+    /// Ordinals are biased by `ordinal_base`, so `None` is returned for ordinals below it as well
+    /// as ordinals past the end of the table.
+    pub fn address_for_ordinal(&self, ordinal: u16) -> Option<&ExportAddress> {
+        let index = (ordinal as u32).checked_sub(self.export_directory_table.ordinal_base)?;
+        if index >= self.export_directory_table.address_table_entries {
+            return None;
+        }
+        self.export_address_table.get(index as usize)
+    }
+
+    /// The number of export addresses that are forwarders to another DLL.
+    ///
+    /// `export_directory` is the
+    /// [export table data directory](crate::pe::optional_header::data_directories::DataDirectories::export_table).
+    /// An address inside it points at a forwarder string rather than code or data.
+    /// A DLL where every export is a forwarder, such as an API set, only redirects to other DLLs.
+    pub fn forwarder_count(&self, export_directory: ImageDataDirectory) -> usize {
+        self.export_address_table
+            .iter()
+            .filter(|address| {
+                address.forwarder_rva != 0 || export_directory.contains_rva(address.export_rva)
+            })
+            .count()
+    }
+
+    /// Checks that the name pointer, ordinal and name tables have the same number of entries.
+    ///
+    /// They are matched up by position, so a mismatch means the directory was parsed or built incorrectly.
+    pub fn is_consistent(&self) -> bool {
+        self.name_pointer_table.count() == self.export_ordinal_table.count()
+            && self.name_pointer_table.count() == self.export_name_table.count()
+    }
+
+    /// The named exports as `(name, ordinal, rva)`, sorted lexically by name.
+    ///
+    /// The name pointer table should already be sorted, but this does not rely on it.
+    /// See [`ExportTableDataDirectory::names_are_sorted`].
+    pub fn exports_sorted_by_name(&self) -> Vec<(String, u16, u32)> {
+        let mut exports = self.named_exports();
+        exports.sort_by(|(a, _, _), (b, _, _)| a.as_bytes().cmp(b.as_bytes()));
+        exports
+    }
+
+    /// The named exports as `(name, ordinal, rva)`, sorted by ordinal.
+    pub fn exports_sorted_by_ordinal(&self) -> Vec<(String, u16, u32)> {
+        let mut exports = self.named_exports();
+        exports.sort_by_key(|(_, ordinal, _)| *ordinal);
+        exports
+    }
+
+    /// True if the export name table is in lexical order, as the loader expects for its binary search.
+    pub fn names_are_sorted(&self) -> bool {
+        self.export_name_table
+            .windows(2)
+            .all(|names| names[0].as_bytes() <= names[1].as_bytes())
+    }
+
+    /// Pairs each name with its biased ordinal and the RVA from the export address table.
+    ///
+    /// Exports whose biased ordinal doesn't fit in a `u16` are skipped.
+    fn named_exports(&self) -> Vec<(String, u16, u32)> {
+        self.export_name_table
+            .iter()
+            .zip(self.export_ordinal_table.iter())
+            .filter_map(|(name, ExportOrtinal(index))| {
+                let address = self.export_address_table.get(*index as usize)?;
+                let ordinal = self
+                    .export_directory_table
+                    .ordinal_base
+                    .checked_add(*index as u32)
+                    .and_then(|ordinal| u16::try_from(ordinal).ok())?;
+                Some((name.clone(), ordinal, address.export_rva))
+            })
+            .collect()
+    }
+
+    /// The name of the DLL, read from `export_directory_table.name_rva`.
+    /// Returns `None` if the name is not inside a section.
+    pub fn dll_name(&self, file_bytes: &[u8], section_table: &SectionTable) -> Option<String> {
+        let name_data = section_table
+            .find_rva_data(file_bytes, self.export_directory_table.name_rva as usize)?;
+        let null_term_pos = name_data
+            .iter()
+            .position(|c| *c == 0)
+            .unwrap_or(name_data.len());
+        Some(String::from_utf8_lossy(&name_data[..null_term_pos]).into())
+    }
+
+    /// Lays out the export directory table, export address table, name pointer table,
+    /// ordinal table and name strings for `exports`, as they would be loaded at `base_rva`.
+    ///
+    /// `exports` are `(name, rva)` pairs. Ordinals are assigned in the order given starting at
+    /// [`ExportTableDataDirectory::DEFAULT_ORDINAL_BASE`], the name pointer table is sorted lexically.
+    pub fn layout(
+        dll_name: &str,
+        exports: &[(String, u32)],
+        base_rva: u32,
+    ) -> Result<ExportSectionLayout> {
+        let count = exports.len();
+        let address_table_offset = ExportDirectory::SIZE;
+        let name_pointer_offset = address_table_offset + count * 4;
+        let ordinal_table_offset = name_pointer_offset + count * 4;
+        let names_offset = ordinal_table_offset + count * 2;
+
+        let rva = |offset: usize| -> Result<u32> {
+            u32::try_from(offset)
+                .ok()
+                .and_then(|offset| base_rva.checked_add(offset))
+                .ok_or_else(|| PewterError::invalid_image_format("Export table exceeds 4GB"))
+        };
+        let number_of_exports = u32::try_from(count)
+            .map_err(|_| PewterError::invalid_image_format("Too many exports"))?;
+
+        let mut sorted: Vec<_> = exports.iter().enumerate().collect();
+        sorted.sort_by(|(_, (a, _)), (_, (b, _))| a.as_bytes().cmp(b.as_bytes()));
+
+        let mut names = Vec::new();
+        let name_rva = rva(names_offset)?;
+        names.write_slice(dll_name.as_bytes())?;
+        names.write(0u8)?;
+
+        let mut name_pointers = Vec::with_capacity(count * 4);
+        let mut ordinals = Vec::with_capacity(count * 2);
+        for (index, (name, _)) in &sorted {
+            name_pointers.write(ExportNamePointer(rva(names_offset + names.len())?))?;
+            names.write_slice(name.as_bytes())?;
+            names.write(0u8)?;
+
+            let ordinal = u16::try_from(*index)
+                .map_err(|_| PewterError::invalid_image_format("Too many exports"))?;
+            ordinals.write(ExportOrtinal(ordinal))?;
+        }
+
+        let mut data = Vec::with_capacity(names_offset + names.len());
+        data.write(ExportDirectory {
+            name_rva,
+            ordinal_base: Self::DEFAULT_ORDINAL_BASE,
+            address_table_entries: number_of_exports,
+            number_of_name_pointers: number_of_exports,
+            export_address_table: rva(address_table_offset)?,
+            name_pointer_rva: rva(name_pointer_offset)?,
+            ordinal_table_rva: rva(ordinal_table_offset)?,
+            ..Default::default()
+        })?;
+        for (_, export_rva) in exports {
+            data.write(ExportAddress {
+                export_rva: *export_rva,
+                forwarder_rva: 0,
+            })?;
+        }
+        data.write_slice(&name_pointers)?;
+        data.write_slice(&ordinals)?;
+        data.write_slice(&names)?;
+
+        Ok(ExportSectionLayout {
+            export_table: ImageDataDirectory {
+                virtual_address: base_rva,
+                size: data.len() as u32,
+            },
+            data,
+        })
+    }
+}
+
 /// The export symbol information begins with the export directory table,
 /// which describes the remainder of the export symbol information.
 /// The export directory table contains address information that is used to
@@ -145,6 +350,10 @@ pub struct ExportDirectory {
     pub ordinal_table_rva: u32,
 }
 
+impl ExportDirectory {
+    pub const SIZE: usize = 40;
+}
+
 impl ReadData for ExportDirectory {
     fn read(reader: &mut impl crate::io::Reader) -> Result<Self> {
         Ok(Self {
@@ -198,20 +407,23 @@ pub struct ExportAddress {
     pub forwarder_rva: u32,
 }
 
+/// Both formats share the same 32 bits in the image. They are read into `export_rva`,
+/// a non-zero `forwarder_rva` takes precedence when writing.
 impl ReadData for ExportAddress {
     fn read(reader: &mut impl crate::io::Reader) -> crate::error::Result<Self> {
         Ok(Self {
             export_rva: reader.read()?,
-            forwarder_rva: reader.read()?,
+            forwarder_rva: 0,
         })
     }
 }
 
 impl WriteData for ExportAddress {
     fn write_to(self, writer: &mut impl crate::io::Writer) -> crate::error::Result<()> {
-        writer.write(self.export_rva)?;
-        writer.write(self.forwarder_rva)?;
-        Ok(())
+        match self.forwarder_rva {
+            0 => writer.write(self.export_rva),
+            forwarder_rva => writer.write(forwarder_rva),
+        }
     }
 }
 
@@ -270,3 +482,219 @@ impl WriteData for ExportName {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::sections::{SectionTableRow, Sections};
+
+    #[test]
+    fn forwarder_count_of_one_forwarder() {
+        let export_table = ExportTableDataDirectory {
+            export_address_table: Table(Vec::from([0x1000, 0x3040, 0x1010].map(|export_rva| {
+                ExportAddress {
+                    export_rva,
+                    forwarder_rva: 0,
+                }
+            }))),
+            ..Default::default()
+        };
+        let export_directory = |size| ImageDataDirectory {
+            virtual_address: 0x3000,
+            size,
+        };
+        assert_eq!(export_table.forwarder_count(export_directory(0x100)), 1);
+        assert_eq!(export_table.forwarder_count(export_directory(0x40)), 0);
+    }
+
+    #[test]
+    fn mismatched_ordinal_table_is_inconsistent() {
+        let mut export_table = ExportTableDataDirectory {
+            name_pointer_table: Table(Vec::from([
+                ExportNamePointer(0x3000),
+                ExportNamePointer(0x3002),
+            ])),
+            export_ordinal_table: Table(Vec::from([ExportOrtinal(0), ExportOrtinal(1)])),
+            export_name_table: Table(Vec::from([String::from("A"), String::from("B")])),
+            ..Default::default()
+        };
+        assert!(export_table.is_consistent());
+
+        export_table.export_ordinal_table.pop();
+        assert!(!export_table.is_consistent());
+        assert!(ExportTableDataDirectory::default().is_consistent());
+    }
+
+    #[test]
+    fn address_for_ordinal_subtracts_ordinal_base() {
+        let export_table = ExportTableDataDirectory {
+            export_directory_table: ExportDirectory {
+                ordinal_base: 5,
+                address_table_entries: 2,
+                ..Default::default()
+            },
+            export_address_table: Table(Vec::from([0x1000, 0x2000].map(|export_rva| {
+                ExportAddress {
+                    export_rva,
+                    forwarder_rva: 0,
+                }
+            }))),
+            ..Default::default()
+        };
+
+        assert!(export_table.address_for_ordinal(4).is_none());
+        assert_eq!(
+            export_table.address_for_ordinal(5).map(|a| a.export_rva),
+            Some(0x1000)
+        );
+        assert_eq!(
+            export_table.address_for_ordinal(6).map(|a| a.export_rva),
+            Some(0x2000)
+        );
+        assert!(export_table.address_for_ordinal(7).is_none());
+    }
+
+    #[test]
+    fn unsorted_name_table() {
+        let export_table = ExportTableDataDirectory {
+            export_directory_table: ExportDirectory {
+                ordinal_base: 1,
+                ..Default::default()
+            },
+            export_address_table: Table(Vec::from([0x1000, 0x2000, 0x3000].map(|export_rva| {
+                ExportAddress {
+                    export_rva,
+                    forwarder_rva: 0,
+                }
+            }))),
+            export_ordinal_table: Table(Vec::from([0, 2, 1].map(ExportOrtinal))),
+            export_name_table: Table(Vec::from(["Zeta", "Alpha", "Mid"].map(String::from))),
+            ..Default::default()
+        };
+        assert!(!export_table.names_are_sorted());
+
+        assert_eq!(
+            export_table.exports_sorted_by_name(),
+            [
+                (String::from("Alpha"), 3, 0x3000),
+                (String::from("Mid"), 2, 0x2000),
+                (String::from("Zeta"), 1, 0x1000),
+            ]
+        );
+        assert_eq!(
+            export_table.exports_sorted_by_ordinal(),
+            [
+                (String::from("Zeta"), 1, 0x1000),
+                (String::from("Mid"), 2, 0x2000),
+                (String::from("Alpha"), 3, 0x3000),
+            ]
+        );
+
+        let sorted = ExportTableDataDirectory {
+            export_name_table: Table(Vec::from(["Alpha", "Mid", "Zeta"].map(String::from))),
+            ..export_table
+        };
+        assert!(sorted.names_are_sorted());
+    }
+
+    #[test]
+    fn huge_ordinal_base_skips_exports() {
+        let export_table = ExportTableDataDirectory {
+            export_directory_table: ExportDirectory {
+                ordinal_base: u32::MAX,
+                ..Default::default()
+            },
+            export_address_table: Table(Vec::from([0x1000, 0x2000].map(|export_rva| {
+                ExportAddress {
+                    export_rva,
+                    forwarder_rva: 0,
+                }
+            }))),
+            export_ordinal_table: Table(Vec::from([0, 1].map(ExportOrtinal))),
+            export_name_table: Table(Vec::from(["First", "Second"].map(String::from))),
+            ..Default::default()
+        };
+        assert!(export_table.exports_sorted_by_name().is_empty());
+        assert!(export_table.exports_sorted_by_ordinal().is_empty());
+
+        let export_table = ExportTableDataDirectory {
+            export_directory_table: ExportDirectory {
+                ordinal_base: u16::MAX as u32,
+                ..Default::default()
+            },
+            ..export_table
+        };
+        assert_eq!(
+            export_table.exports_sorted_by_ordinal(),
+            [(String::from("First"), u16::MAX, 0x1000)]
+        );
+    }
+
+    #[test]
+    fn dll_name_is_read_from_section() {
+        let layout = ExportTableDataDirectory::layout(
+            "TEST.DLL",
+            &[(String::from("Exported"), 0x1000)],
+            0x2000,
+        )
+        .unwrap();
+        let mut file_bytes = Vec::from([0u8; 0x200]);
+        file_bytes.extend_from_slice(&layout.data);
+        let section_table = SectionTable(Table(Vec::from([SectionTableRow {
+            virtual_address: 0x2000,
+            virtual_size: layout.data.len() as u32,
+            pointer_to_raw_data: 0x200,
+            size_of_raw_data: layout.data.len() as u32,
+            ..Default::default()
+        }])));
+
+        let mut export_table = ExportTableDataDirectory {
+            export_directory_table: ExportDirectory::read(&mut layout.data.as_slice()).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            export_table
+                .dll_name(&file_bytes, &section_table)
+                .as_deref(),
+            Some("TEST.DLL")
+        );
+
+        export_table.export_directory_table.name_rva = 0x8000;
+        assert!(export_table.dll_name(&file_bytes, &section_table).is_none());
+    }
+
+    #[test]
+    fn export_count_over_limit_is_an_error() {
+        let mut section_data = Vec::new();
+        section_data
+            .write(ExportDirectory {
+                address_table_entries: 0x10000,
+                export_address_table: 0x1000,
+                ..Default::default()
+            })
+            .unwrap();
+        let sections = Sections(Table::new());
+        let parse = |max_exports| {
+            ExportTableDataDirectory::parse_with_options(
+                &section_data,
+                &sections,
+                &OptionalHeader::default(),
+                &CoffFileHeader::default(),
+                &Options {
+                    max_exports,
+                    ..Default::default()
+                },
+            )
+        };
+
+        assert!(matches!(
+            parse(0xFFFF),
+            Err(PewterError::LimitExceeded { limit: 0xFFFF, .. })
+        ));
+        // Within the limit, parsing gets as far as mapping the export address table.
+        assert!(matches!(
+            parse(0x10000),
+            Err(PewterError::InvalidImageFormat { .. })
+        ));
+    }
+}
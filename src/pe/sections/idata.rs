@@ -6,10 +6,14 @@ use super::{ParseSectionData, Sections};
 use crate::{
     containers::Table,
     error::{PewterError, Result},
-    io::{ReadData, Reader, WriteData},
+    io::{ReadData, Reader, WriteData, Writer},
     pe::{
         coff::CoffFileHeader,
-        optional_header::{OptionalHeader, OptionalHeaderMagic},
+        file::definition::align_up,
+        optional_header::{
+            data_directories::ImageDataDirectory, OptionalHeader, OptionalHeaderMagic,
+        },
+        options::Options,
     },
 };
 
@@ -30,10 +34,26 @@ pub struct ImportTableDataDirectory {
 
 impl ParseSectionData for ImportTableDataDirectory {
     fn parse(
+        section_data: &[u8],
+        sections: &super::Sections,
+        optional_header: &OptionalHeader,
+        coff_header: &CoffFileHeader,
+    ) -> Result<Self> {
+        Self::parse_with_options(
+            section_data,
+            sections,
+            optional_header,
+            coff_header,
+            &Options::default(),
+        )
+    }
+
+    fn parse_with_options(
         section_data: &[u8],
         sections: &super::Sections,
         optional_header: &OptionalHeader,
         _: &CoffFileHeader,
+        options: &Options,
     ) -> Result<Self> {
         let entries = {
             let mut import_lookup_table_ptr = section_data;
@@ -48,6 +68,7 @@ impl ParseSectionData for ImportTableDataDirectory {
                     dir,
                     sections,
                     optional_header.standard_fields.magic,
+                    options.max_imports_per_dll,
                 )?);
             }
             Table(import_directory_tables)
@@ -57,6 +78,87 @@ impl ParseSectionData for ImportTableDataDirectory {
     }
 }
 
+impl ImportTableDataDirectory {
+    /// The hint/name table RVAs that are not 2 byte aligned.
+    ///
+    /// Linkers always align hint/name entries, so odd RVAs are a sign the imports were tampered with.
+    pub fn misaligned_names(&self) -> Vec<u32> {
+        self.entries
+            .iter()
+            .flat_map(|entry| entry.import_lookup_table.iter())
+            .filter_map(ImportTableRow::name_rva)
+            .filter(|name_rva| name_rva % 2 != 0)
+            .collect()
+    }
+
+    /// Adds `function` to the imports of `dll`, adding the DLL if it is not imported yet.
+    /// DLL names are compared ASCII case-insensitively, like the loader does.
+    ///
+    /// Only the in-memory model is changed. Functions imported by name have a `name_rva` of 0
+    /// until the table is written out with [`ImportTableDataDirectory::layout`].
+    pub fn add_function(&mut self, dll: &str, function: ImportName) {
+        let row = match function {
+            ImportName::ByName(name) => ImportTableRow::HintName {
+                hint: 0,
+                name_rva: 0,
+                name,
+            },
+            ImportName::ByOrdinal(ordinal) => ImportTableRow::Ordinal(ordinal),
+        };
+        match self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.dll_name.eq_ignore_ascii_case(dll))
+        {
+            Some(entry) => entry.import_lookup_table.push(row),
+            None => self.entries.push(ImportTableDataDirectoryEntry {
+                dll_name: dll.into(),
+                import_lookup_table: Table(Vec::from([row])),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// The imports as [`ImportDefinition`]s, in the same order.
+    ///
+    /// Hints are not kept, they are written as 0 by [`ImportDefinition::layout`].
+    pub fn to_definitions(&self) -> Vec<ImportDefinition> {
+        self.entries
+            .iter()
+            .map(|entry| ImportDefinition {
+                dll_name: entry.dll_name.clone(),
+                functions: entry
+                    .import_lookup_table
+                    .iter()
+                    .map(|row| match row {
+                        ImportTableRow::Ordinal(ordinal) => ImportName::ByOrdinal(*ordinal),
+                        ImportTableRow::HintName { name, .. } => ImportName::ByName(name.clone()),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Lays the imports back out contiguously as they would be loaded at `base_rva`.
+    /// See [`ImportDefinition::layout`].
+    pub fn layout(&self, base_rva: u32, magic: OptionalHeaderMagic) -> Result<ImportSectionLayout> {
+        ImportDefinition::layout(&self.to_definitions(), base_rva, magic)
+    }
+}
+
+/// Writes the import directory table: the [`ImportDirectoryTable`] of each entry, then the
+/// null entry that ends the table.
+///
+/// The lookup tables and names are not written, see [`ImportTableDataDirectory::layout`].
+impl WriteData for &ImportTableDataDirectory {
+    fn write_to(self, writer: &mut impl Writer) -> Result<()> {
+        for entry in self.entries.iter() {
+            writer.write(&entry.import_directory_table)?;
+        }
+        writer.write(&ImportDirectoryTable::default())
+    }
+}
+
 /// This is not how data is atcualy structered in the PE file.
 /// This groups the `import_directory_table` and `import_lookup_table`
 /// to the imoported dll.
@@ -73,16 +175,30 @@ pub struct ImportTableDataDirectoryEntry {
     /// The collection of these entries describes all imports from a given DLL. The last entry is set to
     /// zero (NULL) to indicate the end of the table.
     pub import_lookup_table: Table<ImportTableRow>,
+    /// The resolved virtual addresses in the import address table, one for each entry of
+    /// `import_lookup_table`. Only read if the DLL [is bound](ImportTableDataDirectoryEntry::is_bound),
+    /// otherwise the import address table is a copy of the lookup table and this is empty.
+    pub bound_addresses: Vec<u64>,
 }
 
 impl ImportTableDataDirectoryEntry {
+    /// Fails with [`PewterError::LimitExceeded`] if the import lookup table has more than
+    /// `max_imports` entries.
+    ///
+    /// Some linkers leave `import_lookup_table_rva` as zero and only fill in the import address table.
+    /// Like the loader, the import address table is then read as the lookup table.
     pub fn parse(
         import_directory_table: ImportDirectoryTable,
         sections: &Sections,
         magic: OptionalHeaderMagic,
+        max_imports: usize,
     ) -> Result<Self> {
+        let import_lookup_table_rva = match import_directory_table.import_lookup_table_rva {
+            0 => import_directory_table.import_address_table_rva,
+            rva => rva,
+        };
         let import_lookup_table_data = sections
-            .find_rva_data(import_directory_table.import_lookup_table_rva as usize)
+            .find_rva_data(import_lookup_table_rva as usize)
             .ok_or_else(|| {
                 PewterError::invalid_image_format(
                     "Failed to map import_lookup_table_rva inside image",
@@ -94,7 +210,7 @@ impl ImportTableDataDirectoryEntry {
             let mut lookup_items = Vec::new();
             loop {
                 let lookup_entry = match magic {
-                    OptionalHeaderMagic::PE32 => {
+                    OptionalHeaderMagic::PE32 | OptionalHeaderMagic::Other(_) => {
                         ImportTableRow::from_u32(sections, u32::read(&mut lookup_table_data_ptr)?)?
                     }
                     OptionalHeaderMagic::PE32Plus => {
@@ -103,6 +219,9 @@ impl ImportTableDataDirectoryEntry {
                 };
 
                 if let Some(lookup_entry) = lookup_entry {
+                    if lookup_items.len() == max_imports {
+                        return Err(PewterError::limit_exceeded("imports per DLL", max_imports));
+                    }
                     lookup_items.push(lookup_entry);
                 } else {
                     break;
@@ -123,12 +242,43 @@ impl ImportTableDataDirectoryEntry {
             String::from_utf8_lossy(&dll_name_data[..null_terminator]).into()
         };
 
+        let bound_addresses = if import_directory_table.time_date_stamp != 0 {
+            let mut address_table_ptr = sections
+                .find_rva_data(import_directory_table.import_address_table_rva as usize)
+                .ok_or_else(|| {
+                    PewterError::invalid_image_format(
+                        "Failed to map import_address_table_rva inside image",
+                    )
+                })?;
+            let mut addresses = Vec::with_capacity(import_lookup_table.len());
+            for _ in 0..import_lookup_table.len() {
+                addresses.push(match magic {
+                    OptionalHeaderMagic::PE32 | OptionalHeaderMagic::Other(_) => {
+                        u64::from(address_table_ptr.read::<u32>()?)
+                    }
+                    OptionalHeaderMagic::PE32Plus => address_table_ptr.read()?,
+                });
+            }
+            addresses
+        } else {
+            Vec::new()
+        };
+
         Ok(Self {
             import_directory_table,
             dll_name,
             import_lookup_table,
+            bound_addresses,
         })
     }
+
+    /// Whether the DLL has been bound, meaning the import address table holds the resolved
+    /// addresses of the imports instead of a copy of the lookup table.
+    ///
+    /// A `time_date_stamp` of `0xFFFFFFFF` means the real stamp is in the bound import table.
+    pub fn is_bound(&self) -> bool {
+        self.import_directory_table.time_date_stamp != 0
+    }
 }
 
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
@@ -151,6 +301,8 @@ pub struct ImportDirectoryTable {
 }
 
 impl ImportDirectoryTable {
+    pub const SIZE: usize = 20;
+
     pub fn is_null(&self) -> bool {
         self == &ImportDirectoryTable::default()
     }
@@ -200,6 +352,15 @@ impl ImportTableRow {
     pub fn is_null(&self) -> bool {
         matches!(self, Self::Ordinal(0))
     }
+
+    /// The RVA of the hint/name table entry, if imported by name.
+    pub fn name_rva(&self) -> Option<u32> {
+        match self {
+            Self::Ordinal(_) => None,
+            Self::HintName { name_rva, .. } => Some(*name_rva),
+        }
+    }
+
     fn from_lower_bits(
         sections: &Sections,
         import_by_ordinal: bool,
@@ -250,3 +411,384 @@ impl Default for ImportTableRow {
         Self::Ordinal(0)
     }
 }
+
+/// The import address table (IAT) referenced by [DataDirectories::ita](crate::pe::optional_header::data_directories::DataDirectories::ita).
+///
+/// Until the image is bound, the entries are identical to the import lookup tables.
+/// Entries are 32 bits for PE32 and 64 bits for PE32+, they are always stored as `u64` here.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct ImportAddressTableDataDirectory {
+    pub entries: Table<u64>,
+}
+
+impl ParseSectionData for ImportAddressTableDataDirectory {
+    fn parse(
+        section_data: &[u8],
+        _: &super::Sections,
+        optional_header: &OptionalHeader,
+        _: &CoffFileHeader,
+    ) -> Result<Self> {
+        let mut reader = section_data;
+        let entries = match optional_header.standard_fields.magic {
+            OptionalHeaderMagic::PE32 | OptionalHeaderMagic::Other(_) => Table::new_with_reader(
+                &mut reader,
+                section_data.len() / core::mem::size_of::<u32>(),
+                |r| r.read::<u32>().map(u64::from),
+            )?,
+            OptionalHeaderMagic::PE32Plus => Table::new_linear(
+                &mut reader,
+                section_data.len() / core::mem::size_of::<u64>(),
+            )?,
+        };
+        Ok(Self { entries })
+    }
+}
+
+/// A function imported from a DLL by [`PEImageDef::add_import`](crate::pe::file::definition::PEImageDef::add_import).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImportName {
+    /// Import by name through a hint/name table entry.
+    ByName(String),
+    /// Import by ordinal.
+    ByOrdinal(u16),
+}
+
+/// The functions imported from a single DLL.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct ImportDefinition {
+    pub dll_name: String,
+    pub functions: Vec<ImportName>,
+}
+
+/// The contents of an import section built by [`ImportDefinition::layout`].
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct ImportSectionLayout {
+    /// The section data, starting at the `base_rva` passed to [`ImportDefinition::layout`].
+    pub data: Vec<u8>,
+    /// The location of the import directory table.
+    pub import_table: ImageDataDirectory,
+    /// The location of the import address table.
+    pub import_address_table: ImageDataDirectory,
+}
+
+impl ImportDefinition {
+    /// Lays out the import directory table, import lookup tables, import address tables and
+    /// hint/name table for `imports`, as they would be loaded at `base_rva`.
+    ///
+    /// The import address tables are contiguous so they can be described by a single
+    /// [DataDirectories::ita](crate::pe::optional_header::data_directories::DataDirectories::ita).
+    pub fn layout(
+        imports: &[ImportDefinition],
+        base_rva: u32,
+        magic: OptionalHeaderMagic,
+    ) -> Result<ImportSectionLayout> {
+        let pointer_size = magic.pointer_size();
+        let ordinal_flag = 1u64 << (pointer_size * 8 - 1);
+
+        let directory_size = (imports.len() + 1) * ImportDirectoryTable::SIZE;
+        let thunks_size: usize = imports
+            .iter()
+            .map(|import| (import.functions.len() + 1) * pointer_size)
+            .sum();
+        let lookup_table_offset = align_up(directory_size, pointer_size);
+        let address_table_offset = lookup_table_offset + thunks_size;
+        let names_offset = address_table_offset + thunks_size;
+
+        let rva = |offset: usize| -> Result<u32> {
+            u32::try_from(offset)
+                .ok()
+                .and_then(|offset| base_rva.checked_add(offset))
+                .ok_or_else(|| PewterError::invalid_image_format("Import table exceeds 4GB"))
+        };
+
+        let mut directory = Vec::with_capacity(directory_size);
+        let mut thunks = Vec::with_capacity(thunks_size);
+        let mut names = Vec::new();
+        for import in imports {
+            let import_lookup_table_rva = rva(lookup_table_offset + thunks.len())?;
+            let import_address_table_rva = rva(address_table_offset + thunks.len())?;
+
+            for function in &import.functions {
+                let thunk = match function {
+                    ImportName::ByOrdinal(ordinal) => ordinal_flag | u64::from(*ordinal),
+                    ImportName::ByName(name) => {
+                        let hint_name_rva = rva(names_offset + names.len())?;
+                        names.write(0u16)?;
+                        names.write_slice(name.as_bytes())?;
+                        names.write(0u8)?;
+                        if names.len() % 2 != 0 {
+                            names.write(0u8)?;
+                        }
+                        u64::from(hint_name_rva)
+                    }
+                };
+                match magic {
+                    OptionalHeaderMagic::PE32 | OptionalHeaderMagic::Other(_) => {
+                        thunks.write(thunk as u32)?
+                    }
+                    OptionalHeaderMagic::PE32Plus => thunks.write(thunk)?,
+                }
+            }
+            thunks.write_slice(&[0u8; 8][..pointer_size])?;
+
+            let name_rva = rva(names_offset + names.len())?;
+            names.write_slice(import.dll_name.as_bytes())?;
+            names.write(0u8)?;
+            if names.len() % 2 != 0 {
+                names.write(0u8)?;
+            }
+
+            directory.write(&ImportDirectoryTable {
+                import_lookup_table_rva,
+                name_rva,
+                import_address_table_rva,
+                ..Default::default()
+            })?;
+        }
+        directory.write(&ImportDirectoryTable::default())?;
+
+        let mut data = directory;
+        data.resize(lookup_table_offset, 0);
+        // Until the image is bound, the import address table is a copy of the lookup table.
+        data.write_slice(&thunks)?;
+        data.write_slice(&thunks)?;
+        data.write_slice(&names)?;
+
+        Ok(ImportSectionLayout {
+            data,
+            import_table: ImageDataDirectory {
+                virtual_address: base_rva,
+                size: directory_size as u32,
+            },
+            import_address_table: ImageDataDirectory {
+                virtual_address: rva(address_table_offset)?,
+                size: thunks_size as u32,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::sections::{SectionRow, SectionTableRow};
+
+    #[test]
+    fn odd_name_rva_is_misaligned() {
+        let hint_name = |name_rva| ImportTableRow::HintName {
+            hint: 0,
+            name_rva,
+            name: String::from("Function"),
+        };
+        let import_table = ImportTableDataDirectory {
+            entries: Table(Vec::from([ImportTableDataDirectoryEntry {
+                import_lookup_table: Table(Vec::from([
+                    hint_name(0x2000),
+                    ImportTableRow::Ordinal(3),
+                    hint_name(0x2011),
+                ])),
+                ..Default::default()
+            }])),
+        };
+        assert_eq!(import_table.misaligned_names(), [0x2011]);
+    }
+
+    #[test]
+    fn import_lookup_table_over_limit_is_an_error() {
+        // Three ordinal imports, the terminator, then the DLL name at 0x1010.
+        let mut section_data = Vec::new();
+        for lookup_entry in [0x8000_0001u32, 0x8000_0002, 0x8000_0003, 0] {
+            section_data.write(lookup_entry).unwrap();
+        }
+        section_data.write_slice(b"TEST.dll\0").unwrap();
+        let sections = Sections(Table(Vec::from([SectionRow {
+            row: SectionTableRow {
+                virtual_address: 0x1000,
+                virtual_size: section_data.len() as u32,
+                size_of_raw_data: section_data.len() as u32,
+                ..Default::default()
+            },
+            data: &section_data,
+        }])));
+        let import_directory_table = ImportDirectoryTable {
+            import_lookup_table_rva: 0x1000,
+            name_rva: 0x1010,
+            ..Default::default()
+        };
+        let parse = |max_imports| {
+            ImportTableDataDirectoryEntry::parse(
+                import_directory_table.clone(),
+                &sections,
+                OptionalHeaderMagic::PE32,
+                max_imports,
+            )
+        };
+
+        assert_eq!(parse(3).unwrap().import_lookup_table.len(), 3);
+        assert!(matches!(
+            parse(2),
+            Err(PewterError::LimitExceeded { limit: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn add_function_then_relayout() {
+        let parse = |layout: &ImportSectionLayout| {
+            let sections = Sections(Table(Vec::from([SectionRow {
+                row: SectionTableRow {
+                    virtual_address: layout.import_table.virtual_address,
+                    virtual_size: layout.data.len() as u32,
+                    size_of_raw_data: layout.data.len() as u32,
+                    ..Default::default()
+                },
+                data: &layout.data,
+            }])));
+            ImportTableDataDirectory::parse(
+                &layout.data,
+                &sections,
+                &OptionalHeader::default(),
+                &CoffFileHeader::default(),
+            )
+            .unwrap()
+        };
+        let layout = ImportDefinition::layout(
+            &[ImportDefinition {
+                dll_name: "KERNEL32.dll".into(),
+                functions: Vec::from([ImportName::ByName("ExitProcess".into())]),
+            }],
+            0x3000,
+            OptionalHeaderMagic::PE32,
+        )
+        .unwrap();
+
+        let mut imports = parse(&layout);
+        imports.add_function("kernel32.dll", ImportName::ByName("Sleep".into()));
+        imports.add_function("USER32.dll", ImportName::ByOrdinal(7));
+        let layout = imports.layout(0x5000, OptionalHeaderMagic::PE32).unwrap();
+
+        let imports = parse(&layout);
+        assert_eq!(
+            imports.to_definitions(),
+            [
+                ImportDefinition {
+                    dll_name: "KERNEL32.dll".into(),
+                    functions: Vec::from([
+                        ImportName::ByName("ExitProcess".into()),
+                        ImportName::ByName("Sleep".into()),
+                    ]),
+                },
+                ImportDefinition {
+                    dll_name: "USER32.dll".into(),
+                    functions: Vec::from([ImportName::ByOrdinal(7)]),
+                },
+            ]
+        );
+        assert!(imports.entries[0].import_lookup_table[1]
+            .name_rva()
+            .is_some_and(|name_rva| name_rva > 0x5000));
+    }
+
+    #[test]
+    fn written_import_table_ends_with_null_entry() {
+        let import_table = ImportTableDataDirectory {
+            entries: Table(Vec::from([ImportTableDataDirectoryEntry {
+                import_directory_table: ImportDirectoryTable {
+                    import_lookup_table_rva: 0x2000,
+                    name_rva: 0x2100,
+                    import_address_table_rva: 0x2200,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }])),
+        };
+        let mut written = Vec::new();
+        written.write(&import_table).unwrap();
+
+        assert_eq!(written.len(), 2 * ImportDirectoryTable::SIZE);
+        assert_eq!(
+            ImportDirectoryTable::read(&mut written.as_slice()).unwrap(),
+            import_table.entries[0].import_directory_table
+        );
+        assert_eq!(written[ImportDirectoryTable::SIZE..], [0; 20]);
+    }
+
+    #[test]
+    fn bound_import_reads_address_table() {
+        // A lookup table with two ordinal imports at 0x1000, the bound address table at 0x100C
+        // and the DLL name at 0x1018.
+        let mut section_data = Vec::new();
+        for entry in [0x8000_0001u32, 0x8000_0002, 0, 0x7700_1000, 0x7700_2000, 0] {
+            section_data.write(entry).unwrap();
+        }
+        section_data.write_slice(b"TEST.dll\0").unwrap();
+        let sections = Sections(Table(Vec::from([SectionRow {
+            row: SectionTableRow {
+                virtual_address: 0x1000,
+                virtual_size: section_data.len() as u32,
+                size_of_raw_data: section_data.len() as u32,
+                ..Default::default()
+            },
+            data: &section_data,
+        }])));
+        let mut import_directory_table = ImportDirectoryTable {
+            import_lookup_table_rva: 0x1000,
+            name_rva: 0x1018,
+            import_address_table_rva: 0x100C,
+            ..Default::default()
+        };
+        let parse = |import_directory_table: &ImportDirectoryTable| {
+            ImportTableDataDirectoryEntry::parse(
+                import_directory_table.clone(),
+                &sections,
+                OptionalHeaderMagic::PE32,
+                Options::DEFAULT_MAX_IMPORTS_PER_DLL,
+            )
+            .unwrap()
+        };
+
+        let unbound = parse(&import_directory_table);
+        assert!(!unbound.is_bound());
+        assert!(unbound.bound_addresses.is_empty());
+
+        import_directory_table.time_date_stamp = 0x5F00_0000;
+        let bound = parse(&import_directory_table);
+        assert!(bound.is_bound());
+        assert_eq!(bound.import_lookup_table[1], ImportTableRow::Ordinal(2));
+        assert_eq!(bound.bound_addresses, [0x7700_1000, 0x7700_2000]);
+    }
+
+    #[test]
+    fn zero_lookup_table_rva_falls_back_to_iat() {
+        // An import address table with one ordinal import, the terminator, then the DLL name at 0x1008.
+        let mut section_data = Vec::new();
+        for address_entry in [0x8000_0007u32, 0] {
+            section_data.write(address_entry).unwrap();
+        }
+        section_data.write_slice(b"TEST.dll\0").unwrap();
+        let sections = Sections(Table(Vec::from([SectionRow {
+            row: SectionTableRow {
+                virtual_address: 0x1000,
+                virtual_size: section_data.len() as u32,
+                size_of_raw_data: section_data.len() as u32,
+                ..Default::default()
+            },
+            data: &section_data,
+        }])));
+        let import_directory_table = ImportDirectoryTable {
+            import_lookup_table_rva: 0,
+            name_rva: 0x1008,
+            import_address_table_rva: 0x1000,
+            ..Default::default()
+        };
+
+        let entry = ImportTableDataDirectoryEntry::parse(
+            import_directory_table,
+            &sections,
+            OptionalHeaderMagic::PE32,
+            16,
+        )
+        .unwrap();
+        assert_eq!(entry.dll_name, "TEST.dll");
+        assert_eq!(entry.import_lookup_table.0, [ImportTableRow::Ordinal(7)]);
+    }
+}
@@ -0,0 +1,25 @@
+//! The Load Configuration Structure (Image Only)
+//!
+//! The load configuration structure (IMAGE_LOAD_CONFIG_DIRECTORY) was formerly used in very limited
+//! cases in the Windows NT operating system itself to describe various features too difficult or too
+//! large to describe in the file header or optional header of the image. Current versions of the
+//! Microsoft linker and Windows XP and later versions of Windows use a new version of this structure
+//! for 32-bit x86-based systems that include reserved SEH technology.
+//!
+//! The layout of the structure grows with each version of Windows, so the raw bytes are kept as-is.
+
+use super::ParseSectionData;
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct LoadConfigDataDirectory(pub crate::vec::Vec<u8>);
+
+impl ParseSectionData for LoadConfigDataDirectory {
+    fn parse(
+        section_data: &[u8],
+        _: &super::Sections,
+        _: &crate::pe::optional_header::OptionalHeader,
+        _: &crate::pe::coff::CoffFileHeader,
+    ) -> crate::error::Result<Self> {
+        Ok(Self(crate::vec::Vec::from(section_data)))
+    }
+}
@@ -1,9 +1,14 @@
 pub mod base_relocation;
+pub mod bound_import;
 pub mod certificate;
+pub mod debug;
+pub mod delay_import;
 pub mod edata;
 pub mod idata;
+pub mod load_config;
 pub mod pdata;
 pub mod rsrc;
+pub mod tls;
 pub mod cor20;
 use crate::containers::Table;
 use crate::error::{PewterError, Result};
@@ -12,9 +17,11 @@ use bitflags::bitflags;
 use core::fmt::Debug;
 use core::ops::{Deref, DerefMut};
 
-use super::coff::CoffFileHeader;
+use super::address::{FileOffset, Rva};
+use super::coff::{CoffFileHeader, CoffRelocation};
 use super::optional_header::data_directories::ImageDataDirectory;
 use super::optional_header::OptionalHeader;
+use super::options::{Options, ParseSectionFlags};
 
 use crate::vec::Vec;
 
@@ -27,6 +34,11 @@ impl SectionTable {
         Table::new_linear(data_ptr, items_count).map(Self)
     }
 
+    /// A section table made of `rows`, in the given order.
+    pub(crate) fn from_rows(rows: Vec<SectionTableRow>) -> Self {
+        Self(Table(rows))
+    }
+
     #[inline(always)]
     pub fn new_with_reader(
         data_ptr: &mut &[u8],
@@ -48,33 +60,123 @@ impl SectionTable {
         self.iter().find(|section| section.name == check_name_buffer)
     }
 
+    /// Like [`SectionTable::get_by_name`], but compares the names ASCII case-insensitively,
+    /// so `.TEXT` finds a `.text` section.
+    pub fn get_by_name_ignore_ascii_case(&self, name: &str) -> Option<&SectionTableRow> {
+        if name.len() > 8 || self.is_empty() {
+            return None;
+        }
+        let mut check_name_buffer = [0; 8];
+        for (n, b) in name.as_bytes().iter().zip(check_name_buffer.iter_mut()) {
+            *b = *n;
+        }
+
+        self.iter()
+            .find(|section| section.name.eq_ignore_ascii_case(&check_name_buffer))
+    }
+
     #[inline(always)]
-    pub fn find_rva(&self, virtual_address: usize) -> Option<&SectionTableRow> {
+    pub fn find_rva(&self, virtual_address: impl Into<Rva>) -> Option<&SectionTableRow> {
+        let Rva(virtual_address) = virtual_address.into();
         if virtual_address == 0 {
             return None;
         }
+        self.0
+            .iter()
+            .find(|row| row.contains_rva(virtual_address as u64))
+    }
+
+    /// Computes the size of the image once loaded: the highest `virtual_address + virtual_size`
+    /// of any section, rounded up to `section_alignment`.
+    ///
+    /// The headers are mapped at RVA 0, so the image is at least `size_of_headers` even without
+    /// any sections. Saturates to `u32::MAX` if the image doesn't fit in 4GB.
+    pub fn compute_size_of_image(&self, size_of_headers: u32, section_alignment: u32) -> u32 {
+        let image_end = self
+            .iter()
+            .map(|row| row.virtual_address as u64 + row.virtual_size as u64)
+            .fold(size_of_headers as u64, u64::max);
+        let section_alignment = section_alignment.max(1) as u64;
+        u32::try_from(image_end.div_ceil(section_alignment) * section_alignment).unwrap_or(u32::MAX)
+    }
+
+    /// Whether the rows are in ascending order of `virtual_address`, as the loader expects.
+    ///
+    /// Lookups that binary search the table are only correct when this is `true`.
+    pub fn is_sorted_by_rva(&self) -> bool {
+        rows_are_sorted_by_rva(self.iter())
+    }
+
+    /// The `virtual_size - size_of_raw_data` of each section, with its index in the table.
+    ///
+    /// A large positive value (a big virtual size backed by little raw data) is typical of packers,
+    /// which unpack into the section at runtime.
+    pub fn size_discrepancies(&self) -> Vec<(usize, i64)> {
+        self.iter()
+            .enumerate()
+            .map(|(index, row)| (index, row.virtual_size as i64 - row.size_of_raw_data as i64))
+            .collect()
+    }
+
+    /// The file offset and length of the section table, for an image whose PE signature is at
+    /// `e_lfanew` and whose COFF header has a `size_of_optional_header` of `optional_header_size`.
+    pub fn raw_span(&self, e_lfanew: usize, optional_header_size: usize) -> (usize, usize) {
+        let offset =
+            e_lfanew + super::PEFile::SIGNATURE.len() + CoffFileHeader::SIZE + optional_header_size;
+        (offset, self.len() * SectionTableRow::SIZE)
+    }
+
+    /// Finds the section that contains the absolute virtual address `va`, as seen in a debugger.
+    /// Returns `None` if `va` is below `image_base`.
+    #[inline(always)]
+    pub fn find_va(&self, image_base: u64, va: u64) -> Option<&SectionTableRow> {
+        let virtual_address = va.checked_sub(image_base)?;
+        self.find_rva(u32::try_from(virtual_address).ok()?)
+    }
+
+    /// Finds the section whose raw data contains the file offset `offset`.
+    /// Sections without raw data are skipped.
+    pub fn find_file_offset(&self, offset: impl Into<FileOffset>) -> Option<&SectionTableRow> {
+        let FileOffset(offset) = offset.into();
+        let offset = offset as u64;
         self.0.iter().find(|row| {
-            virtual_address >= (row.virtual_address as usize)
-                && virtual_address < (row.virtual_address as usize + row.virtual_size as usize)
+            let start = row.pointer_to_raw_data as u64;
+            start != 0 && offset >= start && offset < start + row.size_of_raw_data as u64
         })
     }
 
+    /// Maps an RVA to an offset into the file.
+    /// Returns `None` if no section contains the RVA or the RVA is past the section's raw data.
+    #[inline(always)]
+    pub fn rva_to_file_offset(&self, virtual_address: u64) -> Option<usize> {
+        self.find_rva(u32::try_from(virtual_address).ok()?)
+            .and_then(|row| row.rva_to_file_offset(virtual_address))
+    }
+
     #[inline(always)]
     pub fn find_rva_map<T>(
         &self,
         virtual_address: usize,
         func: impl FnMut(&SectionTableRow) -> Result<T>,
     ) -> Result<Option<T>> {
-        self.find_rva(virtual_address).map(func).transpose()
+        u32::try_from(virtual_address)
+            .ok()
+            .and_then(|virtual_address| self.find_rva(virtual_address))
+            .map(func)
+            .transpose()
     }
 
+    /// The data from `virtual_address` to the end of the section that contains it.
+    ///
+    /// Returns `None` for sections with a `pointer_to_raw_data` of 0, which have no bytes in the file.
     #[inline(always)]
     pub fn find_rva_data<'a>(
         &self,
         file_bytes: &'a [u8],
         virtual_address: usize,
     ) -> Option<&'a [u8]> {
-        self.find_rva(virtual_address)
+        self.find_rva(u32::try_from(virtual_address).ok()?)
+            .filter(|section| section.pointer_to_raw_data != 0)
             .map(|section| section.get_data(file_bytes, virtual_address))
     }
 
@@ -272,11 +374,49 @@ impl SectionTableRow {
             .unwrap_or(self.name.len());
         core::str::from_utf8(&self.name[..null_terminator]).unwrap_or("")
     }
+
+    /// The size of the section when loaded: `virtual_size`, or `size_of_raw_data` if
+    /// `virtual_size` is 0, as some object files and unusual images leave it unset.
+    pub fn effective_virtual_size(&self) -> u32 {
+        match self.virtual_size {
+            0 => self.size_of_raw_data,
+            virtual_size => virtual_size,
+        }
+    }
+
+    /// Returns true if `virtual_address` is inside
+    /// `virtual_address..virtual_address + effective_virtual_size()`.
+    ///
+    /// The arithmetic is done in `u64` so it can't overflow on 32-bit hosts.
+    #[inline(always)]
+    pub fn contains_rva(&self, virtual_address: u64) -> bool {
+        let section_start = self.virtual_address as u64;
+        let section_end = section_start + self.effective_virtual_size() as u64;
+        virtual_address >= section_start && virtual_address < section_end
+    }
+
+    /// Maps an RVA inside this section to an offset into the file.
+    /// Returns `None` if the RVA is not backed by the section's raw data.
+    pub fn rva_to_file_offset(&self, virtual_address: u64) -> Option<usize> {
+        let section_offset = virtual_address.checked_sub(self.virtual_address as u64)?;
+        if section_offset >= self.size_of_raw_data as u64 {
+            return None;
+        }
+        usize::try_from(self.pointer_to_raw_data as u64 + section_offset).ok()
+    }
+
+    /// Returns the file range from `virtual_address` to the end of the section's raw data.
+    ///
+    /// Values that don't fit in a `usize` saturate to `usize::MAX`, so they fail
+    /// any later bounds check instead of wrapping around.
     pub fn get_data_range(&self, virtual_address: usize) -> (usize, usize) {
-        let section_offset = virtual_address - self.virtual_address as usize;
-        let section_start = self.pointer_to_raw_data as usize + section_offset;
-        let section_end = self.pointer_to_raw_data as usize + self.size_of_raw_data as usize;
-        (section_start, section_end)
+        let section_offset = (virtual_address as u64).saturating_sub(self.virtual_address as u64);
+        let section_start = self.pointer_to_raw_data as u64 + section_offset;
+        let section_end = self.pointer_to_raw_data as u64 + self.size_of_raw_data as u64;
+        (
+            usize::try_from(section_start).unwrap_or(usize::MAX),
+            usize::try_from(section_end).unwrap_or(usize::MAX),
+        )
     }
 
     pub fn get_data<'a>(&self, image_base: &'a [u8], virtual_address: usize) -> &'a [u8] {
@@ -293,9 +433,36 @@ impl SectionTableRow {
             return None;
         }
         let (section_start, section_end) = self.get_data_range(virtual_address);
-        (section_start < section_end && section_end < image_base.len())
+        (section_start < section_end && section_end <= image_base.len())
             .then(|| &image_base[section_start..section_end])
     }
+
+    /// The whole raw data of the section in `file_bytes`, or `None` if it is outside of the file.
+    pub fn raw_data<'a>(&self, file_bytes: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.pointer_to_raw_data as usize;
+        let end = start.checked_add(self.size_of_raw_data as usize)?;
+        file_bytes.get(start..end)
+    }
+
+    /// Reads the COFF relocations for this section from an object file.
+    ///
+    /// If the section has [`SectionFlags::LNK_NRELOC_OVFL`] set and `number_of_relocaions` is
+    /// 0xFFFF, the actual count is read from the `virtual_address` of the first relocation.
+    pub fn read_relocations(&self, file_bytes: &[u8]) -> Result<Vec<CoffRelocation>> {
+        let mut reader = file_bytes
+            .get(self.pointer_to_relocations as usize..)
+            .ok_or_else(|| PewterError::not_enough_data(self.pointer_to_relocations as usize))?;
+
+        let mut count = self.number_of_relocaions as usize;
+        if self.characteristics.contains(SectionFlags::LNK_NRELOC_OVFL) && count == 0xFFFF {
+            let overflow = CoffRelocation::read(&mut reader)?;
+            // The count includes the overflow record itself.
+            count = (overflow.virtual_address as usize).saturating_sub(1);
+        }
+
+        let Table(relocations) = Table::new_linear(&mut reader, count)?;
+        Ok(relocations)
+    }
 }
 
 impl ReadData for SectionTableRow {
@@ -331,22 +498,38 @@ impl WriteData for &SectionTableRow {
     }
 }
 
-/// Sections with their ccorrisponding data
+/// Sections with their corresponding data, borrowed from the file.
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct Sections<'a>(pub Table<SectionRow<'a>>);
 
+/// Shared by [`SectionTable::is_sorted_by_rva`] and [`Sections::is_sorted_by_rva`].
+fn rows_are_sorted_by_rva<'r>(rows: impl Iterator<Item = &'r SectionTableRow>) -> bool {
+    rows.is_sorted_by_key(|row| row.virtual_address)
+}
+
 impl<'a> Sections<'a> {
+    /// Pairs each row of `section_table` with its raw data in `file_bytes`.
+    ///
+    /// Fails if a section's raw data is not inside the file. Sections with a `pointer_to_raw_data`
+    /// of 0 have no bytes in the file, so their data is empty.
     pub fn parse(file_bytes: &'a [u8], section_table: SectionTable) -> Result<Self> {
         let SectionTable(Table(section_table_rows)) = section_table;
 
         let sections = section_table_rows
             .into_iter()
             .map(|section_row| {
+                if section_row.pointer_to_raw_data == 0 {
+                    return Ok(SectionRow {
+                        row: section_row,
+                        data: &[],
+                    });
+                }
                 let heap_start = section_row.pointer_to_raw_data as usize;
                 let heap_end = heap_start + section_row.size_of_raw_data as usize;
                 let bytes = file_bytes.get(heap_start..heap_end).ok_or_else(|| {
-                    PewterError::invalid_image_format(
+                    PewterError::invalid_image_format_at(
                         "Cant map pointer_to_raw_data for section into file",
+                        heap_start,
                     )
                 });
                 bytes.map(|b| SectionRow {
@@ -358,22 +541,34 @@ impl<'a> Sections<'a> {
         sections.map(|s| Self(Table(s)))
     }
 
+    pub fn get_by_name(&self, name: &str) -> Option<&SectionRow<'a>> {
+        self.0.iter().find(|section| section.row.name_str() == name)
+    }
+
+    /// Whether the sections are in ascending order of `virtual_address`.
+    /// See [`SectionTable::is_sorted_by_rva`].
+    pub fn is_sorted_by_rva(&self) -> bool {
+        rows_are_sorted_by_rva(self.0.iter().map(|section| &section.row))
+    }
 
     #[inline(always)]
-    pub fn find_rva(&self, virtual_address: usize) -> Option<&SectionRow<'a>> {
+    pub fn find_rva(&self, virtual_address: impl Into<Rva>) -> Option<&SectionRow<'a>> {
+        let Rva(virtual_address) = virtual_address.into();
         if virtual_address == 0 {
             return None;
         }
-        self.0.iter().find(|section| {
-            let SectionRow { row, .. } = section;
-            virtual_address >= (row.virtual_address as usize)
-                && virtual_address < (row.virtual_address as usize + row.virtual_size as usize)
-        })
+        self.0
+            .iter()
+            .find(|section| section.row.contains_rva(virtual_address as u64))
     }
 
+    /// The data from `virtual_address` to the end of the section that contains it.
+    ///
+    /// Returns `None` for sections without any raw data, such as `.bss`.
     #[inline(always)]
     pub fn find_rva_data(&self, virtual_address: usize) -> Option<&'a [u8]> {
-        self.find_rva(virtual_address)
+        self.find_rva(u32::try_from(virtual_address).ok()?)
+            .filter(|section| !section.data.is_empty())
             .map(|section| section.get_data(virtual_address))
     }
 
@@ -384,7 +579,14 @@ impl<'a> Sections<'a> {
         func: impl FnMut(&[u8]) -> Result<T>,
     ) -> Result<Option<T>> {
         self.find_rva_data(data_directory.virtual_address as usize)
-            .map(|data| &data[..data_directory.size as usize])
+            .map(|data| {
+                data.get(..data_directory.size as usize).ok_or_else(|| {
+                    PewterError::invalid_image_format(
+                        "Data directory extends past the end of its section",
+                    )
+                })
+            })
+            .transpose()?
             .map(func)
             .transpose()
     }
@@ -398,16 +600,15 @@ pub struct SectionRow<'a> {
 
 impl<'a> SectionRow<'a> {
     pub fn get_data_range(&self, virtual_address: usize) -> (usize, usize) {
-        let section_offset = virtual_address - self.row.virtual_address as usize;
-        let section_start = self.row.pointer_to_raw_data as usize + section_offset;
-        let section_end =
-            self.row.pointer_to_raw_data as usize + self.row.size_of_raw_data as usize;
-        (section_start, section_end)
+        self.row.get_data_range(virtual_address)
     }
 
+    /// Returns the data from `virtual_address` to the end of the section's raw data.
+    ///
+    /// This is empty if `virtual_address` is only backed by the section's virtual size.
     pub fn get_data(&self, virtual_address: usize) -> &'a [u8] {
-        let section_offset = virtual_address - self.row.virtual_address as usize;
-        &self.data[section_offset..]
+        let section_offset = virtual_address.saturating_sub(self.row.virtual_address as usize);
+        self.data.get(section_offset..).unwrap_or_default()
     }
 }
 
@@ -427,17 +628,30 @@ pub trait ParseSectionData: Sized {
         optional_header: &OptionalHeader,
         coff_header: &CoffFileHeader,
     ) -> Result<Self>;
+
+    /// Parse with the limits in `options`.
+    /// Tables without any limits use [`ParseSectionData::parse`].
+    #[inline(always)]
+    fn parse_with_options(
+        section_data: &[u8],
+        sections: &Sections,
+        optional_header: &OptionalHeader,
+        coff_header: &CoffFileHeader,
+        _options: &Options,
+    ) -> Result<Self> {
+        Self::parse(section_data, sections, optional_header, coff_header)
+    }
 }
 
 /// Sections parsed from [OptionalHeader::data_directories](super::optional_header::OptionalHeader::data_directories).
 ///
-/// If the section is not specigied in [Options::parse_sections](crate::Options::parse_sections), it will be `None`.
+/// If the section is not specigied in [Options::parse_special_sections](crate::Options::parse_special_sections), it will be `None`.
 #[derive(Debug, Clone, Default, PartialEq)]
 pub struct SpecialSections {
     /// .edata section
     pub export_table: Option<edata::ExportTableDataDirectory>,
     /// .rsrc
-    pub resource_table: Option<Vec<u8>>,
+    pub resource_table: Option<rsrc::ResourceDataDirectory>,
     /// .idata section
     pub import_table: Option<idata::ImportTableDataDirectory>,
     /// .pdata section
@@ -446,12 +660,108 @@ pub struct SpecialSections {
     pub certificate_table: Option<certificate::CertificateDataDirectory>,
     /// The base relocation table address and size
     pub relocation_table: Option<base_relocation::BaseRelocationDataDitectory>,
+    /// .debug section
+    pub debug: Option<debug::DebugDataDirectory>,
+    /// Raw bytes of the architecture data directory, which is reserved and should be empty.
+    pub architecture: Option<Vec<u8>>,
+    /// The RVA of the value to be stored in the global pointer register.
+    pub global_ptr: Option<u32>,
+    /// .tls section
+    pub tls_table: Option<tls::TlsDirectory>,
+    /// The load configuration structure
+    pub load_config_table: Option<load_config::LoadConfigDataDirectory>,
+    /// The bound import table
+    pub bound_import: Option<bound_import::BoundImportDataDirectory>,
+    /// The import address table
+    pub import_address_table: Option<idata::ImportAddressTableDataDirectory>,
+    /// The delay-load import tables
+    pub delay_import_table: Option<delay_import::DelayImportDataDirectory>,
+    /// The CLR runtime header
+    pub clr_runtime_header: Option<cor20::ImageCor20Header>,
 }
 
+impl SpecialSections {
+    /// Parse every data directory that is set in [`Options::parse_special_sections`].
+    /// Directories that are null or can't be mapped to a section are left as `None`.
+    ///
+    /// [`ParseSectionFlags::RESERVED`] has nothing to parse and is ignored.
+    /// The certificate table is read from `file_bytes`, as its address is a file offset.
+    pub fn parse_tables(
+        file_bytes: &[u8],
+        sections: &Sections,
+        optional_header: &OptionalHeader,
+        coff_header: &CoffFileHeader,
+        options: &Options,
+    ) -> Result<Self> {
+        let data_directories = &optional_header.data_directories;
+        let parse_flags = options.parse_special_sections;
+
+        macro_rules! parse_table {
+            ($flag:ident, $data_directory:ident) => {
+                if parse_flags.contains(ParseSectionFlags::$flag) {
+                    sections.find_data_directory_data_map(
+                        &data_directories.$data_directory,
+                        |data| {
+                            ParseSectionData::parse_with_options(
+                                data,
+                                sections,
+                                optional_header,
+                                coff_header,
+                                options,
+                            )
+                        },
+                    )?
+                } else {
+                    None
+                }
+            };
+        }
+
+        let architecture = if parse_flags.contains(ParseSectionFlags::ARCHITECTURE) {
+            sections.find_data_directory_data_map(&data_directories.architecture, |data| {
+                Ok(Vec::from(data))
+            })?
+        } else {
+            None
+        };
+
+        let certificate_table = if parse_flags.contains(ParseSectionFlags::CERTIFICATE_TABLE) {
+            certificate::CertificateDataDirectory::parse_at_file_offset(
+                file_bytes,
+                &data_directories.certificate_table,
+            )?
+        } else {
+            None
+        };
+
+        let global_ptr = (parse_flags.contains(ParseSectionFlags::GLOBAL_PTR)
+            && data_directories.global_ptr.virtual_address != 0)
+            .then_some(data_directories.global_ptr.virtual_address);
+
+        Ok(Self {
+            export_table: parse_table!(EXPORT_TABLE, export_table),
+            resource_table: parse_table!(RESOURCE_TABLE, resource_table),
+            import_table: parse_table!(IMPORT_TABLE, import_table),
+            exception_table: parse_table!(EXCEPTION_TABLE, exception_table),
+            certificate_table,
+            relocation_table: parse_table!(BASE_RELOCATION_TABLE, base_relocation_table),
+            debug: parse_table!(DEBUG, debug),
+            architecture,
+            global_ptr,
+            tls_table: parse_table!(TLS_TABLE, tls_table),
+            load_config_table: parse_table!(LOAD_CONFIG_TABLE, load_config_table),
+            bound_import: parse_table!(BOUND_TABLE, bound_import),
+            import_address_table: parse_table!(ITA, ita),
+            delay_import_table: parse_table!(DELAY_IMPORT_DESCRIPTOR, delay_import_descriptor),
+            clr_runtime_header: parse_table!(CLR_RUNTIME_HEADER, clr_runtime_header),
+        })
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::io::Writer;
     use crate::vec;
 
     #[test]
@@ -475,4 +785,360 @@ mod tests {
         assert!(text_section.is_some());
         assert_eq!(section_table.get_by_name(".text"), section_table.last())
     }
+
+    #[test]
+    fn find_section_by_va() {
+        const IMAGE_BASE: u64 = 0x140000000;
+        let section_table = SectionTable(Table(vec![SectionTableRow {
+            name: [b'.', b't', b'e', b'x', b't', 0, 0, 0],
+            virtual_address: 0x1000,
+            virtual_size: 0x2000,
+            ..Default::default()
+        }]));
+
+        assert_eq!(
+            section_table.find_va(IMAGE_BASE, 0x140001234),
+            section_table.first()
+        );
+        assert!(section_table.find_va(IMAGE_BASE, 0x140003000).is_none());
+        assert!(section_table.find_va(IMAGE_BASE, 0x1234).is_none());
+    }
+
+    #[test]
+    fn out_of_order_sections_are_not_sorted() {
+        let row = |virtual_address| SectionTableRow {
+            virtual_address,
+            ..Default::default()
+        };
+        let mut section_table = SectionTable(Table(vec![row(0x2000), row(0x1000)]));
+        assert!(!section_table.is_sorted_by_rva());
+
+        section_table.reverse();
+        assert!(section_table.is_sorted_by_rva());
+    }
+
+    #[test]
+    fn size_discrepancy_of_packed_section() {
+        let section_table = SectionTable(Table(vec![
+            SectionTableRow {
+                virtual_size: 0x10000,
+                size_of_raw_data: 0x200,
+                ..Default::default()
+            },
+            SectionTableRow {
+                virtual_size: 0x100,
+                size_of_raw_data: 0x200,
+                ..Default::default()
+            },
+        ]));
+        assert_eq!(
+            section_table.size_discrepancies(),
+            [(0, 0xFE00), (1, -0x100)]
+        );
+    }
+
+    fn test_sections(file_bytes: &[u8]) -> Sections<'_> {
+        Sections::parse(
+            file_bytes,
+            SectionTable(Table(vec![SectionTableRow {
+                name: [b'.', b'd', b'a', b't', b'a', 0, 0, 0],
+                virtual_address: 0x1000,
+                virtual_size: 0x2000,
+                pointer_to_raw_data: 0x10,
+                size_of_raw_data: 0x10,
+                ..Default::default()
+            }])),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn raw_data_is_size_of_raw_data_long() {
+        let file_bytes: Vec<u8> = (0..0x20).collect();
+        let mut row = SectionTableRow {
+            virtual_address: 0x1000,
+            pointer_to_raw_data: 0x10,
+            size_of_raw_data: 0x8,
+            ..Default::default()
+        };
+        assert_eq!(row.raw_data(&file_bytes), Some(&file_bytes[0x10..0x18]));
+
+        row.size_of_raw_data = 0x11;
+        assert!(row.raw_data(&file_bytes).is_none());
+    }
+
+    #[test]
+    fn raw_span_covers_section_table() {
+        let data = include_bytes!("../../../fixtures/minimal_pe32.exe");
+        let headers = crate::pe::PEFile::parse_headers(data).unwrap();
+
+        let (offset, len) = headers.section_table.raw_span(
+            headers.dos_header.e_lfanew as usize,
+            headers.coff_header.size_of_optional_header as usize,
+        );
+        assert_eq!(len, headers.coff_header.number_of_sections as usize * 40);
+        let mut rows = Vec::new();
+        headers.section_table.write_to(&mut rows).unwrap();
+        assert_eq!(&data[offset..offset + len], rows);
+    }
+
+    #[test]
+    fn bss_section_has_no_rva_data() {
+        let file_bytes: Vec<u8> = (0..0x20).collect();
+        let section_table = SectionTable(Table(vec![SectionTableRow {
+            name: *b".bss\0\0\0\0",
+            virtual_address: 0x1000,
+            virtual_size: 0x100,
+            size_of_raw_data: 0x10,
+            pointer_to_raw_data: 0,
+            characteristics: SectionFlags::CNT_UNINITIALIZED_DATA,
+            ..Default::default()
+        }]));
+        assert!(section_table.find_rva(0x1004).is_some());
+        assert_eq!(section_table.find_rva_data(&file_bytes, 0x1004), None);
+
+        let sections = Sections::parse(&file_bytes, section_table).unwrap();
+        assert!(sections.0[0].data.is_empty());
+        assert_eq!(sections.find_rva_data(0x1004), None);
+    }
+
+    #[test]
+    fn zero_virtual_size_uses_raw_size() {
+        let section_table = SectionTable(Table(vec![SectionTableRow {
+            virtual_address: 0x1000,
+            virtual_size: 0,
+            size_of_raw_data: 0x200,
+            pointer_to_raw_data: 0x400,
+            ..Default::default()
+        }]));
+        assert_eq!(section_table[0].effective_virtual_size(), 0x200);
+        assert!(section_table.find_rva(0x11FF).is_some());
+        assert!(section_table.find_rva(0x1200).is_none());
+    }
+
+    #[test]
+    fn sections_borrow_raw_data() {
+        let file_bytes: Vec<u8> = (0..0x20).collect();
+        let sections = test_sections(&file_bytes);
+        assert_eq!(sections.0[0].data, &file_bytes[0x10..]);
+        assert_eq!(sections.get_by_name(".data"), sections.0.first());
+        assert!(sections.get_by_name(".text").is_none());
+
+        let out_of_file = SectionTable(Table(vec![SectionTableRow {
+            pointer_to_raw_data: 0x18,
+            size_of_raw_data: 0x10,
+            ..Default::default()
+        }]));
+        assert!(Sections::parse(&file_bytes, out_of_file).is_err());
+    }
+
+    #[test]
+    fn sections_find_rva_data() {
+        let file_bytes: Vec<u8> = (0..0x20).collect();
+        let sections = test_sections(&file_bytes);
+        assert_eq!(sections.find_rva(Rva(0x1004)), sections.0.first());
+        assert!(sections.find_rva(Rva(0)).is_none());
+        assert!(sections.find_rva(Rva(0x3000)).is_none());
+
+        assert_eq!(sections.find_rva_data(0x1004), Some(&file_bytes[0x14..]));
+        // Past the raw data but inside the virtual size.
+        assert_eq!(sections.find_rva_data(0x1800), Some([].as_slice()));
+    }
+
+    #[test]
+    fn sections_find_data_directory_data_map() {
+        let file_bytes: Vec<u8> = (0..0x20).collect();
+        let sections = test_sections(&file_bytes);
+        let data_directory = ImageDataDirectory {
+            virtual_address: 0x1002,
+            size: 4,
+        };
+        assert_eq!(
+            sections
+                .find_data_directory_data_map(&data_directory, |data| Ok(Vec::from(data)))
+                .unwrap(),
+            Some(vec![0x12, 0x13, 0x14, 0x15])
+        );
+
+        let too_large = ImageDataDirectory {
+            virtual_address: 0x1002,
+            size: 0x20,
+        };
+        assert!(sections
+            .find_data_directory_data_map(&too_large, |_| Ok(()))
+            .is_err());
+        assert_eq!(
+            sections
+                .find_data_directory_data_map(&ImageDataDirectory::default(), |_| Ok(()))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn find_section_by_file_offset() {
+        let section_table = SectionTable(Table(vec![
+            SectionTableRow {
+                name: [b'.', b'b', b's', b's', 0, 0, 0, 0],
+                size_of_raw_data: 0x200,
+                ..Default::default()
+            },
+            SectionTableRow {
+                name: [b'.', b't', b'e', b'x', b't', 0, 0, 0],
+                pointer_to_raw_data: 0x400,
+                size_of_raw_data: 0x200,
+                ..Default::default()
+            },
+        ]));
+
+        assert_eq!(
+            section_table.find_file_offset(FileOffset(0x450)),
+            section_table.get_by_name(".text")
+        );
+        assert!(section_table.find_file_offset(FileOffset(0x10)).is_none());
+        assert!(section_table.find_file_offset(FileOffset(0x600)).is_none());
+    }
+
+    #[test]
+    fn compute_size_of_image_from_sections() {
+        let section_table = SectionTable(Table(vec![
+            SectionTableRow {
+                virtual_address: 0x1000,
+                virtual_size: 0x1800,
+                ..Default::default()
+            },
+            SectionTableRow {
+                virtual_address: 0x3000,
+                virtual_size: 0x10,
+                ..Default::default()
+            },
+        ]));
+        assert_eq!(section_table.compute_size_of_image(0x400, 0x1000), 0x4000);
+        assert_eq!(section_table.compute_size_of_image(0x400, 0x200), 0x3200);
+        assert_eq!(
+            SectionTable::default().compute_size_of_image(0x400, 0x1000),
+            0x1000
+        );
+    }
+
+    #[test]
+    fn get_by_name_ignore_ascii_case() {
+        let section_table = SectionTable(Table(vec![SectionTableRow {
+            name: *b".text\0\0\0",
+            ..Default::default()
+        }]));
+        assert!(section_table.get_by_name(".TEXT").is_none());
+        assert_eq!(
+            section_table.get_by_name_ignore_ascii_case(".TEXT"),
+            section_table.get_by_name(".text")
+        );
+        assert!(section_table.get_by_name(".text").is_some());
+        assert_eq!(section_table.get_by_name_ignore_ascii_case(".TEX"), None);
+    }
+
+    #[test]
+    fn parse_tables_populates_requested_debug() {
+        use crate::io::Writer;
+        use crate::pe::optional_header::data_directories::DataDirectories;
+
+        let mut section_data = Vec::new();
+        for debug_type in [debug::DebugType::CodeView, debug::DebugType::Repro] {
+            section_data
+                .write(&debug::DebugDirectory {
+                    debug_type,
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+
+        let sections = Sections(Table(vec![SectionRow {
+            row: SectionTableRow {
+                virtual_address: 0x1000,
+                virtual_size: section_data.len() as u32,
+                size_of_raw_data: section_data.len() as u32,
+                ..Default::default()
+            },
+            data: &section_data,
+        }]));
+        let optional_header = OptionalHeader {
+            data_directories: DataDirectories {
+                debug: ImageDataDirectory {
+                    virtual_address: 0x1000,
+                    size: section_data.len() as u32,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let special_sections = SpecialSections::parse_tables(
+            &[],
+            &sections,
+            &optional_header,
+            &CoffFileHeader::default(),
+            &Options {
+                parse_special_sections: ParseSectionFlags::DEBUG,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let debug = special_sections.debug.unwrap();
+        assert_eq!(debug.entries.len(), 2);
+        assert_eq!(debug.entries[1].debug_type, debug::DebugType::Repro);
+
+        let special_sections = SpecialSections::parse_tables(
+            &[],
+            &sections,
+            &optional_header,
+            &CoffFileHeader::default(),
+            &Options::minimal(),
+        )
+        .unwrap();
+        assert!(special_sections.debug.is_none());
+    }
+
+    #[test]
+    fn rva_above_4gb_is_not_truncated() {
+        let section_table = SectionTable(Table(vec![SectionTableRow {
+            virtual_address: 0x1000,
+            virtual_size: 0x1000,
+            pointer_to_raw_data: 0x400,
+            size_of_raw_data: 0x200,
+            ..Default::default()
+        }]));
+
+        assert_eq!(section_table.rva_to_file_offset(0x1010), Some(0x410));
+        // 0x1_0000_1010 truncated to 32 bits would land inside the section.
+        assert_eq!(section_table.rva_to_file_offset(0x1_0000_1010), None);
+        assert!(!section_table[0].contains_rva(0x1_0000_1010));
+        // Inside the virtual size but past the raw data.
+        assert_eq!(section_table.rva_to_file_offset(0x1800), None);
+    }
+
+    #[test]
+    fn read_two_coff_relocations() {
+        let relocations = [
+            CoffRelocation {
+                virtual_address: 0x10,
+                symbol_table_index: 3,
+                type_: 0x4,
+            },
+            CoffRelocation {
+                virtual_address: 0x24,
+                symbol_table_index: 7,
+                type_: 0x1,
+            },
+        ];
+        let mut file_bytes = Vec::from([0xCCu8; 0x20]);
+        for relocation in &relocations {
+            file_bytes.write(relocation).unwrap();
+        }
+        let row = SectionTableRow {
+            pointer_to_relocations: 0x20,
+            number_of_relocaions: 2,
+            ..Default::default()
+        };
+
+        assert_eq!(row.read_relocations(&file_bytes).unwrap(), relocations);
+    }
 }
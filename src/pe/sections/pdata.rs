@@ -4,6 +4,7 @@
 //! The target platform determines which of the three function table entry format variations described below is used.
 
 use crate::{
+    containers::Table,
     error::Result,
     io::{ReadData, WriteData},
     pe::coff::{CoffFileHeader, ImageFileMachine},
@@ -11,13 +12,16 @@ use crate::{
 
 use super::ParseSectionData;
 
+use crate::vec::Vec;
+
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub enum ExceptionHandlerDataDirectory {
     #[default]
     Unsupported,
-    Mips32(Mips32ExceptionHandlerTable),
-    ArmPowerPCSH4WindowsCE(ArmPowerPCSH4WindowsCEExceptionHandlerTable),
-    X64(X64ExceptionHandlerTable),
+    Mips32(Table<Mips32ExceptionHandlerTable>),
+    ArmPowerPCSH4WindowsCE(Table<ArmPowerPCSH4WindowsCEExceptionHandlerTable>),
+    X64(Table<X64ExceptionHandlerTable>),
+    Arm64(Table<Arm64ExceptionHandlerTable>),
 }
 
 impl ParseSectionData for ExceptionHandlerDataDirectory {
@@ -29,31 +33,87 @@ impl ParseSectionData for ExceptionHandlerDataDirectory {
     ) -> Result<Self> {
         let mut reader = section_data;
         let val = match coff_header.machine {
-            ImageFileMachine::MipsFPU | ImageFileMachine::R4000 => Self::Mips32(
-                Mips32ExceptionHandlerTable::read(&mut reader)?,
-            ),
+            ImageFileMachine::MipsFPU | ImageFileMachine::R4000 => Self::Mips32(Table::new_linear(
+                &mut reader,
+                section_data.len() / Mips32ExceptionHandlerTable::SIZE,
+            )?),
             ImageFileMachine::Arm | ImageFileMachine::PowerPC | ImageFileMachine::PowerPCFP => {
-                Self::ArmPowerPCSH4WindowsCE(ArmPowerPCSH4WindowsCEExceptionHandlerTable::read(
+                Self::ArmPowerPCSH4WindowsCE(Table::new_linear(
                     &mut reader,
+                    section_data.len() / ArmPowerPCSH4WindowsCEExceptionHandlerTable::SIZE,
                 )?)
             }
-            ImageFileMachine::Arm64 | ImageFileMachine::RiscV64 | ImageFileMachine::Amd64 => {
-                Self::X64(X64ExceptionHandlerTable::read(&mut reader)?)
-            }
+            ImageFileMachine::Arm64 => Self::Arm64(Table::new_linear(
+                &mut reader,
+                section_data.len() / Arm64ExceptionHandlerTable::SIZE,
+            )?),
+            ImageFileMachine::RiscV64 | ImageFileMachine::Amd64 => Self::X64(Table::new_linear(
+                &mut reader,
+                section_data.len() / X64ExceptionHandlerTable::SIZE,
+            )?),
             _ => Self::Unsupported,
         };
         Ok(val)
     }
 }
 
+impl ExceptionHandlerDataDirectory {
+    /// Returns `(begin_address, end_address)` for each function in the table,
+    /// regardless of the machine specific layout.
+    ///
+    /// The ARM/PowerPC/SH4 layout only stores the number of instructions, so the
+    /// end address is computed from the instruction length. It is `None` if that overflows.
+    /// ARM64 functions with their unwind data in `.xdata` have an end address of `None`,
+    /// read the [`Arm64XdataHeader`] to get their length.
+    pub fn function_ranges(&self) -> Vec<(u32, Option<u32>)> {
+        match self {
+            Self::Mips32(table) => table
+                .iter()
+                .map(|exc| (exc.begin_address, Some(exc.end_address)))
+                .collect(),
+            Self::ArmPowerPCSH4WindowsCE(table) => table
+                .iter()
+                .map(|exc| (exc.begin_address, exc.end_address()))
+                .collect(),
+            Self::X64(table) => table
+                .iter()
+                .map(|exc| (exc.begin_address, Some(exc.end_address)))
+                .collect(),
+            Self::Arm64(table) => table
+                .iter()
+                .map(|exc| (exc.begin_address, exc.end_address()))
+                .collect(),
+            Self::Unsupported => Vec::new(),
+        }
+    }
+}
+
 impl WriteData for &ExceptionHandlerDataDirectory {
     fn write_to(self, writer: &mut impl crate::io::Writer) -> Result<()> {
         match self {
-            ExceptionHandlerDataDirectory::Mips32(exc) => writer.write(exc),
-            ExceptionHandlerDataDirectory::ArmPowerPCSH4WindowsCE(exec) => writer.write(exec),
-            ExceptionHandlerDataDirectory::X64(exec) => writer.write(exec),
-            ExceptionHandlerDataDirectory::Unsupported => Ok(()),
+            ExceptionHandlerDataDirectory::Mips32(table) => {
+                for exc in table.iter() {
+                    writer.write(exc)?;
+                }
+            }
+            ExceptionHandlerDataDirectory::ArmPowerPCSH4WindowsCE(table) => {
+                for exec in table.iter() {
+                    writer.write(exec)?;
+                }
+            }
+            ExceptionHandlerDataDirectory::X64(table) => {
+                for exec in table.iter() {
+                    writer.write(exec)?;
+                }
+            }
+            ExceptionHandlerDataDirectory::Arm64(table) => {
+                for exec in table.iter() {
+                    writer.write(exec)?;
+                }
+            }
+            ExceptionHandlerDataDirectory::Unsupported => {}
         }
+        Ok(())
     }
 }
 
@@ -71,6 +131,10 @@ pub struct Mips32ExceptionHandlerTable {
     pub prolog_end_address: u32,
 }
 
+impl Mips32ExceptionHandlerTable {
+    pub const SIZE: usize = 20;
+}
+
 impl ReadData for Mips32ExceptionHandlerTable {
     fn read(reader: &mut impl crate::io::Reader) -> crate::error::Result<Self> {
         Ok(Self {
@@ -101,6 +165,21 @@ pub enum ExceptionHandlerInstructionLength {
     Is32Bit,
 }
 
+impl ExceptionHandlerInstructionLength {
+    /// Decode the instruction length bit, where a set bit means 32-bit instructions.
+    pub fn from_bit(is_32_bit: bool) -> Self {
+        match is_32_bit {
+            true => Self::Is32Bit,
+            false => Self::Is16Bit,
+        }
+    }
+
+    /// The value of the instruction length bit.
+    pub fn bit(&self) -> bool {
+        matches!(self, Self::Is32Bit)
+    }
+}
+
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub struct ArmPowerPCSH4WindowsCEExceptionHandlerTable {
     /// The VA of the corresponding function.
@@ -117,6 +196,21 @@ pub struct ArmPowerPCSH4WindowsCEExceptionHandlerTable {
     pub exception_hander_exists: bool,
 }
 
+impl ArmPowerPCSH4WindowsCEExceptionHandlerTable {
+    pub const SIZE: usize = 8;
+
+    /// The end of the function, computed from `function_length` and `instruction_length`.
+    pub fn end_address(&self) -> Option<u32> {
+        let instruction_size = match self.instruction_length {
+            ExceptionHandlerInstructionLength::Is16Bit => 2,
+            ExceptionHandlerInstructionLength::Is32Bit => 4,
+        };
+        self.function_length
+            .checked_mul(instruction_size)
+            .and_then(|length| self.begin_address.checked_add(length))
+    }
+}
+
 impl ReadData for ArmPowerPCSH4WindowsCEExceptionHandlerTable {
     fn read(reader: &mut impl crate::io::Reader) -> crate::error::Result<Self> {
         let begin_address: u32 = reader.read()?;
@@ -129,10 +223,8 @@ impl ReadData for ArmPowerPCSH4WindowsCEExceptionHandlerTable {
         let function_length = (other_fields >> 2) & 0xc34ff;
 
         // 1 bit
-        let instruction_length = match ((other_fields >> 1) & 1) == 1 {
-            true => ExceptionHandlerInstructionLength::Is32Bit,
-            false => ExceptionHandlerInstructionLength::Is16Bit,
-        };
+        let instruction_length =
+            ExceptionHandlerInstructionLength::from_bit(((other_fields >> 1) & 1) == 1);
 
         let exception_hander_exists = (other_fields & 1) == 1;
 
@@ -153,10 +245,7 @@ impl WriteData for &ArmPowerPCSH4WindowsCEExceptionHandlerTable {
         let mut other_fields = 0;
         other_fields |= (self.prolog_length as u32) << 24;
         other_fields |= (self.function_length) << 2;
-        other_fields |= match self.instruction_length {
-            ExceptionHandlerInstructionLength::Is16Bit => 0,
-            ExceptionHandlerInstructionLength::Is32Bit => 1u32 << 1,
-        };
+        other_fields |= (self.instruction_length.bit() as u32) << 1;
         other_fields |= self.exception_hander_exists as u32;
 
         writer.write(other_fields)?;
@@ -174,6 +263,10 @@ pub struct X64ExceptionHandlerTable {
     pub unwind_infomation: u32,
 }
 
+impl X64ExceptionHandlerTable {
+    pub const SIZE: usize = 12;
+}
+
 impl ReadData for X64ExceptionHandlerTable {
     fn read(reader: &mut impl crate::io::Reader) -> crate::error::Result<Self> {
         Ok(Self {
@@ -192,3 +285,297 @@ impl WriteData for &X64ExceptionHandlerTable {
         Ok(())
     }
 }
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct Arm64ExceptionHandlerTable {
+    /// The RVA of the corresponding function.
+    pub begin_address: u32,
+    /// Either the packed unwind data, or the RVA of the `.xdata` record.
+    /// The low 2 bits select which, see [`Arm64ExceptionHandlerTable::unwind_info`].
+    pub unwind_data: u32,
+}
+
+impl Arm64ExceptionHandlerTable {
+    pub const SIZE: usize = 8;
+
+    /// Decodes [`Self::unwind_data`].
+    pub fn unwind_info(&self) -> Arm64UnwindInfo {
+        match self.unwind_data & 0b11 {
+            0 => Arm64UnwindInfo::Xdata {
+                unwind_data_rva: self.unwind_data,
+            },
+            flag @ (1 | 2) => Arm64UnwindInfo::Packed(Arm64PackedUnwindData {
+                is_fragment: flag == 2,
+                function_length: ((self.unwind_data >> 2) & 0x7FF) as u16,
+                reg_f: ((self.unwind_data >> 13) & 0b111) as u8,
+                reg_i: ((self.unwind_data >> 16) & 0xF) as u8,
+                homes_parameters: (self.unwind_data >> 20) & 1 == 1,
+                cr: ((self.unwind_data >> 21) & 0b11) as u8,
+                frame_size: ((self.unwind_data >> 23) & 0x1FF) as u16,
+            }),
+            _ => Arm64UnwindInfo::Reserved(self.unwind_data),
+        }
+    }
+
+    /// The end of the function, if the unwind data is packed.
+    pub fn end_address(&self) -> Option<u32> {
+        match self.unwind_info() {
+            Arm64UnwindInfo::Packed(packed) => self
+                .begin_address
+                .checked_add(packed.function_length as u32 * 4),
+            Arm64UnwindInfo::Xdata { .. } | Arm64UnwindInfo::Reserved(_) => None,
+        }
+    }
+}
+
+impl ReadData for Arm64ExceptionHandlerTable {
+    fn read(reader: &mut impl crate::io::Reader) -> crate::error::Result<Self> {
+        Ok(Self {
+            begin_address: reader.read()?,
+            unwind_data: reader.read()?,
+        })
+    }
+}
+
+impl WriteData for &Arm64ExceptionHandlerTable {
+    fn write_to(self, writer: &mut impl crate::io::Writer) -> crate::error::Result<()> {
+        writer.write(self.begin_address)?;
+        writer.write(self.unwind_data)?;
+        Ok(())
+    }
+}
+
+/// The unwind data of an ARM64 function, from [`Arm64ExceptionHandlerTable::unwind_info`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Arm64UnwindInfo {
+    /// The unwind data is stored in an `.xdata` record at `unwind_data_rva`,
+    /// which starts with an [`Arm64XdataHeader`].
+    Xdata { unwind_data_rva: u32 },
+    /// The unwind data is packed into the `.pdata` entry.
+    Packed(Arm64PackedUnwindData),
+    /// A flag of 3, which is reserved. Contains the raw unwind data.
+    Reserved(u32),
+}
+
+/// Unwind data for functions with a canonical prolog and epilog, packed into 30 bits.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct Arm64PackedUnwindData {
+    /// Set if the entry describes a fragment of a function without a prolog (flag 2),
+    /// clear for a whole function (flag 1).
+    pub is_fragment: bool,
+    /// The length of the function in 4 byte units.
+    pub function_length: u16,
+    /// The number of non-volatile floating point registers (d8-d15) saved, minus one.
+    /// 0 if no floating point registers are saved.
+    pub reg_f: u8,
+    /// The number of non-volatile integer registers (x19-x28) saved.
+    pub reg_i: u8,
+    /// If set, the function homes the integer parameter registers (x0-x7).
+    pub homes_parameters: bool,
+    /// Whether the function saves lr, and sets up a frame chain with fp and lr.
+    pub cr: u8,
+    /// The size of the stack frame in 16 byte units.
+    pub frame_size: u16,
+}
+
+/// The header at the start of an ARM64 `.xdata` record.
+///
+/// It is followed by the epilog scopes, the unwind codes and, if
+/// [`exception_data_present`](Self::exception_data_present) is set, the exception handler data.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct Arm64XdataHeader {
+    /// The length of the function in 4 byte units.
+    pub function_length: u32,
+    /// The version of the record, currently 0.
+    pub version: u8,
+    /// If set, exception handler data follows the unwind codes.
+    pub exception_data_present: bool,
+    /// If set, there is a single epilog and [`Self::epilog_count`] is the index of its first
+    /// unwind code instead of the number of epilog scopes.
+    pub single_epilog_packed: bool,
+    /// The number of epilog scopes, or the index of the epilog's first unwind code.
+    pub epilog_count: u16,
+    /// The number of 32-bit words of unwind codes.
+    pub code_words: u8,
+}
+
+impl ReadData for Arm64XdataHeader {
+    /// Reads the header word, and the extension word if the epilog count and code words are
+    /// both 0.
+    fn read(reader: &mut impl crate::io::Reader) -> crate::error::Result<Self> {
+        let header: u32 = reader.read()?;
+        let mut epilog_count = ((header >> 22) & 0x1F) as u16;
+        let mut code_words = ((header >> 27) & 0x1F) as u8;
+        if epilog_count == 0 && code_words == 0 {
+            let extension: u32 = reader.read()?;
+            epilog_count = (extension & 0xFFFF) as u16;
+            code_words = ((extension >> 16) & 0xFF) as u8;
+        }
+        Ok(Self {
+            function_length: header & 0x3FFFF,
+            version: ((header >> 18) & 0b11) as u8,
+            exception_data_present: (header >> 20) & 1 == 1,
+            single_epilog_packed: (header >> 21) & 1 == 1,
+            epilog_count,
+            code_words,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::Writer;
+
+    #[test]
+    fn x64_function_ranges() {
+        let table =
+            ExceptionHandlerDataDirectory::X64(Table(Vec::from([X64ExceptionHandlerTable {
+                begin_address: 0x1000,
+                end_address: 0x1040,
+                unwind_infomation: 0x5000,
+            }])));
+        assert_eq!(table.function_ranges(), [(0x1000, Some(0x1040))]);
+    }
+
+    #[test]
+    fn mips_function_ranges() {
+        let table = ExceptionHandlerDataDirectory::Mips32(Table(Vec::from([
+            Mips32ExceptionHandlerTable {
+                begin_address: 0x2000,
+                end_address: 0x2100,
+                ..Default::default()
+            },
+        ])));
+        assert_eq!(table.function_ranges(), [(0x2000, Some(0x2100))]);
+    }
+
+    #[test]
+    fn arm_function_ranges_use_instruction_length() {
+        let table = ExceptionHandlerDataDirectory::ArmPowerPCSH4WindowsCE(Table(Vec::from([
+            ArmPowerPCSH4WindowsCEExceptionHandlerTable {
+                begin_address: 0x3000,
+                function_length: 0x10,
+                instruction_length: ExceptionHandlerInstructionLength::Is32Bit,
+                ..Default::default()
+            },
+        ])));
+        assert_eq!(table.function_ranges(), [(0x3000, Some(0x3040))]);
+        assert!(ExceptionHandlerDataDirectory::Unsupported
+            .function_ranges()
+            .is_empty());
+    }
+
+    #[test]
+    fn arm64_packed_unwind_data() {
+        // A function of 0x20 instructions that saves lr, x19 and x20 in a 0x40 byte frame.
+        let entry = Arm64ExceptionHandlerTable {
+            begin_address: 0x1000,
+            unwind_data: 1 | (0x20 << 2) | (2 << 16) | (3 << 21) | (4 << 23),
+        };
+        assert_eq!(
+            entry.unwind_info(),
+            Arm64UnwindInfo::Packed(Arm64PackedUnwindData {
+                is_fragment: false,
+                function_length: 0x20,
+                reg_f: 0,
+                reg_i: 2,
+                homes_parameters: false,
+                cr: 3,
+                frame_size: 4,
+            })
+        );
+        assert_eq!(entry.end_address(), Some(0x1080));
+    }
+
+    #[test]
+    fn arm64_xdata_unwind_data() {
+        let mut section_data = Vec::new();
+        section_data
+            .write(&Arm64ExceptionHandlerTable {
+                begin_address: 0x1000,
+                unwind_data: 0x5000,
+            })
+            .unwrap();
+        let coff_header = CoffFileHeader {
+            machine: ImageFileMachine::Arm64,
+            ..Default::default()
+        };
+        let table = ExceptionHandlerDataDirectory::parse(
+            &section_data,
+            &Default::default(),
+            &Default::default(),
+            &coff_header,
+        )
+        .unwrap();
+        let ExceptionHandlerDataDirectory::Arm64(entries) = &table else {
+            panic!("expected ARM64 exception table");
+        };
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].unwind_info(),
+            Arm64UnwindInfo::Xdata {
+                unwind_data_rva: 0x5000
+            }
+        );
+        assert_eq!(table.function_ranges(), [(0x1000, None)]);
+
+        // 0x40 instructions, exception data, one epilog scope and two words of unwind codes.
+        let header: u32 = 0x40 | (1 << 20) | (1 << 22) | (2 << 27);
+        let xdata_header = Arm64XdataHeader::read(&mut header.to_le_bytes().as_slice()).unwrap();
+        assert_eq!(
+            xdata_header,
+            Arm64XdataHeader {
+                function_length: 0x40,
+                version: 0,
+                exception_data_present: true,
+                single_epilog_packed: false,
+                epilog_count: 1,
+                code_words: 2,
+            }
+        );
+
+        let mut extended = Vec::new();
+        extended.write(0x40u32).unwrap();
+        extended.write(40u32 | (200 << 16)).unwrap();
+        let xdata_header = Arm64XdataHeader::read(&mut extended.as_slice()).unwrap();
+        assert_eq!(xdata_header.epilog_count, 40);
+        assert_eq!(xdata_header.code_words, 200);
+    }
+
+    #[test]
+    fn parse_reads_every_x64_entry() {
+        let mut section_data = Vec::new();
+        for i in 0..3u32 {
+            section_data
+                .write(&X64ExceptionHandlerTable {
+                    begin_address: 0x1000 * (i + 1),
+                    end_address: 0x1000 * (i + 1) + 0x20,
+                    unwind_infomation: 0x8000 + i * 8,
+                })
+                .unwrap();
+        }
+        let coff_header = CoffFileHeader {
+            machine: ImageFileMachine::Amd64,
+            ..Default::default()
+        };
+
+        let table = ExceptionHandlerDataDirectory::parse(
+            &section_data,
+            &Default::default(),
+            &Default::default(),
+            &coff_header,
+        )
+        .unwrap();
+        let ExceptionHandlerDataDirectory::X64(entries) = &table else {
+            panic!("expected X64 exception table");
+        };
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2].begin_address, 0x3000);
+        assert_eq!(entries[2].unwind_infomation, 0x8010);
+
+        let mut written = Vec::new();
+        written.write(&table).unwrap();
+        assert_eq!(written, section_data);
+    }
+}
@@ -14,17 +14,459 @@
 //! determines Name ID, and the third table determines Language ID.
 
 use super::ParseSectionData;
+use crate::{
+    collections::BTreeSet,
+    error::{PewterError, Result},
+    io::{ReadData, Reader, WriteData, Writer},
+    string::String,
+    vec::Vec,
+};
 
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
-pub struct ResourceDataDirectory(crate::vec::Vec<u8>);
+pub struct ResourceDataDirectory {
+    data: crate::vec::Vec<u8>,
+    /// The RVA of the resource table, used to find the data of [`ResourceDataEntry::data_rva`].
+    virtual_address: u32,
+}
 
 impl ParseSectionData for ResourceDataDirectory {
     fn parse(
         section_data: &[u8],
         _: &super::Sections,
-        _: &crate::pe::optional_header::OptionalHeader,
+        optional_header: &crate::pe::optional_header::OptionalHeader,
         _: &crate::pe::coff::CoffFileHeader,
     ) -> crate::error::Result<Self> {
-        Ok(Self(crate::vec::Vec::from(section_data)))
+        Ok(Self {
+            data: crate::vec::Vec::from(section_data),
+            virtual_address: optional_header
+                .data_directories
+                .resource_table
+                .virtual_address,
+        })
+    }
+}
+
+impl ResourceDataDirectory {
+    /// Tables deeper than this are not followed. Windows only uses three levels, and the limit
+    /// bounds the recursion on malformed tables.
+    const MAX_DEPTH: usize = 8;
+    /// The resource type of string tables.
+    pub const RT_STRING: u32 = 6;
+    /// The number of strings in each [`Self::RT_STRING`] bundle.
+    const STRINGS_PER_BUNDLE: u16 = 16;
+
+    /// Walks the resource tree and returns every leaf.
+    ///
+    /// Each directory table may only be referenced once. A table shared between several entries,
+    /// or one that points back at itself, is an error, so the walk is bounded by the size of the
+    /// resource table.
+    pub fn data_entries(&self) -> Result<Vec<ResourceDataEntry>> {
+        let mut data_entries = Vec::new();
+        self.collect_data_entries(0, 0, &mut BTreeSet::new(), &mut data_entries)?;
+        Ok(data_entries)
+    }
+
+    fn collect_data_entries(
+        &self,
+        table_offset: usize,
+        depth: usize,
+        visited_tables: &mut BTreeSet<usize>,
+        data_entries: &mut Vec<ResourceDataEntry>,
+    ) -> Result<()> {
+        if depth >= Self::MAX_DEPTH {
+            return Err(PewterError::invalid_image_format(
+                "Resource directory is nested too deeply",
+            ));
+        }
+        if !visited_tables.insert(table_offset) {
+            return Err(PewterError::invalid_image_format(
+                "Resource directory table is referenced more than once",
+            ));
+        }
+        for entry in self.read_directory_entries(table_offset)? {
+            if entry.is_subdirectory() {
+                self.collect_data_entries(
+                    entry.target_offset(),
+                    depth + 1,
+                    visited_tables,
+                    data_entries,
+                )?;
+            } else {
+                data_entries.push(self.data_at(entry.target_offset())?.read()?);
+            }
+        }
+        Ok(())
+    }
+
+    /// The string resource `id`, as loaded by `LoadString`.
+    ///
+    /// Strings are stored in [`Self::RT_STRING`] bundles of 16, the bundle with name ID
+    /// `id / 16 + 1` holds the string at position `id % 16`. The first language of the bundle is used.
+    /// Returns `None` if the string is missing, empty or its data is outside of the resource table.
+    pub fn string(&self, id: u16) -> Option<String> {
+        self.read_string(id).ok().flatten()
+    }
+
+    fn read_string(&self, id: u16) -> Result<Option<String>> {
+        let bundle_id = (id / Self::STRINGS_PER_BUNDLE) as u32 + 1;
+        let Some(string_tables) = self.find_subdirectory(0, Self::RT_STRING)? else {
+            return Ok(None);
+        };
+        let Some(languages) = self.find_subdirectory(string_tables, bundle_id)? else {
+            return Ok(None);
+        };
+        let Some(bundle) = self
+            .read_directory_entries(languages)?
+            .into_iter()
+            .find(|entry| !entry.is_subdirectory())
+        else {
+            return Ok(None);
+        };
+        let data_entry: ResourceDataEntry = self.data_at(bundle.target_offset())?.read()?;
+        let mut bundle_data = data_entry
+            .data_rva
+            .checked_sub(self.virtual_address)
+            .and_then(|offset| {
+                self.data
+                    .get(offset as usize..)?
+                    .get(..data_entry.size as usize)
+            })
+            .ok_or_else(|| {
+                PewterError::invalid_image_format("String bundle is outside of the resource table")
+            })?;
+
+        for _ in 0..id % Self::STRINGS_PER_BUNDLE {
+            let length: u16 = bundle_data.read()?;
+            bundle_data.read_slice(length as usize * 2)?;
+        }
+        let length: u16 = bundle_data.read()?;
+        if length == 0 {
+            return Ok(None);
+        }
+        let units = bundle_data.read_slice(length as usize * 2)?;
+        let string = char::decode_utf16(
+            units
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]])),
+        )
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+        Ok(Some(string))
+    }
+
+    /// The offset of the subdirectory with integer ID `id` in the table at `table_offset`.
+    fn find_subdirectory(&self, table_offset: usize, id: u32) -> Result<Option<usize>> {
+        Ok(self
+            .read_directory_entries(table_offset)?
+            .into_iter()
+            .find(|entry| entry.id() == Some(id) && entry.is_subdirectory())
+            .map(|entry| entry.target_offset()))
+    }
+
+    /// Reads the directory table at `table_offset` and the entries that follow it.
+    fn read_directory_entries(&self, table_offset: usize) -> Result<Vec<ResourceDirectoryEntry>> {
+        let mut table_data = self.data_at(table_offset)?;
+        let table: ResourceDirectoryTable = table_data.read()?;
+        let entry_count =
+            table.number_of_name_entries as usize + table.number_of_id_entries as usize;
+        (0..entry_count).map(|_| table_data.read()).collect()
+    }
+
+    fn data_at(&self, offset: usize) -> Result<&[u8]> {
+        self.data.get(offset..).ok_or_else(|| {
+            PewterError::invalid_image_format("Resource offset is outside of the resource table")
+        })
+    }
+}
+
+/// Each resource directory table has the following format.
+/// The table is followed by `number_of_name_entries + number_of_id_entries` [`ResourceDirectoryEntry`]s.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct ResourceDirectoryTable {
+    /// Resource flags. This field is reserved for future use. It is currently set to zero.
+    pub characteristics: u32,
+    /// The time that the resource data was created by the resource compiler.
+    pub time_date_stamp: u32,
+    /// The major version number, set by the user.
+    pub major_version: u16,
+    /// The minor version number, set by the user.
+    pub minor_version: u16,
+    /// The number of directory entries immediately following the table that use strings to
+    /// identify Type, Name, or Language entries (depending on the level of the table).
+    pub number_of_name_entries: u16,
+    /// The number of directory entries immediately following the Name entries that use numeric
+    /// IDs for Type, Name, or Language entries.
+    pub number_of_id_entries: u16,
+}
+
+impl ResourceDirectoryTable {
+    pub const SIZE: usize = 16;
+}
+
+impl ReadData for ResourceDirectoryTable {
+    fn read(reader: &mut impl Reader) -> Result<Self> {
+        Ok(Self {
+            characteristics: reader.read()?,
+            time_date_stamp: reader.read()?,
+            major_version: reader.read()?,
+            minor_version: reader.read()?,
+            number_of_name_entries: reader.read()?,
+            number_of_id_entries: reader.read()?,
+        })
+    }
+}
+
+impl WriteData for &ResourceDirectoryTable {
+    fn write_to(self, writer: &mut impl Writer) -> Result<()> {
+        writer.write(self.characteristics)?;
+        writer.write(self.time_date_stamp)?;
+        writer.write(self.major_version)?;
+        writer.write(self.minor_version)?;
+        writer.write(self.number_of_name_entries)?;
+        writer.write(self.number_of_id_entries)?;
+        Ok(())
+    }
+}
+
+/// A directory entry points to either a resource data entry (a leaf) or another resource
+/// directory table (the next level down).
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct ResourceDirectoryEntry {
+    /// Either an integer ID, or the offset of a string that gives the name, if the high bit is set.
+    pub name_offset_or_id: u32,
+    /// The offset of a resource data entry, or of another resource directory table if the high bit is set.
+    pub offset: u32,
+}
+
+impl ResourceDirectoryEntry {
+    pub const SIZE: usize = 8;
+    const HIGH_BIT: u32 = 0x8000_0000;
+
+    /// The integer ID of the entry, or `None` if it is identified by name.
+    pub fn id(&self) -> Option<u32> {
+        (self.name_offset_or_id & Self::HIGH_BIT == 0).then_some(self.name_offset_or_id)
+    }
+
+    /// True if [`Self::offset`] points to another resource directory table.
+    pub fn is_subdirectory(&self) -> bool {
+        self.offset & Self::HIGH_BIT != 0
+    }
+
+    /// The offset of the table or data entry, from the start of the resource table.
+    pub fn target_offset(&self) -> usize {
+        (self.offset & !Self::HIGH_BIT) as usize
+    }
+}
+
+impl ReadData for ResourceDirectoryEntry {
+    fn read(reader: &mut impl Reader) -> Result<Self> {
+        Ok(Self {
+            name_offset_or_id: reader.read()?,
+            offset: reader.read()?,
+        })
+    }
+}
+
+impl WriteData for &ResourceDirectoryEntry {
+    fn write_to(self, writer: &mut impl Writer) -> Result<()> {
+        writer.write(self.name_offset_or_id)?;
+        writer.write(self.offset)?;
+        Ok(())
+    }
+}
+
+/// The leaves of the resource tree. Each one describes the actual unit of raw data.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct ResourceDataEntry {
+    /// The address of a unit of resource data in the Resource Data area.
+    pub data_rva: u32,
+    /// The size, in bytes, of the resource data that is pointed to by `data_rva`.
+    pub size: u32,
+    /// The code page that is used to decode code point values within the resource data.
+    /// Typically, the code page would be the Unicode code page.
+    pub code_page: u32,
+    /// Reserved, must be 0.
+    pub reserved: u32,
+}
+
+impl ResourceDataEntry {
+    pub const SIZE: usize = 16;
+    /// The code page of UTF-16LE, which Windows uses for resources without a code page.
+    pub const CODE_PAGE_UTF16_LE: u32 = 1200;
+
+    /// The code page of the resource data. A [`Self::code_page`] of 0 is read as UTF-16LE
+    /// ([`Self::CODE_PAGE_UTF16_LE`]).
+    pub fn code_page(&self) -> u32 {
+        match self.code_page {
+            0 => Self::CODE_PAGE_UTF16_LE,
+            code_page => code_page,
+        }
+    }
+}
+
+impl ReadData for ResourceDataEntry {
+    fn read(reader: &mut impl Reader) -> Result<Self> {
+        Ok(Self {
+            data_rva: reader.read()?,
+            size: reader.read()?,
+            code_page: reader.read()?,
+            reserved: reader.read()?,
+        })
+    }
+}
+
+impl WriteData for &ResourceDataEntry {
+    fn write_to(self, writer: &mut impl Writer) -> Result<()> {
+        writer.write(self.data_rva)?;
+        writer.write(self.size)?;
+        writer.write(self.code_page)?;
+        writer.write(self.reserved)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A resource table with a single leaf at `type_id / name_id / language_id`.
+    fn single_resource_table(
+        type_id: u32,
+        name_id: u32,
+        language_id: u32,
+        data_entry: &ResourceDataEntry,
+    ) -> Vec<u8> {
+        const LEVEL_SIZE: usize = ResourceDirectoryTable::SIZE + ResourceDirectoryEntry::SIZE;
+        let mut data = Vec::new();
+        for (level, id) in [type_id, name_id, language_id].into_iter().enumerate() {
+            let next_offset = (LEVEL_SIZE * (level + 1)) as u32;
+            data.write(&ResourceDirectoryTable {
+                number_of_id_entries: 1,
+                ..Default::default()
+            })
+            .unwrap();
+            data.write(&ResourceDirectoryEntry {
+                name_offset_or_id: id,
+                offset: if level < 2 {
+                    next_offset | ResourceDirectoryEntry::HIGH_BIT
+                } else {
+                    next_offset
+                },
+            })
+            .unwrap();
+        }
+        data.write(data_entry).unwrap();
+        data
+    }
+
+    #[test]
+    fn data_entry_code_page_is_preserved() {
+        let data_entry = ResourceDataEntry {
+            data_rva: 0x2000,
+            size: 0x10,
+            code_page: 1252,
+            reserved: 0,
+        };
+        let resources = ResourceDataDirectory {
+            data: single_resource_table(6, 1, 0x409, &data_entry),
+            virtual_address: 0x2000,
+        };
+
+        let data_entries = resources.data_entries().unwrap();
+        assert_eq!(data_entries, [data_entry]);
+        assert_eq!(data_entries[0].code_page(), 1252);
+
+        let unicode = ResourceDataEntry::default();
+        assert_eq!(unicode.code_page(), ResourceDataEntry::CODE_PAGE_UTF16_LE);
+    }
+
+    #[test]
+    fn self_referencing_table_is_an_error() {
+        let mut data = Vec::new();
+        data.write(&ResourceDirectoryTable {
+            number_of_id_entries: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        data.write(&ResourceDirectoryEntry {
+            name_offset_or_id: 1,
+            offset: ResourceDirectoryEntry::HIGH_BIT,
+        })
+        .unwrap();
+        let resources = ResourceDataDirectory {
+            data,
+            ..Default::default()
+        };
+        assert!(resources.data_entries().is_err());
+    }
+
+    #[test]
+    fn shared_subtable_is_an_error() {
+        // Two levels whose entries all point at the next table, which would expand to 4 leaves.
+        const TABLE_SIZE: u32 =
+            (ResourceDirectoryTable::SIZE + 2 * ResourceDirectoryEntry::SIZE) as u32;
+        let mut data = Vec::new();
+        for next_table in [
+            TABLE_SIZE | ResourceDirectoryEntry::HIGH_BIT,
+            2 * TABLE_SIZE,
+        ] {
+            data.write(&ResourceDirectoryTable {
+                number_of_id_entries: 2,
+                ..Default::default()
+            })
+            .unwrap();
+            for id in 1..=2 {
+                data.write(&ResourceDirectoryEntry {
+                    name_offset_or_id: id,
+                    offset: next_table,
+                })
+                .unwrap();
+            }
+        }
+        data.write(&ResourceDataEntry::default()).unwrap();
+
+        let resources = ResourceDataDirectory {
+            data,
+            ..Default::default()
+        };
+        assert!(resources.data_entries().is_err());
+    }
+
+    #[test]
+    fn string_from_bundle() {
+        const VIRTUAL_ADDRESS: u32 = 0x3000;
+        // String 18 is the third string of bundle 2.
+        let mut bundle = Vec::new();
+        for position in 0..16 {
+            let string = match position {
+                1 => "Skipped",
+                2 => "Hello",
+                _ => "",
+            };
+            bundle.write(string.len() as u16).unwrap();
+            for unit in string.encode_utf16() {
+                bundle.write(unit).unwrap();
+            }
+        }
+
+        let data_offset = single_resource_table(0, 0, 0, &ResourceDataEntry::default()).len();
+        let data_entry = ResourceDataEntry {
+            data_rva: VIRTUAL_ADDRESS + data_offset as u32,
+            size: bundle.len() as u32,
+            ..Default::default()
+        };
+        let mut data =
+            single_resource_table(ResourceDataDirectory::RT_STRING, 2, 0x409, &data_entry);
+        data.extend_from_slice(&bundle);
+        let resources = ResourceDataDirectory {
+            data,
+            virtual_address: VIRTUAL_ADDRESS,
+        };
+
+        assert_eq!(resources.string(18).as_deref(), Some("Hello"));
+        assert_eq!(resources.string(17).as_deref(), Some("Skipped"));
+        assert_eq!(resources.string(16), None);
+        assert_eq!(resources.string(19), None);
+        assert_eq!(resources.string(2), None);
     }
 }
@@ -0,0 +1,167 @@
+//! The .tls Section
+//!
+//! The .tls section provides direct PE and COFF support for static thread local storage (TLS).
+//! TLS is a special storage class that Windows supports in which a data object is not an automatic
+//! (stack) variable, yet is local to each individual thread that runs the code.
+use crate::{
+    error::Result,
+    io::Reader,
+    pe::{
+        coff::CoffFileHeader,
+        optional_header::{OptionalHeader, OptionalHeaderMagic},
+    },
+    vec::Vec,
+};
+
+use super::{ParseSectionData, SectionTable};
+
+/// The TLS directory has the same layout in PE32 and PE32+, except the first four
+/// fields are 32 bits in PE32 and 64 bits in PE32+. They are always stored as `u64` here.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct TlsDirectory {
+    /// The starting address of the TLS template. The template is a block of data that is used
+    /// to initialize TLS data. The system copies all of this data each time a thread is created,
+    /// so it must not be corrupted. Note that this address is not an RVA; it is an address for
+    /// which there should be a base relocation in the .reloc section.
+    pub raw_data_start_va: u64,
+    /// The address of the last byte of the TLS, except for the zero fill. As with the
+    /// `raw_data_start_va` field, this is a VA, not an RVA.
+    pub raw_data_end_va: u64,
+    /// The location to receive the TLS index, which the loader assigns. This location
+    /// is in an ordinary data section, so it can be given a symbolic name that is accessible to the program.
+    pub address_of_index: u64,
+    /// The pointer to an array of TLS callback functions. The array is null-terminated,
+    /// so if no callback function is supported, this field points to 4 bytes set to zero.
+    pub address_of_callbacks: u64,
+    /// The size in bytes of the template, beyond the initialized data delimited by the
+    /// `raw_data_start_va` and `raw_data_end_va` fields. The total template size should be
+    /// the same as the total size of TLS data in the image file. The zero fill is the amount
+    /// of data that comes after the initialized nonzero data.
+    pub size_of_zero_fill: u32,
+    /// The four bits \[23:20\] describe alignment info. Possible values are those defined as
+    /// IMAGE_SCN_ALIGN_*, which are also used to describe alignment of section in object files.
+    /// The other 28 bits are reserved for future use.
+    pub characteristics: u32,
+}
+
+impl ParseSectionData for TlsDirectory {
+    fn parse(
+        section_data: &[u8],
+        _: &super::Sections,
+        optional_header: &OptionalHeader,
+        _: &CoffFileHeader,
+    ) -> Result<Self> {
+        let mut reader = section_data;
+        let read_address = |reader: &mut &[u8]| -> Result<u64> {
+            match optional_header.standard_fields.magic {
+                OptionalHeaderMagic::PE32 | OptionalHeaderMagic::Other(_) => {
+                    reader.read::<u32>().map(u64::from)
+                }
+                OptionalHeaderMagic::PE32Plus => reader.read(),
+            }
+        };
+
+        Ok(Self {
+            raw_data_start_va: read_address(&mut reader)?,
+            raw_data_end_va: read_address(&mut reader)?,
+            address_of_index: read_address(&mut reader)?,
+            address_of_callbacks: read_address(&mut reader)?,
+            size_of_zero_fill: reader.read()?,
+            characteristics: reader.read()?,
+        })
+    }
+}
+
+impl TlsDirectory {
+    /// The RVAs of the TLS callbacks, which run before the entry point.
+    ///
+    /// The array at `address_of_callbacks` holds VAs, so `image_base` is subtracted from each one.
+    /// Entries are 32 bits for PE32 and 64 bits for PE32+. Reading stops at the null terminator,
+    /// the end of the section's raw data, or an address that is not inside the image.
+    pub fn callbacks(
+        &self,
+        file_bytes: &[u8],
+        section_table: &SectionTable,
+        image_base: u64,
+        magic: OptionalHeaderMagic,
+    ) -> Vec<u32> {
+        let mut callbacks = Vec::new();
+        let Some(mut reader) = section_table
+            .find_va(image_base, self.address_of_callbacks)
+            .and_then(|row| {
+                let rva = self.address_of_callbacks - image_base;
+                row.try_get_data(file_bytes, rva as usize)
+            })
+        else {
+            return callbacks;
+        };
+
+        loop {
+            let callback_va = match magic {
+                OptionalHeaderMagic::PE32 | OptionalHeaderMagic::Other(_) => {
+                    reader.read::<u32>().map(u64::from)
+                }
+                OptionalHeaderMagic::PE32Plus => reader.read(),
+            };
+            let Some(callback_rva) = callback_va
+                .ok()
+                .filter(|va| *va != 0)
+                .and_then(|va| va.checked_sub(image_base))
+                .and_then(|rva| u32::try_from(rva).ok())
+            else {
+                break;
+            };
+            callbacks.push(callback_rva);
+        }
+        callbacks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{containers::Table, io::Writer, pe::sections::SectionTableRow};
+
+    #[test]
+    fn callbacks_are_rebased_to_rvas() {
+        const IMAGE_BASE: u64 = 0x400000;
+        let mut file_bytes = Vec::from([0u8; 0x10]);
+        for callback_va in [0x401010u32, 0x401020, 0, 0] {
+            file_bytes.write(callback_va).unwrap();
+        }
+        let section_table = SectionTable(Table(Vec::from([SectionTableRow {
+            virtual_address: 0x2000,
+            virtual_size: 0x100,
+            pointer_to_raw_data: 0x10,
+            size_of_raw_data: 0x10,
+            ..Default::default()
+        }])));
+        let tls_directory = TlsDirectory {
+            address_of_callbacks: IMAGE_BASE + 0x2000,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            tls_directory.callbacks(
+                &file_bytes,
+                &section_table,
+                IMAGE_BASE,
+                OptionalHeaderMagic::PE32
+            ),
+            [0x1010, 0x1020]
+        );
+
+        let unmapped = TlsDirectory {
+            address_of_callbacks: IMAGE_BASE + 0x3000,
+            ..Default::default()
+        };
+        assert!(unmapped
+            .callbacks(
+                &file_bytes,
+                &section_table,
+                IMAGE_BASE,
+                OptionalHeaderMagic::PE32
+            )
+            .is_empty());
+    }
+}